@@ -0,0 +1,86 @@
+//! Compares lookups in the default `BTreeMap` storage against the `hashmap`
+//! and `small` features. Run each with:
+//!
+//! ```text
+//! cargo bench --bench lookup
+//! cargo bench --bench lookup --features hashmap
+//! cargo bench --bench lookup --features small
+//! ```
+
+use gotham_state::State;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+
+macro_rules! types {
+	($($name:ident),+) => {
+		$(struct $name(#[allow(dead_code)] u64);)+
+
+		fn fill(state: &mut State) {
+			$(state.put($name(0));)+
+		}
+
+		fn borrow_all(state: &State) {
+			$(black_box(state.borrow::<$name>());)+
+		}
+	};
+}
+
+types!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+
+/// A typical per-request container holding a few values.
+fn small(state: &mut State) {
+	state.put(A(0));
+	state.put(B(0));
+	state.put(C(0));
+	state.put(D(0));
+}
+
+fn borrow_small(state: &State) {
+	black_box(state.borrow::<A>());
+	black_box(state.borrow::<D>());
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+	let start = Instant::now();
+	for _ in 0..ITERATIONS {
+		f();
+	}
+	let elapsed = start.elapsed();
+	println!(
+		"{:<10} {:>8.2} ns/iter",
+		name,
+		elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+	);
+}
+
+fn main() {
+	let backend = if cfg!(feature = "hashmap") {
+		"HashMap"
+	} else {
+		"BTreeMap"
+	};
+	let inline = if cfg!(feature = "small") {
+		" with inline entries"
+	} else {
+		""
+	};
+	println!("{} storage{}", backend, inline);
+	bench("small", || {
+		let mut state = State::default();
+		small(&mut state);
+		borrow_small(black_box(&state));
+	});
+	let mut state = State::default();
+	fill(&mut state);
+	bench("borrow 16", || borrow_all(black_box(&state)));
+	bench("put 16", || fill(black_box(&mut state)));
+	bench("has", || {
+		black_box(state.has::<P>());
+	});
+	let key = state.key::<P>().expect("P was put");
+	bench("get key", || {
+		black_box(state.get(black_box(key)));
+	});
+}
@@ -0,0 +1,86 @@
+//! Measures `put`, `borrow` and `take` for containers holding different
+//! numbers of entries, for each storage backend. Run each with:
+//!
+//! ```text
+//! cargo bench --bench operations
+//! cargo bench --bench operations --features hashmap
+//! cargo bench --bench operations --features small
+//! ```
+//!
+//! With the `bench-internals` feature, the storage counters gathered while
+//! running each case are printed after its timing.
+
+use gotham_state::State;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 200_000;
+
+struct V<const N: usize>(#[allow(dead_code)] u64);
+
+macro_rules! cases {
+	($($count:literal => [$($n:literal),+]),+ $(,)?) => {
+		const CASES: &[(usize, fn(&mut State), fn(&State), fn(&mut State))] = &[$((
+			$count,
+			|state| {
+				$(state.put(V::<$n>(0));)+
+			},
+			|state| {
+				$(black_box(state.borrow::<V<$n>>());)+
+			},
+			|state| {
+				$(black_box(state.take::<V<$n>>());)+
+			},
+		)),+];
+	};
+}
+
+cases!(
+	1 => [0],
+	4 => [0, 1, 2, 3],
+	16 => [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+	32 => [
+		0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+		25, 26, 27, 28, 29, 30, 31
+	],
+);
+
+fn bench(name: &str, count: usize, state: &mut State, mut f: impl FnMut(&mut State)) {
+	let start = Instant::now();
+	for _ in 0..ITERATIONS {
+		f(black_box(&mut *state));
+	}
+	let elapsed = start.elapsed();
+	println!(
+		"{:<7} {:>3} entries {:>8.2} ns/entry",
+		name,
+		count,
+		elapsed.as_nanos() as f64 / f64::from(ITERATIONS) / count as f64
+	);
+	#[cfg(feature = "bench-internals")]
+	println!("        {:?}", state.internal_counters());
+}
+
+fn main() {
+	let backend = if cfg!(feature = "hashmap") {
+		"HashMap"
+	} else {
+		"BTreeMap"
+	};
+	let inline = if cfg!(feature = "small") {
+		" with inline entries"
+	} else {
+		""
+	};
+	println!("{} storage{}", backend, inline);
+	for &(count, fill, borrow_all, take_all) in CASES {
+		bench("put", count, &mut State::default(), fill);
+		let mut state = State::default();
+		fill(&mut state);
+		bench("borrow", count, &mut state, |state| borrow_all(state));
+		bench("take", count, &mut State::default(), |state| {
+			fill(state);
+			take_all(state);
+		});
+	}
+}
@@ -0,0 +1,241 @@
+// Copyright 2022 James Bradlee. All rights reserved. MIT license.
+
+//! Derive macros for `gotham_state`. Use them through the `derive` feature
+//! of `gotham_state` rather than depending on this crate directly.
+
+use proc_macro::Delimiter;
+use proc_macro::Spacing;
+use proc_macro::TokenStream;
+use proc_macro::TokenTree;
+
+/// Derives `gotham_state::Extract` for a struct with named fields, pulling
+/// every field out of a `State` by type.
+///
+/// Fields of type `&'a T` are borrowed and fields of type `&'a mut T` are
+/// mutably borrowed, while fields of any other type are taken out of the
+/// `State`. Nothing is taken unless every field is present, no type is
+/// named by two fields, and no field that is taken or mutably borrowed is
+/// frozen. The struct may declare at most one lifetime parameter and no
+/// type parameters.
+#[proc_macro_derive(Extract)]
+pub fn derive_extract(input: TokenStream) -> TokenStream {
+	match parse_struct(input) {
+		Ok(item) => expand_extract(&item),
+		Err(message) => compile_error(&message),
+	}
+}
+
+struct Struct {
+	name: String,
+	lifetime: Option<String>,
+	fields: Vec<Field>,
+}
+
+struct Field {
+	name: String,
+	ty: String,
+	access: Access,
+}
+
+enum Access {
+	Borrow,
+	BorrowMut,
+	Take,
+}
+
+fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+	let mut tokens = input.into_iter().peekable();
+	// Skip attributes and visibility up to the `struct` keyword.
+	loop {
+		match tokens.next() {
+			Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => break,
+			Some(TokenTree::Ident(ident))
+				if ident.to_string() == "enum" || ident.to_string() == "union" =>
+			{
+				return Err("Extract can only be derived for structs".into());
+			},
+			Some(_) => continue,
+			None => return Err("expected a struct".into()),
+		}
+	}
+	let name = match tokens.next() {
+		Some(TokenTree::Ident(ident)) => ident.to_string(),
+		_ => return Err("expected a struct name".into()),
+	};
+	let mut lifetime = None;
+	if matches!(tokens.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '<') {
+		tokens.next();
+		let mut generics = Vec::new();
+		for token in tokens.by_ref() {
+			match token {
+				TokenTree::Punct(p) if p.as_char() == '>' => break,
+				token => generics.push(token),
+			}
+		}
+		lifetime = Some(parse_lifetime(&generics)?);
+	}
+	let body = match tokens.next() {
+		Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+		_ => return Err("Extract can only be derived for structs with named fields".into()),
+	};
+	let fields = split_top_level(body)
+		.into_iter()
+		.map(parse_field)
+		.collect::<Result<_, _>>()?;
+	Ok(Struct {
+		name,
+		lifetime,
+		fields,
+	})
+}
+
+fn parse_lifetime(generics: &[TokenTree]) -> Result<String, String> {
+	match generics {
+		[TokenTree::Punct(p), TokenTree::Ident(ident)] if p.as_char() == '\'' => {
+			Ok(format!("'{}", ident))
+		},
+		_ => Err(
+			"Extract can only be derived for structs with at most one lifetime parameter".into(),
+		),
+	}
+}
+
+/// Splits a field list at the commas that are not nested inside angle
+/// brackets.
+fn split_top_level(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+	let mut items = vec![Vec::new()];
+	let mut depth = 0usize;
+	let mut previous_joint_dash = false;
+	for token in stream {
+		let mut joint_dash = false;
+		if let TokenTree::Punct(p) = &token {
+			match p.as_char() {
+				'<' => depth += 1,
+				'>' if !previous_joint_dash => depth = depth.saturating_sub(1),
+				',' if depth == 0 => {
+					items.push(Vec::new());
+					previous_joint_dash = false;
+					continue;
+				},
+				'-' => joint_dash = p.spacing() == Spacing::Joint,
+				_ => {},
+			}
+		}
+		previous_joint_dash = joint_dash;
+		items.last_mut().expect("items is never empty").push(token);
+	}
+	items.retain(|item| !item.is_empty());
+	items
+}
+
+fn parse_field(tokens: Vec<TokenTree>) -> Result<Field, String> {
+	let colon = tokens
+		.iter()
+		.position(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ':'))
+		.ok_or("expected a named field")?;
+	let name = match &tokens[..colon].last() {
+		Some(TokenTree::Ident(ident)) => ident.to_string(),
+		_ => return Err("expected a named field".into()),
+	};
+	let mut ty = &tokens[colon + 1..];
+	let mut access = Access::Take;
+	if let Some(TokenTree::Punct(p)) = ty.first() {
+		if p.as_char() == '&' {
+			access = Access::Borrow;
+			ty = &ty[1..];
+			if matches!(ty.first(), Some(TokenTree::Punct(p)) if p.as_char() == '\'') {
+				ty = &ty[2..];
+			}
+			if matches!(ty.first(), Some(TokenTree::Ident(i)) if i.to_string() == "mut") {
+				access = Access::BorrowMut;
+				ty = &ty[1..];
+			}
+		}
+	}
+	let ty = ty.iter().cloned().collect::<TokenStream>().to_string();
+	Ok(Field { name, ty, access })
+}
+
+fn expand_extract(item: &Struct) -> TokenStream {
+	let lifetime = item.lifetime.clone().unwrap_or_else(|| "'__state".into());
+	let self_ty = match &item.lifetime {
+		Some(lifetime) => format!("{}<{}>", item.name, lifetime),
+		None => item.name.clone(),
+	};
+	let mut checks = String::new();
+	let mut type_ids = Vec::new();
+	let mut takes = String::new();
+	let mut borrowed_names = Vec::new();
+	let mut borrowed_types = Vec::new();
+	for field in &item.fields {
+		checks.push_str(&format!("state.borrow_checked::<{}>()?;", field.ty));
+		if !matches!(field.access, Access::Borrow) {
+			checks.push_str(&format!(
+				"if state.is_type_frozen::<{ty}>() {{
+					return ::core::result::Result::Err(::gotham_state::StateError::Frozen {{
+						name: ::core::any::type_name::<{ty}>(),
+					}});
+				}}",
+				ty = field.ty
+			));
+		}
+		type_ids.push(format!(
+			"(::core::any::TypeId::of::<{ty}>(), ::core::any::type_name::<{ty}>())",
+			ty = field.ty
+		));
+		match field.access {
+			Access::Take => takes.push_str(&format!(
+				"let {} = state.take::<{}>();",
+				field.name, field.ty
+			)),
+			Access::Borrow => {
+				borrowed_names.push(field.name.clone());
+				borrowed_types.push(format!("&{} {}", lifetime, field.ty));
+			},
+			Access::BorrowMut => {
+				borrowed_names.push(field.name.clone());
+				borrowed_types.push(format!("&{} mut {}", lifetime, field.ty));
+			},
+		}
+	}
+	let borrows = if borrowed_names.is_empty() {
+		String::new()
+	} else {
+		format!(
+			"let ({},) = state.borrow_many::<({},)>();",
+			borrowed_names.join(", "),
+			borrowed_types.join(", ")
+		)
+	};
+	let field_names: Vec<_> = item.fields.iter().map(|f| f.name.as_str()).collect();
+	format!(
+		"impl<{lifetime}> ::gotham_state::Extract<{lifetime}> for {self_ty} {{
+			fn try_extract(
+				state: &{lifetime} mut ::gotham_state::State,
+			) -> ::core::result::Result<Self, ::gotham_state::StateError> {{
+				{checks}
+				let type_ids = [{type_ids}];
+				for (i, (type_id, name)) in type_ids.iter().enumerate() {{
+					if type_ids[..i].iter().any(|(other, _)| other == type_id) {{
+						return ::core::result::Result::Err(
+							::gotham_state::StateError::BorrowConflict {{ name }},
+						);
+					}}
+				}}
+				{takes}
+				{borrows}
+				::core::result::Result::Ok(Self {{ {fields} }})
+			}}
+		}}",
+		type_ids = type_ids.join(", "),
+		fields = field_names.join(", "),
+	)
+	.parse()
+	.expect("generated impl is valid Rust")
+}
+
+fn compile_error(message: &str) -> TokenStream {
+	format!("::core::compile_error!({:?});", message)
+		.parse()
+		.expect("compile_error! invocation is valid Rust")
+}
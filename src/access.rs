@@ -0,0 +1,183 @@
+use crate::missing;
+use crate::FrozenState;
+use crate::LayeredState;
+use crate::State;
+
+/// Read access to a state container, so code such as middleware can be
+/// generic over the container it reads from.
+///
+/// Implemented by [`State`], [`FrozenState`], [`LayeredState`],
+/// [`StateView`](crate::StateView), [`SyncState`](crate::SyncState) and the
+/// containers generated by [`typed_state!`](crate::typed_state).
+pub trait StateRead {
+	/// Tries to borrow a value of type `T` from the container.
+	fn try_borrow<T: 'static>(&self) -> Option<&T>;
+
+	/// Determines if a value of type `T` exists in the container.
+	fn has<T: 'static>(&self) -> bool {
+		self.try_borrow::<T>().is_some()
+	}
+
+	/// Borrows a value of type `T` from the container.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the container.
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		match self.try_borrow() {
+			Some(value) => value,
+			None => missing::<T>(None, core::iter::empty()),
+		}
+	}
+}
+
+/// Write access to a state container, in addition to [`StateRead`].
+///
+/// Implemented by [`State`], [`LayeredState`] and the containers generated by
+/// [`typed_state!`](crate::typed_state).
+pub trait StateWrite: StateRead {
+	/// Puts a value of type `T` into the container, overwriting the existing
+	/// value of the same type.
+	///
+	/// # Panics
+	///
+	/// If the container cannot hold values of type `T`.
+	fn put<T: 'static>(&mut self, t: T);
+
+	/// Tries to mutably borrow a value of type `T` from the container.
+	fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T>;
+
+	/// Mutably borrows a value of type `T` from the container.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the container.
+	#[track_caller]
+	fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		if self.try_borrow_mut::<T>().is_none() {
+			missing::<T>(None, core::iter::empty())
+		}
+		self.try_borrow_mut().expect("value was borrowed above")
+	}
+}
+
+impl StateRead for State {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		State::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		State::has::<T>(self)
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		State::borrow(self)
+	}
+}
+
+impl StateWrite for State {
+	fn put<T: 'static>(&mut self, t: T) {
+		State::put(self, t)
+	}
+
+	fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		State::try_borrow_mut(self)
+	}
+
+	#[track_caller]
+	fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		State::borrow_mut(self)
+	}
+}
+
+impl StateRead for FrozenState {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		State::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		State::has::<T>(self)
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		State::borrow(self)
+	}
+}
+
+impl StateRead for LayeredState<'_> {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		LayeredState::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		LayeredState::has::<T>(self)
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		LayeredState::borrow(self)
+	}
+}
+
+impl StateWrite for LayeredState<'_> {
+	fn put<T: 'static>(&mut self, t: T) {
+		LayeredState::put(self, t)
+	}
+
+	fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		LayeredState::try_borrow_mut(self)
+	}
+
+	#[track_caller]
+	fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		LayeredState::borrow_mut(self)
+	}
+}
+
+impl<S: StateRead> StateRead for &S {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		S::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		S::has::<T>(self)
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		S::borrow(self)
+	}
+}
+
+impl<S: StateRead> StateRead for &mut S {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		S::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		S::has::<T>(self)
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		S::borrow(self)
+	}
+}
+
+impl<S: StateWrite> StateWrite for &mut S {
+	fn put<T: 'static>(&mut self, t: T) {
+		S::put(self, t)
+	}
+
+	fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		S::try_borrow_mut(self)
+	}
+
+	#[track_caller]
+	fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		S::borrow_mut(self)
+	}
+}
@@ -0,0 +1,100 @@
+//! A shim mirroring the `AnyMap` API of the `anymap` crate on top of
+//! [`State`], so code migrating from `anymap` only has to change its
+//! imports:
+//!
+//! ```ignore
+//! use gotham_state::anymap::AnyMap;
+//!
+//! let mut map = AnyMap::new();
+//! map.insert(Config::default());
+//! if let Some(config) = map.get_mut::<Config>() {
+//!     config.verbose = true;
+//! }
+//! ```
+
+use crate::State;
+
+/// A map holding one value of each type, with the API of `anymap::AnyMap`.
+#[derive(Debug, Default)]
+pub struct AnyMap(State);
+
+impl AnyMap {
+	/// Creates an empty map.
+	pub fn new() -> Self {
+		AnyMap::default()
+	}
+
+	/// Creates an empty map with room for at least `capacity` values.
+	pub fn with_capacity(capacity: usize) -> Self {
+		AnyMap(State::with_capacity(capacity))
+	}
+
+	/// Returns the number of values in the map.
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	/// Determines if the map holds no values.
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Removes every value from the map.
+	pub fn clear(&mut self) {
+		self.0.clear();
+	}
+
+	/// Inserts a value, returning the previous value of the same type.
+	pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+		let previous = self.0.try_take::<T>();
+		self.0.put(value);
+		previous
+	}
+
+	/// Borrows the value of type `T`.
+	pub fn get<T: 'static>(&self) -> Option<&T> {
+		self.0.try_borrow()
+	}
+
+	/// Mutably borrows the value of type `T`.
+	pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.0.try_borrow_mut()
+	}
+
+	/// Removes the value of type `T` and returns it.
+	pub fn remove<T: 'static>(&mut self) -> Option<T> {
+		self.0.try_take()
+	}
+
+	/// Determines if the map holds a value of type `T`.
+	pub fn contains<T: 'static>(&self) -> bool {
+		self.0.has::<T>()
+	}
+
+	/// Returns the `State` backing the map.
+	pub fn as_state(&self) -> &State {
+		&self.0
+	}
+
+	/// Returns the `State` backing the map mutably.
+	pub fn as_state_mut(&mut self) -> &mut State {
+		&mut self.0
+	}
+
+	/// Converts the map into the `State` backing it.
+	pub fn into_state(self) -> State {
+		self.0
+	}
+}
+
+impl From<State> for AnyMap {
+	fn from(state: State) -> Self {
+		AnyMap(state)
+	}
+}
+
+impl From<AnyMap> for State {
+	fn from(map: AnyMap) -> Self {
+		map.0
+	}
+}
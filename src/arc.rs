@@ -0,0 +1,45 @@
+use crate::State;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+
+impl State {
+	/// Puts a value into the `State` storage wrapped in an `Arc`, so it can
+	/// be handed out with [`get_shared`](State::get_shared). The value is
+	/// stored as an `Arc<T>`, and can also be borrowed as such.
+	pub fn put_shared<T: 'static>(&mut self, t: T) {
+		self.put(Arc::new(t));
+	}
+
+	/// Tries to clone the `Arc<T>` stored in the `State` storage, whether it
+	/// was put with [`put_shared`](State::put_shared) or as an `Arc<T>`.
+	pub fn try_get_shared<T: 'static>(&self) -> Option<Arc<T>> {
+		self.try_borrow::<Arc<T>>().cloned()
+	}
+
+	/// Clones the `Arc<T>` stored in the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `Arc<T>` is not present in `State`.
+	#[track_caller]
+	pub fn get_shared<T: 'static>(&self) -> Arc<T> {
+		self.borrow::<Arc<T>>().clone()
+	}
+
+	/// Tries to get a `Weak` handle to the `Arc<T>` stored in the `State`
+	/// storage, which does not keep the value alive once it is removed from
+	/// the `State` and every other `Arc` is dropped.
+	pub fn try_get_weak<T: 'static>(&self) -> Option<Weak<T>> {
+		self.try_borrow::<Arc<T>>().map(Arc::downgrade)
+	}
+
+	/// Gets a `Weak` handle to the `Arc<T>` stored in the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `Arc<T>` is not present in `State`.
+	#[track_caller]
+	pub fn get_weak<T: 'static>(&self) -> Weak<T> {
+		Arc::downgrade(self.borrow::<Arc<T>>())
+	}
+}
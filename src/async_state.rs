@@ -0,0 +1,587 @@
+use crate::fail_at;
+use crate::lock::Mutex;
+use crate::map::Map;
+use crate::missing;
+use crate::StateError;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::future::poll_fn;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::Location;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+use std::sync::MutexGuard;
+
+/// A state container for use from async tasks. Every slot is guarded by its
+/// own async read-write lock, so many tasks can read an entry concurrently
+/// while writers to that entry are serialized, without requiring exclusive
+/// access to the whole container.
+///
+/// The locks do not depend on a particular async runtime.
+#[derive(Default, Debug)]
+pub struct AsyncState {
+	data: Map<Arc<Slot>>,
+}
+
+impl AsyncState {
+	/// Puts a value into the `AsyncState` storage. One value of each type is
+	/// retained. Successive calls to `put` will overwrite the existing value
+	/// of the same type.
+	pub fn put<T: Any + Send + Sync>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting record to async state for type_id `{:?}`",
+			type_id
+		);
+		self.data
+			.insert(type_id, Arc::new(Slot::new(type_name::<T>(), Box::new(t))));
+	}
+
+	/// Determines if the current value exists in `AsyncState` storage.
+	pub fn has<T: Any + Send + Sync>(&self) -> bool {
+		self.data.contains_key(&TypeId::of::<T>())
+	}
+
+	/// Tries to acquire shared read access to a value in the `AsyncState`
+	/// storage, waiting for any writer of that value to finish. Resolves to
+	/// `None` if no value of type `T` is present.
+	pub async fn try_read<T: Any + Send + Sync>(&self) -> Option<ReadGuard<'_, T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(" reading async state data for type_id `{:?}`", type_id);
+		let slot = self.data.get(&type_id)?;
+		poll_fn(|cx| slot.poll_read(cx)).await;
+		Some(ReadGuard {
+			slot,
+			marker: PhantomData,
+		})
+	}
+
+	/// Acquires shared read access to a value in the `AsyncState` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `AsyncState`.
+	#[track_caller]
+	pub fn read<T: Any + Send + Sync>(&self) -> impl Future<Output = ReadGuard<'_, T>> + '_ {
+		let caller = Location::caller();
+		async move {
+			match self.try_read().await {
+				Some(guard) => guard,
+				None => self.fail_at::<T>(caller),
+			}
+		}
+	}
+
+	/// Tries to acquire exclusive write access to a value in the `AsyncState`
+	/// storage, waiting for all readers and writers of that value to finish.
+	/// Resolves to `None` if no value of type `T` is present.
+	pub async fn try_write<T: Any + Send + Sync>(&self) -> Option<WriteGuard<'_, T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(" writing async state data for type_id `{:?}`", type_id);
+		let slot = self.data.get(&type_id)?;
+		poll_fn(|cx| slot.poll_write(cx)).await;
+		Some(WriteGuard {
+			slot,
+			marker: PhantomData,
+		})
+	}
+
+	/// Acquires exclusive write access to a value in the `AsyncState`
+	/// storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `AsyncState`.
+	#[track_caller]
+	pub fn write<T: Any + Send + Sync>(&self) -> impl Future<Output = WriteGuard<'_, T>> + '_ {
+		let caller = Location::caller();
+		async move {
+			match self.try_write().await {
+				Some(guard) => guard,
+				None => self.fail_at::<T>(caller),
+			}
+		}
+	}
+
+	/// Tries to acquire shared read access to a value in the `AsyncState`
+	/// storage like [`try_read`](AsyncState::try_read), but with a guard that
+	/// does not borrow the container, so it can be held across `.await`
+	/// points independently of it.
+	pub async fn try_get_owned<T: Any + Send + Sync>(&self) -> Option<OwnedRef<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" reading owned async state data for type_id `{:?}`",
+			type_id
+		);
+		let slot = self.data.get(&type_id)?.clone();
+		poll_fn(|cx| slot.poll_read(cx)).await;
+		Some(OwnedRef {
+			slot,
+			marker: PhantomData,
+		})
+	}
+
+	/// Acquires shared read access to a value in the `AsyncState` storage
+	/// with a guard that does not borrow the container.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `AsyncState`.
+	#[track_caller]
+	pub fn get_owned<T: Any + Send + Sync>(&self) -> impl Future<Output = OwnedRef<T>> + '_ {
+		let caller = Location::caller();
+		async move {
+			match self.try_get_owned().await {
+				Some(guard) => guard,
+				None => self.fail_at::<T>(caller),
+			}
+		}
+	}
+
+	/// Tries to acquire exclusive write access to a value in the `AsyncState`
+	/// storage like [`try_write`](AsyncState::try_write), but with a guard
+	/// that does not borrow the container.
+	pub async fn try_get_owned_mut<T: Any + Send + Sync>(&self) -> Option<OwnedMut<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" writing owned async state data for type_id `{:?}`",
+			type_id
+		);
+		let slot = self.data.get(&type_id)?.clone();
+		poll_fn(|cx| slot.poll_write(cx)).await;
+		Some(OwnedMut {
+			slot,
+			marker: PhantomData,
+		})
+	}
+
+	/// Acquires exclusive write access to a value in the `AsyncState` storage
+	/// with a guard that does not borrow the container.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `AsyncState`.
+	#[track_caller]
+	pub fn get_owned_mut<T: Any + Send + Sync>(&self) -> impl Future<Output = OwnedMut<T>> + '_ {
+		let caller = Location::caller();
+		async move {
+			match self.try_get_owned_mut().await {
+				Some(guard) => guard,
+				None => self.fail_at::<T>(caller),
+			}
+		}
+	}
+
+	/// Tries to move a value out of the `AsyncState` storage and return
+	/// ownership. Returns `None` while an [`OwnedRef`] or [`OwnedMut`] guard
+	/// of the value is alive.
+	pub fn try_take<T: Any + Send + Sync>(&mut self) -> Option<T> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" taking ownership from async state data for type_id `{:?}`",
+			type_id
+		);
+		let slot = self.data.remove(&type_id)?;
+		match Arc::try_unwrap(slot) {
+			Ok(slot) => slot.value.into_inner().downcast().ok().map(|b| *b),
+			Err(slot) => {
+				self.data.insert(type_id, slot);
+				None
+			},
+		}
+	}
+
+	/// Moves a value out of the `AsyncState` storage and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `AsyncState`, or if an
+	/// owned guard of it is alive.
+	#[track_caller]
+	pub fn take<T: Any + Send + Sync>(&mut self) -> T {
+		match self.try_take() {
+			Some(value) => value,
+			None => missing::<T>(None, self.type_names()),
+		}
+	}
+
+	/// Panics for a missing value of type `T`, naming `caller` as the caller.
+	fn fail_at<T: 'static>(&self, caller: &'static Location<'static>) -> ! {
+		fail_at(StateError::missing::<T>(), None, self.type_names(), caller)
+	}
+
+	/// The names of the types stored in the `AsyncState`.
+	fn type_names(&self) -> Vec<&'static str> {
+		self.data.values().map(|slot| slot.name).collect()
+	}
+}
+
+/// Shared read access to a value in an [`AsyncState`]. The read lock on the
+/// slot is released when the guard is dropped.
+pub struct ReadGuard<'a, T> {
+	slot: &'a Slot,
+	marker: PhantomData<&'a T>,
+}
+
+/// Exclusive write access to a value in an [`AsyncState`]. The write lock on
+/// the slot is released when the guard is dropped.
+pub struct WriteGuard<'a, T> {
+	slot: &'a Slot,
+	marker: PhantomData<&'a mut T>,
+}
+
+impl<T: 'static> Deref for ReadGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		// SAFETY: the guard holds a read lock on the slot, so no writer can
+		// access the value until it is dropped.
+		let value = unsafe { &*self.slot.value.get() };
+		value.downcast_ref().expect("slot holds its type")
+	}
+}
+
+impl<T> Drop for ReadGuard<'_, T> {
+	fn drop(&mut self) {
+		self.slot.release(|lock| lock.readers -= 1);
+	}
+}
+
+impl<T: 'static> Deref for WriteGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		// SAFETY: the guard holds the write lock on the slot, so nothing else
+		// can access the value until it is dropped.
+		let value = unsafe { &*self.slot.value.get() };
+		value.downcast_ref().expect("slot holds its type")
+	}
+}
+
+impl<T: 'static> DerefMut for WriteGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		// SAFETY: the guard holds the write lock on the slot, so nothing else
+		// can access the value until it is dropped.
+		let value = unsafe { &mut *self.slot.value.get() };
+		value.downcast_mut().expect("slot holds its type")
+	}
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+	fn drop(&mut self) {
+		self.slot.release(|lock| lock.writer = false);
+	}
+}
+
+impl<T: 'static> Deref for OwnedRef<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		// SAFETY: the guard holds a read lock on the slot, so no writer can
+		// access the value until it is dropped.
+		let value = unsafe { &*self.slot.value.get() };
+		value.downcast_ref().expect("slot holds its type")
+	}
+}
+
+impl<T> Drop for OwnedRef<T> {
+	fn drop(&mut self) {
+		self.slot.release(|lock| lock.readers -= 1);
+	}
+}
+
+impl<T: 'static> Deref for OwnedMut<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		// SAFETY: the guard holds the write lock on the slot, so nothing else
+		// can access the value until it is dropped.
+		let value = unsafe { &*self.slot.value.get() };
+		value.downcast_ref().expect("slot holds its type")
+	}
+}
+
+impl<T: 'static> DerefMut for OwnedMut<T> {
+	fn deref_mut(&mut self) -> &mut T {
+		// SAFETY: the guard holds the write lock on the slot, so nothing else
+		// can access the value until it is dropped.
+		let value = unsafe { &mut *self.slot.value.get() };
+		value.downcast_mut().expect("slot holds its type")
+	}
+}
+
+impl<T> Drop for OwnedMut<T> {
+	fn drop(&mut self) {
+		self.slot.release(|lock| lock.writer = false);
+	}
+}
+
+/// Shared read access to a value in an [`AsyncState`] that does not borrow
+/// the container. The read lock on the slot is released when the guard is
+/// dropped.
+pub struct OwnedRef<T> {
+	slot: Arc<Slot>,
+	marker: PhantomData<T>,
+}
+
+/// Exclusive write access to a value in an [`AsyncState`] that does not
+/// borrow the container. The write lock on the slot is released when the
+/// guard is dropped.
+pub struct OwnedMut<T> {
+	slot: Arc<Slot>,
+	marker: PhantomData<T>,
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for ReadGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for WriteGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for OwnedRef<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for OwnedMut<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+/// A value together with the async read-write lock guarding it.
+struct Slot {
+	name: &'static str,
+	lock: Mutex<LockState>,
+	value: UnsafeCell<Box<dyn Any + Send + Sync>>,
+}
+
+#[derive(Default)]
+struct LockState {
+	readers: usize,
+	writer: bool,
+	waiters: Vec<Waker>,
+}
+
+// SAFETY: the value is only accessed through the guards. A read guard is
+// only handed out while `lock` records no writer, and a write guard only
+// while it records no readers and no other writer, so a `&mut` to the value
+// never coexists with any other reference to it. The value is `Sync`, so it
+// may be read from several threads at once, and `Send`, so it may be written
+// from whichever thread holds the write guard.
+unsafe impl Sync for Slot {}
+
+impl Slot {
+	fn new(name: &'static str, value: Box<dyn Any + Send + Sync>) -> Self {
+		Slot {
+			name,
+			lock: Mutex::default(),
+			value: UnsafeCell::new(value),
+		}
+	}
+
+	fn lock(&self) -> MutexGuard<'_, LockState> {
+		// The bookkeeping is never left half-updated, so a poisoned lock is
+		// still consistent.
+		self.lock.lock()
+	}
+
+	fn poll_read(&self, cx: &mut Context<'_>) -> Poll<()> {
+		let mut lock = self.lock();
+		if lock.writer {
+			lock.wait(cx.waker());
+			return Poll::Pending;
+		}
+		lock.readers += 1;
+		Poll::Ready(())
+	}
+
+	fn poll_write(&self, cx: &mut Context<'_>) -> Poll<()> {
+		let mut lock = self.lock();
+		if lock.writer || lock.readers > 0 {
+			lock.wait(cx.waker());
+			return Poll::Pending;
+		}
+		lock.writer = true;
+		Poll::Ready(())
+	}
+
+	fn release(&self, f: impl FnOnce(&mut LockState)) {
+		let waiters = {
+			let mut lock = self.lock();
+			f(&mut lock);
+			core::mem::take(&mut lock.waiters)
+		};
+		waiters.into_iter().for_each(Waker::wake);
+	}
+}
+
+impl LockState {
+	fn wait(&mut self, waker: &Waker) {
+		if !self.waiters.iter().any(|w| w.will_wake(waker)) {
+			self.waiters.push(waker.clone());
+		}
+	}
+}
+
+impl fmt::Debug for Slot {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Slot").finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::future::Future;
+	use core::pin::pin;
+	use core::sync::atomic::AtomicUsize;
+	use core::sync::atomic::Ordering;
+	use std::task::Wake;
+	use std::thread;
+	use std::thread::Thread;
+
+	/// Counts how often it is woken.
+	#[derive(Default)]
+	struct Counter(AtomicUsize);
+
+	impl Wake for Counter {
+		fn wake(self: Arc<Self>) {
+			self.0.fetch_add(1, Ordering::SeqCst);
+		}
+	}
+
+	impl Counter {
+		fn woken(&self) -> usize {
+			self.0.load(Ordering::SeqCst)
+		}
+	}
+
+	struct Unpark(Thread);
+
+	impl Wake for Unpark {
+		fn wake(self: Arc<Self>) {
+			self.0.unpark();
+		}
+	}
+
+	fn block_on<F: Future>(future: F) -> F::Output {
+		let mut future = pin!(future);
+		let waker = Waker::from(Arc::new(Unpark(thread::current())));
+		let mut cx = Context::from_waker(&waker);
+		loop {
+			if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+				return output;
+			}
+			thread::park();
+		}
+	}
+
+	#[test]
+	fn readers_share_and_writers_wait() {
+		let mut state = AsyncState::default();
+		state.put(1u32);
+		let counter = Arc::new(Counter::default());
+		let waker = Waker::from(counter.clone());
+		let mut cx = Context::from_waker(&waker);
+
+		let first = block_on(state.read::<u32>());
+		let second = block_on(state.read::<u32>());
+		assert_eq!((*first, *second), (1, 1));
+		let mut write = pin!(state.write::<u32>());
+		assert!(write.as_mut().poll(&mut cx).is_pending());
+		drop(first);
+		assert_eq!(counter.woken(), 1);
+		assert!(write.as_mut().poll(&mut cx).is_pending());
+		drop(second);
+		assert_eq!(counter.woken(), 2);
+		let Poll::Ready(mut guard) = write.as_mut().poll(&mut cx) else {
+			panic!("the last reader is gone");
+		};
+		*guard = 2;
+
+		let mut read = pin!(state.read::<u32>());
+		assert!(read.as_mut().poll(&mut cx).is_pending());
+		drop(guard);
+		assert_eq!(counter.woken(), 3);
+		let Poll::Ready(guard) = read.as_mut().poll(&mut cx) else {
+			panic!("the writer is gone");
+		};
+		assert_eq!(*guard, 2);
+	}
+
+	#[test]
+	fn cancelled_waiters_do_not_hold_the_lock() {
+		let mut state = AsyncState::default();
+		state.put(1u32);
+		let waker = Waker::from(Arc::new(Counter::default()));
+		let mut cx = Context::from_waker(&waker);
+
+		let guard = block_on(state.get_owned_mut::<u32>());
+		{
+			let mut read = pin!(state.read::<u32>());
+			assert!(read.as_mut().poll(&mut cx).is_pending());
+			let mut write = pin!(state.write::<u32>());
+			assert!(write.as_mut().poll(&mut cx).is_pending());
+		}
+		drop(guard);
+		*block_on(state.write::<u32>()) += 1;
+		assert_eq!(*block_on(state.read::<u32>()), 2);
+		assert_eq!(state.take::<u32>(), 2);
+	}
+
+	#[test]
+	fn owned_guards_keep_the_value_from_being_taken() {
+		let mut state = AsyncState::default();
+		state.put(1u32);
+		let guard = block_on(state.get_owned::<u32>());
+		assert_eq!(state.try_take::<u32>(), None);
+		assert_eq!(*guard, 1);
+		drop(guard);
+		assert_eq!(state.try_take::<u32>(), Some(1));
+		assert!(!state.has::<u32>());
+	}
+
+	#[test]
+	fn concurrent_readers_and_writers() {
+		let mut state = AsyncState::default();
+		state.put((0u64, 0u64));
+		let state = &state;
+		thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					for _ in 0..1_000 {
+						let mut pair = block_on(state.write::<(u64, u64)>());
+						pair.0 += 1;
+						thread::yield_now();
+						pair.1 += 1;
+					}
+				});
+				scope.spawn(|| {
+					for _ in 0..1_000 {
+						let pair = block_on(state.read::<(u64, u64)>());
+						assert_eq!(pair.0, pair.1);
+					}
+				});
+			}
+		});
+		assert_eq!(*block_on(state.read::<(u64, u64)>()), (4_000, 4_000));
+	}
+}
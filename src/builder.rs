@@ -0,0 +1,49 @@
+use crate::State;
+use alloc::sync::Arc;
+
+/// A fluent builder for populating a `State` declaratively.
+#[derive(Default, Debug)]
+pub struct StateBuilder {
+	state: State,
+}
+
+impl State {
+	/// Creates a [`StateBuilder`] for an empty `State`.
+	pub fn builder() -> StateBuilder {
+		StateBuilder::default()
+	}
+}
+
+impl StateBuilder {
+	/// Creates a builder for an empty `State`.
+	pub fn new() -> Self {
+		StateBuilder::default()
+	}
+
+	/// Puts a value into the `State` being built, overwriting any earlier
+	/// value of the same type.
+	pub fn with<T: 'static>(mut self, t: T) -> Self {
+		self.state.put(t);
+		self
+	}
+
+	/// Puts a cloneable value into the `State` being built. See
+	/// [`State::put_cloneable`].
+	pub fn with_cloneable<T: Clone + 'static>(mut self, t: T) -> Self {
+		self.state.put_cloneable(t);
+		self
+	}
+
+	/// Finishes building and returns the `State`.
+	pub fn build(self) -> State {
+		self.state
+	}
+
+	/// Finishes building and returns the `State` behind an `Arc` for shared
+	/// use. `State` is not `Send`, so use [`SyncState`](crate::SyncState) to
+	/// share state across threads.
+	#[allow(clippy::arc_with_non_send_sync)]
+	pub fn build_arc(self) -> Arc<State> {
+		Arc::new(self.state)
+	}
+}
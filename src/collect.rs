@@ -0,0 +1,35 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+use core::any::Any;
+
+/// Builds a `State` from type-erased values, keyed by the type of each
+/// boxed value. Later values overwrite earlier values of the same type.
+/// Since the type names are not known, the values are listed as `<unknown>`
+/// by [`type_names`](State::type_names).
+impl FromIterator<Box<dyn Any>> for State {
+	fn from_iter<I: IntoIterator<Item = Box<dyn Any>>>(iter: I) -> Self {
+		let mut state = State::default();
+		state.extend(iter);
+		state
+	}
+}
+
+/// Puts type-erased values into a `State`, keyed by the type of each boxed
+/// value, overwriting existing values of the same types.
+///
+/// # Panics
+///
+/// If an existing value of the same type has been frozen.
+impl Extend<Box<dyn Any>> for State {
+	fn extend<I: IntoIterator<Item = Box<dyn Any>>>(&mut self, iter: I) {
+		for value in iter {
+			let type_id = (*value).type_id();
+			trace!(
+				" inserting boxed record to state for type_id `{:?}`",
+				type_id
+			);
+			self.insert_slot(type_id, Slot::named(value, "<unknown>"));
+		}
+	}
+}
@@ -0,0 +1,441 @@
+use crate::lock::Mutex;
+use crate::map::Map;
+use crate::missing;
+use crate::swap::ArcCell;
+use crate::StateError;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+
+type Shared = Arc<dyn Any + Send + Sync>;
+
+/// The value of a slot together with its version, which is bumped every
+/// time the value is replaced.
+#[derive(Debug)]
+struct Versioned {
+	value: Option<Shared>,
+	version: u64,
+}
+
+/// The slot of a value, which is read without locking and replaced by one
+/// writer at a time.
+#[derive(Debug)]
+struct Slot {
+	name: &'static str,
+	current: ArcCell<Versioned>,
+	writer: Mutex<()>,
+}
+
+impl Slot {
+	fn new(name: &'static str, value: Option<Shared>, version: u64) -> Self {
+		Slot {
+			name,
+			current: ArcCell::new(Arc::new(Versioned { value, version })),
+			writer: Mutex::default(),
+		}
+	}
+
+	fn read(&self) -> Arc<Versioned> {
+		self.current.load()
+	}
+
+	/// Replaces the value, bumping its version. Must be called with the
+	/// writer lock held.
+	fn replace(&self, current: &Versioned, value: Option<Shared>) -> Arc<Versioned> {
+		let version = current.version + 1;
+		self.current.swap(Arc::new(Versioned { value, version }))
+	}
+}
+
+/// A state container for read-mostly values shared across threads, such as
+/// configuration that is hot-reloaded.
+///
+/// Every value is held in an `Arc`. Readers get their own clone of the `Arc`
+/// with [`get`](ConcurrentState::get), and [`store`](ConcurrentState::store)
+/// swaps in a new `Arc` through a shared reference, so readers holding the
+/// old value are unaffected. Reads never lock: they only announce
+/// themselves in an atomic counter while cloning the `Arc`, so readers never
+/// wait on each other or on writers. Writers to the same slot take turns
+/// through a lock, and wait for the readers that are cloning the old `Arc`
+/// before releasing it.
+///
+/// Every value also has a version, so writers can detect lost updates with
+/// [`compare_and_put`](ConcurrentState::compare_and_put).
+#[derive(Default, Debug)]
+pub struct ConcurrentState {
+	data: Map<Slot>,
+	policy: PoisonPolicy,
+	#[cfg(feature = "watch")]
+	pub(crate) watchers: crate::watch::Watchers,
+}
+
+/// How a [`ConcurrentState`] treats a value whose lock was poisoned by a
+/// thread that panicked while replacing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoisonPolicy {
+	/// The value is used as if nothing happened. Values are only ever
+	/// replaced as a whole, so they stay consistent.
+	#[default]
+	Ignore,
+	/// Every access to the value panics with [`StateError::Poisoned`],
+	/// including the non-panicking accessors.
+	Propagate,
+	/// The value is dropped and treated as absent from then on, until a new
+	/// value is put.
+	ClearEntry,
+}
+
+/// The error returned by [`ConcurrentState::compare_and_put`] when the value
+/// was replaced since the expected version was read.
+#[derive(Debug)]
+pub struct VersionConflict<T> {
+	/// The value that was not stored.
+	pub value: Arc<T>,
+	/// The current version of the value, or `None` if no value of type `T`
+	/// is present.
+	pub current: Option<u64>,
+}
+
+impl ConcurrentState {
+	/// Creates an empty `ConcurrentState` that treats poisoned values
+	/// according to `policy`.
+	pub fn with_poison_policy(policy: PoisonPolicy) -> Self {
+		ConcurrentState {
+			policy,
+			..ConcurrentState::default()
+		}
+	}
+
+	/// Sets how poisoned values are treated from now on.
+	pub fn set_poison_policy(&mut self, policy: PoisonPolicy) {
+		self.policy = policy;
+	}
+
+	/// Applies the poison policy to the slot of type `T` before it is
+	/// locked.
+	fn recover<T: 'static>(&self, slot: &Slot) {
+		if !slot.writer.is_poisoned() {
+			return;
+		}
+		match self.policy {
+			PoisonPolicy::Ignore => {},
+			PoisonPolicy::Propagate => {
+				panic!(
+					"{}",
+					StateError::Poisoned {
+						name: type_name::<T>()
+					}
+				)
+			},
+			PoisonPolicy::ClearEntry => {
+				trace!(" clearing poisoned concurrent state data");
+				let writer = slot.writer.lock();
+				let previous = slot.replace(&slot.read(), None);
+				drop(writer);
+				slot.writer.clear_poison();
+				drop(previous);
+			},
+		}
+	}
+
+	/// Puts a value into the `ConcurrentState` storage. One value of each
+	/// type is retained. Successive calls to `put` will overwrite the existing
+	/// value of the same type, bumping its version.
+	pub fn put<T: Any + Send + Sync>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting record to concurrent state for type_id `{:?}`",
+			type_id
+		);
+		let version = self
+			.data
+			.get(&type_id)
+			.map_or(0, |slot| slot.read().version + 1);
+		let value: Shared = Arc::new(t);
+		#[cfg(feature = "watch")]
+		self.watchers.notify(type_id, &value);
+		self.data
+			.insert(type_id, Slot::new(type_name::<T>(), Some(value), version));
+	}
+
+	/// Determines if the current value exists in `ConcurrentState` storage.
+	pub fn has<T: Any + Send + Sync>(&self) -> bool {
+		self.data.get(&TypeId::of::<T>()).is_some_and(|slot| {
+			self.recover::<T>(slot);
+			slot.read().value.is_some()
+		})
+	}
+
+	/// Tries to get the current value of type `T`. Returns `None` if no value
+	/// of type `T` is present.
+	pub fn try_get<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(" reading concurrent state data for type_id `{:?}`", type_id);
+		let slot = self.data.get(&type_id)?;
+		self.recover::<T>(slot);
+		let value = slot.read().value.clone()?;
+		value.downcast().ok()
+	}
+
+	/// Gets the current value of type `T`.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `ConcurrentState`.
+	#[track_caller]
+	pub fn get<T: Any + Send + Sync>(&self) -> Arc<T> {
+		match self.try_get() {
+			Some(value) => value,
+			None => missing::<T>(None, self.type_names()),
+		}
+	}
+
+	/// Tries to get a `Weak` handle to the current value of type `T`, which
+	/// does not keep the value alive once it is replaced or taken and every
+	/// other `Arc` is dropped.
+	pub fn try_get_weak<T: Any + Send + Sync>(&self) -> Option<Weak<T>> {
+		self.try_get().map(|value| Arc::downgrade(&value))
+	}
+
+	/// Gets a `Weak` handle to the current value of type `T`.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `ConcurrentState`.
+	#[track_caller]
+	pub fn get_weak<T: Any + Send + Sync>(&self) -> Weak<T> {
+		Arc::downgrade(&self.get())
+	}
+
+	/// Tries to replace the value of type `T` through a shared reference,
+	/// returning the previous value. If no value of type `T` is present, the
+	/// new value is handed back in `Err`, as values can only be added with
+	/// [`put`](ConcurrentState::put). The version of the value is bumped.
+	pub fn try_store<T: Any + Send + Sync>(&self, value: Arc<T>) -> Result<Arc<T>, Arc<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(" storing concurrent state data for type_id `{:?}`", type_id);
+		let Some(slot) = self.data.get(&type_id) else {
+			return Err(value);
+		};
+		self.recover::<T>(slot);
+		let writer = slot.writer.lock();
+		let current = slot.read();
+		let Some(previous) = current.value.clone() else {
+			return Err(value);
+		};
+		#[cfg(feature = "watch")]
+		self.watchers.notify(type_id, &(value.clone() as Shared));
+		slot.replace(&current, Some(value));
+		drop(writer);
+		Ok(previous.downcast().expect("slot holds its type"))
+	}
+
+	/// Replaces the value of type `T` through a shared reference, returning
+	/// the previous value.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `ConcurrentState`.
+	#[track_caller]
+	pub fn store<T: Any + Send + Sync>(&self, value: Arc<T>) -> Arc<T> {
+		match self.try_store(value) {
+			Ok(previous) => previous,
+			Err(_) => missing::<T>(None, self.type_names()),
+		}
+	}
+
+	/// Tries to get the current value of type `T` together with its version,
+	/// to be passed to [`compare_and_put`](ConcurrentState::compare_and_put)
+	/// when storing an updated value.
+	pub fn try_get_versioned<T: Any + Send + Sync>(&self) -> Option<(Arc<T>, u64)> {
+		let slot = self.data.get(&TypeId::of::<T>())?;
+		self.recover::<T>(slot);
+		let current = slot.read();
+		let value = current.value.clone()?.downcast().ok()?;
+		Some((value, current.version))
+	}
+
+	/// Returns the current version of the value of type `T`, if present.
+	pub fn version<T: Any + Send + Sync>(&self) -> Option<u64> {
+		let slot = self.data.get(&TypeId::of::<T>())?;
+		self.recover::<T>(slot);
+		let current = slot.read();
+		current.value.as_ref().map(|_| current.version)
+	}
+
+	/// Replaces the value of type `T` through a shared reference if its
+	/// version is still `expected_version`, returning the new version.
+	/// Otherwise another writer replaced the value since it was read, and
+	/// the new value is handed back in `Err` together with the current
+	/// version, so the update can be retried on top of the current value.
+	pub fn compare_and_put<T: Any + Send + Sync>(
+		&self,
+		expected_version: u64,
+		value: Arc<T>,
+	) -> Result<u64, VersionConflict<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" comparing and storing concurrent state data for type_id `{:?}`",
+			type_id
+		);
+		let Some(slot) = self.data.get(&type_id) else {
+			return Err(VersionConflict {
+				value,
+				current: None,
+			});
+		};
+		self.recover::<T>(slot);
+		let writer = slot.writer.lock();
+		let current = slot.read();
+		if current.value.is_none() || current.version != expected_version {
+			let version = current.value.as_ref().map(|_| current.version);
+			return Err(VersionConflict {
+				value,
+				current: version,
+			});
+		}
+		#[cfg(feature = "watch")]
+		self.watchers.notify(type_id, &(value.clone() as Shared));
+		let previous = slot.replace(&current, Some(value));
+		// The previous value is dropped with the lock held, so a panic in
+		// its destructor poisons the slot.
+		drop((current, previous));
+		drop(writer);
+		Ok(expected_version + 1)
+	}
+
+	/// Tries to move the current value of type `T` out of the
+	/// `ConcurrentState` storage.
+	pub fn try_take<T: Any + Send + Sync>(&mut self) -> Option<Arc<T>> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" taking ownership from concurrent state data for type_id `{:?}`",
+			type_id
+		);
+		let slot = self.data.remove(&type_id)?;
+		self.recover::<T>(&slot);
+		let value = slot.read().value.clone()?;
+		value.downcast().ok()
+	}
+
+	/// Moves the current value of type `T` out of the `ConcurrentState`
+	/// storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `ConcurrentState`.
+	#[track_caller]
+	pub fn take<T: Any + Send + Sync>(&mut self) -> Arc<T> {
+		if !self.has::<T>() {
+			missing::<T>(None, self.type_names())
+		}
+		self.try_take().expect("value was checked above")
+	}
+
+	/// The names of the types stored in the `ConcurrentState`.
+	fn type_names(&self) -> Vec<&'static str> {
+		self.data
+			.values()
+			.filter(|slot| slot.read().value.is_some())
+			.map(|slot| slot.name)
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::panic::AssertUnwindSafe;
+	use std::panic::catch_unwind;
+	use std::thread;
+
+	#[derive(Debug)]
+	struct Config(u64);
+
+	#[test]
+	fn store_bumps_the_version() {
+		let mut state = ConcurrentState::default();
+		state.put(Config(1));
+		let (config, version) = state.try_get_versioned::<Config>().unwrap();
+		assert_eq!((config.0, version), (1, 0));
+		assert_eq!(state.store(Arc::new(Config(2))).0, 1);
+		assert_eq!(state.version::<Config>(), Some(1));
+		let conflict = state
+			.compare_and_put(version, Arc::new(Config(3)))
+			.unwrap_err();
+		assert_eq!(conflict.current, Some(1));
+		assert_eq!(state.compare_and_put(1, Arc::new(Config(3))).unwrap(), 2);
+		assert_eq!(state.get::<Config>().0, 3);
+		assert_eq!(state.take::<Config>().0, 3);
+		assert!(!state.has::<Config>());
+	}
+
+	#[test]
+	fn readers_and_writers_run_concurrently() {
+		let mut state = ConcurrentState::default();
+		state.put(Config(0));
+		thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					for _ in 0..5_000 {
+						assert!(state.get::<Config>().0 <= 400);
+					}
+				});
+			}
+			for _ in 0..2 {
+				scope.spawn(|| {
+					for _ in 0..200 {
+						loop {
+							let (config, version) = state.try_get_versioned::<Config>().unwrap();
+							let next = Arc::new(Config(config.0 + 1));
+							if state.compare_and_put(version, next).is_ok() {
+								break;
+							}
+						}
+					}
+				});
+			}
+		});
+		assert_eq!(state.get::<Config>().0, 400);
+		assert_eq!(state.version::<Config>(), Some(400));
+	}
+
+	/// Panics when dropped if it is armed.
+	struct PanicOnDrop(bool);
+
+	impl Drop for PanicOnDrop {
+		fn drop(&mut self) {
+			if self.0 {
+				panic!("dropping the old value failed");
+			}
+		}
+	}
+
+	fn poisoned(policy: PoisonPolicy) -> ConcurrentState {
+		let mut state = ConcurrentState::with_poison_policy(policy);
+		state.put(PanicOnDrop(true));
+		let result = catch_unwind(AssertUnwindSafe(|| {
+			let _ = state.compare_and_put(0, Arc::new(PanicOnDrop(false)));
+		}));
+		assert!(result.is_err());
+		state
+	}
+
+	#[test]
+	fn poison_policies() {
+		let state = poisoned(PoisonPolicy::Ignore);
+		assert!(state.has::<PanicOnDrop>());
+		assert_eq!(state.version::<PanicOnDrop>(), Some(1));
+
+		let state = poisoned(PoisonPolicy::ClearEntry);
+		assert!(!state.has::<PanicOnDrop>());
+		assert!(state.try_get::<PanicOnDrop>().is_none());
+
+		let state = poisoned(PoisonPolicy::Propagate);
+		let result = catch_unwind(AssertUnwindSafe(|| state.has::<PanicOnDrop>()));
+		assert!(result.is_err());
+	}
+}
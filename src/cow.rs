@@ -0,0 +1,135 @@
+use crate::missing;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateRead;
+use crate::StateWrite;
+use alloc::sync::Arc;
+use core::any::TypeId;
+
+/// A copy-on-write view of a shared base `State`, for cheap per-request
+/// overrides of mostly-shared application state.
+///
+/// Reads fall through to the base unless the value has been overridden
+/// locally. Mutably borrowing a value that is only in the base clones it into
+/// the local layer first, which requires that it was put with
+/// [`put_cloneable`](State::put_cloneable). The base itself is never
+/// modified.
+#[derive(Debug)]
+pub struct CowState {
+	base: Arc<State>,
+	local: State,
+}
+
+impl CowState {
+	/// Creates a `CowState` with no local overrides of `base`.
+	pub fn new(base: Arc<State>) -> Self {
+		CowState {
+			base,
+			local: State::default(),
+		}
+	}
+
+	/// Puts a value into the local layer, overriding the value of the same
+	/// type in the base.
+	pub fn put<T: 'static>(&mut self, t: T) {
+		self.local.put(t);
+	}
+
+	/// Determines if the current value exists in the local layer or the base.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.local.has::<T>() || self.base.has::<T>()
+	}
+
+	/// Tries to borrow a value from the local layer, falling back to the base.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		self.local.try_borrow().or_else(|| self.base.try_borrow())
+	}
+
+	/// Borrows a value from the local layer, falling back to the base.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is present in neither.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: 'static>(&self) -> &T {
+		match self.try_borrow() {
+			Some(value) => value,
+			None => missing::<T>(Some(&self.local), self.type_names()),
+		}
+	}
+
+	/// Tries to mutably borrow a value from the local layer, first cloning it
+	/// from the base if it has not been overridden yet. Returns `None` if the
+	/// value is missing, or if it is only in the base and cannot be cloned.
+	pub fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		let type_id = TypeId::of::<T>();
+		if !self.local.has::<T>() {
+			trace!(" cloning cow state data for type_id `{:?}`", type_id);
+			let slot = self
+				.base
+				.data
+				.get(&type_id)
+				.filter(|slot| !slot.is_expired())
+				.and_then(Slot::try_clone)?;
+			self.local.insert_slot(type_id, slot);
+		}
+		self.local.try_borrow_mut()
+	}
+
+	/// Mutably borrows a value from the local layer, first cloning it from
+	/// the base if it has not been overridden yet.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is present in neither, or if it is only in the
+	/// base and cannot be cloned.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		if self.try_borrow_mut::<T>().is_none() {
+			missing::<T>(Some(&self.local), self.type_names());
+		}
+		self.try_borrow_mut().expect("value was borrowed above")
+	}
+
+	/// Returns the shared base.
+	pub fn base(&self) -> &Arc<State> {
+		&self.base
+	}
+
+	/// Returns the local overrides.
+	pub fn local(&self) -> &State {
+		&self.local
+	}
+
+	/// Detaches the local overrides from the base.
+	pub fn into_local(self) -> State {
+		self.local
+	}
+
+	/// Returns the names of the types in the local layer and the base.
+	fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+		self.local.type_names().chain(self.base.type_names())
+	}
+}
+
+impl StateRead for CowState {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		CowState::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		CowState::has::<T>(self)
+	}
+}
+
+impl StateWrite for CowState {
+	fn put<T: 'static>(&mut self, t: T) {
+		CowState::put(self, t)
+	}
+
+	fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		CowState::try_borrow_mut(self)
+	}
+}
@@ -0,0 +1,86 @@
+use crate::State;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use std::time::UNIX_EPOCH;
+
+impl State {
+	/// Renders a JSON report of the contents of the `State`, for frameworks
+	/// to serve on a debug endpoint such as `/debug/state`.
+	///
+	/// The report is an object with an `entries` array holding, for each
+	/// value, its `type`, its `aliases` registered with
+	/// [`put_named`](State::put_named), when it was put as `inserted_at_ms`
+	/// milliseconds since the Unix epoch, and whether it is `initialized`,
+	/// `cloneable` and `frozen`, and its `size` in bytes if it was put with
+	/// [`put_sized`](State::put_sized). With the `metrics` feature, each
+	/// entry also holds its access counters as `metrics`.
+	pub fn debug_report(&self) -> String {
+		#[cfg(feature = "metrics")]
+		let metrics = self.metrics();
+		let mut out = String::from("{\"entries\":[");
+		for (i, (type_id, slot)) in self.data.iter().enumerate() {
+			if i > 0 {
+				out.push(',');
+			}
+			out.push_str("{\"type\":");
+			push_json_str(&mut out, slot.name);
+			let aliases: Vec<_> = self
+				.aliases
+				.iter()
+				.filter(|(_, id)| *id == type_id)
+				.map(|(alias, _)| alias)
+				.collect();
+			out.push_str(",\"aliases\":[");
+			for (i, alias) in aliases.into_iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				push_json_str(&mut out, alias);
+			}
+			let inserted = slot.inserted.duration_since(UNIX_EPOCH).unwrap_or_default();
+			let _ = write!(
+				out,
+				"],\"inserted_at_ms\":{},\"initialized\":{},\"cloneable\":{},\"frozen\":{}",
+				inserted.as_millis(),
+				slot.try_get().is_some(),
+				slot.is_cloneable(),
+				slot.frozen
+			);
+			match slot.mem_size() {
+				Some(size) => {
+					let _ = write!(out, ",\"size\":{}", size);
+				},
+				None => out.push_str(",\"size\":null"),
+			}
+			#[cfg(feature = "metrics")]
+			{
+				let m = metrics.get(slot.name).copied().unwrap_or_default();
+				let _ = write!(
+					out,
+					",\"metrics\":{{\"puts\":{},\"hits\":{},\"misses\":{},\"takes\":{},\"panics\":{}}}",
+					m.puts, m.hits, m.misses, m.takes, m.panics
+				);
+			}
+			out.push('}');
+		}
+		out.push_str("]}");
+		out
+	}
+}
+
+/// Appends `s` to `out` as a quoted and escaped JSON string.
+fn push_json_str(out: &mut String, s: &str) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(out, "\\u{:04x}", c as u32);
+			},
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
@@ -0,0 +1,103 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+
+/// The differences between two `State` containers, as returned by
+/// [`State::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+	/// The names of the types only present in the left `State`.
+	pub only_left: Vec<&'static str>,
+	/// The names of the types only present in the right `State`.
+	pub only_right: Vec<&'static str>,
+	/// The names of the types present in both, whose values were put with
+	/// [`put_comparable`](State::put_comparable) and are not equal.
+	pub changed: Vec<&'static str>,
+}
+
+impl StateDiff {
+	/// Determines if no differences were found.
+	pub fn is_empty(&self) -> bool {
+		self.only_left.is_empty() && self.only_right.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl State {
+	/// Puts a value into the `State` storage like [`put`](State::put), and
+	/// records how to compare it, so that differing values are reported by
+	/// [`diff`](State::diff).
+	pub fn put_comparable<T: PartialEq + 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting comparable record to state for type_id `{:?}`",
+			type_id
+		);
+		self.insert_slot(type_id, Slot::comparable(t));
+	}
+
+	/// Compares this `State` with `other`. Types present in only one of them
+	/// are reported by name, and values present in both are compared if they
+	/// were put with [`put_comparable`](State::put_comparable). Other values
+	/// are compared by presence only.
+	pub fn diff(&self, other: &State) -> StateDiff {
+		let mut diff = StateDiff::default();
+		for (type_id, slot) in self.data.iter() {
+			match other.data.get(type_id) {
+				None => diff.only_left.push(slot.name),
+				Some(theirs) => {
+					if slot.eq_value(theirs) == Some(false) {
+						diff.changed.push(slot.name);
+					}
+				},
+			}
+		}
+		for (type_id, slot) in other.data.iter() {
+			if !self.data.contains_key(type_id) {
+				diff.only_right.push(slot.name);
+			}
+		}
+		diff
+	}
+}
+
+impl fmt::Display for StateDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let lines = (self.only_left.iter().map(|name| ('-', name)))
+			.chain(self.only_right.iter().map(|name| ('+', name)))
+			.chain(self.changed.iter().map(|name| ('~', name)));
+		for (i, (sign, name)) in lines.enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{} {}", sign, name)?;
+		}
+		Ok(())
+	}
+}
+
+/// Asserts that two `State` containers hold the same types, and equal values
+/// for the types put with [`put_comparable`](crate::State::put_comparable).
+///
+/// On failure, the panic message lists the differences found by
+/// [`State::diff`](crate::State::diff).
+#[macro_export]
+macro_rules! assert_state_eq {
+	($left:expr, $right:expr $(,)?) => {{
+		let diff = $crate::State::diff(&$left, &$right);
+		if !diff.is_empty() {
+			panic!("assertion failed: states are not equal\n{}", diff);
+		}
+	}};
+	($left:expr, $right:expr, $($arg:tt)+) => {{
+		let diff = $crate::State::diff(&$left, &$right);
+		if !diff.is_empty() {
+			panic!(
+				"assertion failed: states are not equal: {}\n{}",
+				::core::format_args!($($arg)+),
+				diff
+			);
+		}
+	}};
+}
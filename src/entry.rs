@@ -0,0 +1,207 @@
+use crate::event::Hooks;
+use crate::slab;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateEvent;
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+/// A view into the slot for type `T` in a `State`, which may either be
+/// vacant or occupied.
+///
+/// This is constructed by [`State::entry`](crate::State::entry).
+pub enum Entry<'a, T: 'static> {
+	/// A value of type `T` is present.
+	Occupied(OccupiedEntry<'a, T>),
+	/// No value of type `T` is present.
+	Vacant(VacantEntry<'a, T>),
+}
+
+/// A view into an occupied slot in a `State`. Part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, T: 'static> {
+	inner: slab::OccupiedEntry<'a, Slot>,
+	hooks: &'a Hooks,
+	marker: PhantomData<T>,
+}
+
+/// A view into a vacant slot in a `State`. Part of the [`Entry`] enum.
+pub struct VacantEntry<'a, T: 'static> {
+	state: &'a mut State,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T: 'static> Entry<'a, T> {
+	pub(crate) fn new(state: &'a mut State) -> Self {
+		let type_id = TypeId::of::<T>();
+		if !state.data.contains_key(&type_id) {
+			return Entry::Vacant(VacantEntry {
+				state,
+				marker: PhantomData,
+			});
+		}
+		let State { data, hooks, .. } = state;
+		let inner = data.occupied(type_id).expect("slot was found above");
+		Entry::Occupied(OccupiedEntry {
+			inner,
+			hooks,
+			marker: PhantomData,
+		})
+	}
+
+	/// Ensures a value is present by inserting `default` if the slot is
+	/// vacant, and returns a mutable reference to the value.
+	pub fn or_insert(self, default: T) -> &'a mut T {
+		match self {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => entry.insert(default),
+		}
+	}
+
+	/// Ensures a value is present by inserting the result of `default` if the
+	/// slot is vacant, and returns a mutable reference to the value.
+	pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+		match self {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => entry.insert(default()),
+		}
+	}
+
+	/// Ensures a value is present by inserting the result of `default` if the
+	/// slot is vacant, and returns a mutable reference to the value. If
+	/// `default` fails, the slot is left vacant and the error is returned.
+	pub fn or_try_insert_with<E, F: FnOnce() -> Result<T, E>>(
+		self,
+		default: F,
+	) -> Result<&'a mut T, E> {
+		match self {
+			Entry::Occupied(entry) => Ok(entry.into_mut()),
+			Entry::Vacant(entry) => Ok(entry.insert(default()?)),
+		}
+	}
+
+	/// Provides in-place mutable access to an occupied slot before any
+	/// potential inserts.
+	pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+		match self {
+			Entry::Occupied(mut entry) => {
+				f(entry.get_mut());
+				Entry::Occupied(entry)
+			},
+			Entry::Vacant(entry) => Entry::Vacant(entry),
+		}
+	}
+}
+
+impl<'a, T: Default + 'static> Entry<'a, T> {
+	/// Ensures a value is present by inserting `T::default()` if the slot is
+	/// vacant, and returns a mutable reference to the value.
+	pub fn or_default(self) -> &'a mut T {
+		self.or_insert_with(T::default)
+	}
+}
+
+impl<'a, T: 'static> OccupiedEntry<'a, T> {
+	/// Gets a reference to the value in the slot.
+	pub fn get(&self) -> &T {
+		self.inner
+			.get()
+			.try_get()
+			.and_then(|v| v.downcast_ref())
+			.expect("slot holds its type")
+	}
+
+	/// Gets a mutable reference to the value in the slot.
+	pub fn get_mut(&mut self) -> &mut T {
+		self.inner
+			.get_mut()
+			.get_mut()
+			.and_then(|v| v.downcast_mut())
+			.expect("slot holds its type")
+	}
+
+	/// Converts the entry into a mutable reference to the value in the slot
+	/// with the lifetime of the `State`.
+	pub fn into_mut(self) -> &'a mut T {
+		self.inner
+			.into_mut()
+			.get_mut()
+			.and_then(|v| v.downcast_mut())
+			.expect("slot holds its type")
+	}
+
+	/// Replaces the value in the slot and returns the old value.
+	pub fn insert(&mut self, t: T) -> T {
+		self.emit(|type_id, name| StateEvent::Overwritten { type_id, name });
+		core::mem::replace(self.get_mut(), t)
+	}
+
+	/// Moves the value out of the `State` storage and returns ownership.
+	pub fn remove(self) -> T {
+		self.emit(|type_id, name| StateEvent::Removed { type_id, name });
+		*self
+			.inner
+			.remove()
+			.into_value()
+			.and_then(|b| b.downcast().ok())
+			.expect("slot holds its type")
+	}
+
+	fn emit(&self, event: fn(TypeId, &'static str) -> StateEvent) {
+		self.hooks
+			.emit(event(*self.inner.key(), self.inner.get().name));
+	}
+}
+
+impl<'a, T: 'static> VacantEntry<'a, T> {
+	/// Puts the value into the vacant slot like [`State::put`], evicting
+	/// values if the `State` exceeds its limit, and returns a mutable
+	/// reference to it.
+	pub fn insert(self, t: T) -> &'a mut T {
+		let type_id = TypeId::of::<T>();
+		let state = self.state;
+		state.insert_slot(type_id, Slot::new(t));
+		state
+			.data
+			.get_mut(&type_id)
+			.and_then(|slot| slot.get_mut())
+			.and_then(|v| v.downcast_mut())
+			.expect("slot holds its type")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::EvictionPolicy;
+	use crate::Limit;
+	use crate::State;
+	use crate::StateEvent;
+	use alloc::rc::Rc;
+	use core::cell::Cell;
+
+	#[test]
+	fn vacant_insert_puts_the_value() {
+		let inserted = Rc::new(Cell::new(0));
+		let mut state = State::with_limit(Limit::Entries(1), EvictionPolicy::Fifo);
+		let count = inserted.clone();
+		state.on_change(move |event| {
+			if let StateEvent::Inserted { .. } = event {
+				count.set(count.get() + 1);
+			}
+		});
+		state.put_evictable(1u8);
+		*state.entry::<u16>().or_insert(1) += 1;
+		assert_eq!(state.borrow::<u16>(), &2);
+		assert!(!state.has::<u8>());
+		assert_eq!(inserted.get(), 2);
+	}
+
+	#[test]
+	fn occupied_entries_keep_the_value() {
+		let mut state = State::default();
+		state.put(1u8);
+		assert_eq!(*state.entry::<u8>().or_insert(2), 1);
+		*state.entry::<u8>().and_modify(|v| *v += 1).or_default() += 1;
+		assert_eq!(state.borrow::<u8>(), &3);
+		assert!(!state.has::<u16>());
+	}
+}
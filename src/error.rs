@@ -0,0 +1,169 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::error::Error;
+use core::fmt;
+
+/// Errors returned by the non-panicking accessors of the state containers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+	/// No value of the requested type is present.
+	MissingType {
+		/// The name of the requested type.
+		name: &'static str,
+	},
+	/// The value is present but cannot be borrowed because doing so would
+	/// conflict with an outstanding borrow.
+	BorrowConflict {
+		/// The name of the requested type.
+		name: &'static str,
+	},
+	/// The value is present but has been frozen, so it cannot be replaced,
+	/// mutated or removed.
+	Frozen {
+		/// The name of the requested type.
+		name: &'static str,
+	},
+	/// The value was being written when another thread panicked, and the
+	/// container is set to propagate such panics.
+	Poisoned {
+		/// The name of the requested type.
+		name: &'static str,
+	},
+	/// The value may not be accessed through a
+	/// [`Restricted`](crate::Restricted) view.
+	Restricted {
+		/// The name of the requested type.
+		name: &'static str,
+	},
+	/// Values were not put with a clone function, so the container holding
+	/// them cannot be cloned.
+	NotCloneable {
+		/// The names of the types that cannot be cloned.
+		names: Vec<&'static str>,
+	},
+	/// Factories depend on each other in a cycle, so none of them can be
+	/// constructed first.
+	Cycle {
+		/// The names of the types in the cycle, in dependency order, starting
+		/// and ending with the same type.
+		names: Vec<&'static str>,
+	},
+	/// Values of the same types are present in both containers being merged.
+	Conflict {
+		/// The names of the conflicting types.
+		names: Vec<&'static str>,
+	},
+}
+
+impl StateError {
+	pub(crate) fn missing<T: 'static>() -> Self {
+		StateError::MissingType {
+			name: type_name::<T>(),
+		}
+	}
+
+	pub(crate) fn frozen<T: 'static>() -> Self {
+		StateError::Frozen {
+			name: type_name::<T>(),
+		}
+	}
+
+	pub(crate) fn restricted<T: 'static>() -> Self {
+		StateError::Restricted {
+			name: type_name::<T>(),
+		}
+	}
+
+	pub(crate) fn conflict<T: 'static>() -> Self {
+		StateError::BorrowConflict {
+			name: type_name::<T>(),
+		}
+	}
+}
+
+impl fmt::Display for StateError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StateError::MissingType { name } => write!(
+				f,
+				"required type {} is not present in State container",
+				name
+			),
+			StateError::BorrowConflict { name } => write!(
+				f,
+				"required type {} is already borrowed in State container",
+				name
+			),
+			StateError::Frozen { name } => {
+				write!(f, "required type {} is frozen in State container", name)
+			},
+			StateError::Poisoned { name } => write!(
+				f,
+				"required type {} was poisoned by a panic in State container",
+				name
+			),
+			StateError::Restricted { name } => write!(
+				f,
+				"required type {} is not accessible through this State view",
+				name
+			),
+			StateError::NotCloneable { names } => write!(
+				f,
+				"types {} cannot be cloned in State container",
+				names.join(", ")
+			),
+			StateError::Cycle { names } => write!(
+				f,
+				"types {} depend on each other in State container",
+				names.join(" -> ")
+			),
+			StateError::Conflict { names } => write!(
+				f,
+				"types {} are present in both State containers",
+				names.join(", ")
+			),
+		}
+	}
+}
+
+impl Error for StateError {}
+
+/// Returns the `stored` type names that are likely what was meant when
+/// `requested` was not found: the same type under a different path, such as
+/// from another version of the same crate, or the type with one wrapper more
+/// or less, such as `Arc<Config>` for `Config`.
+pub(crate) fn near_misses<'a>(
+	requested: &str,
+	stored: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+	let requested = strip_paths(requested);
+	stored
+		.into_iter()
+		.filter(|name| {
+			let name = strip_paths(name);
+			name == requested
+				|| name.contains(&format!("<{}>", requested))
+				|| requested.contains(&format!("<{}>", name))
+		})
+		.collect()
+}
+
+/// Removes the module paths from every path in a type name, turning
+/// `alloc::sync::Arc<app::Config>` into `Arc<Config>`.
+fn strip_paths(name: &str) -> String {
+	let mut out = String::with_capacity(name.len());
+	let mut segment = 0;
+	for c in name.chars() {
+		match c {
+			':' => out.truncate(segment),
+			c if c.is_alphanumeric() || c == '_' => out.push(c),
+			c => {
+				out.push(c);
+				segment = out.len();
+			},
+		}
+	}
+	out
+}
@@ -0,0 +1,143 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+/// A structural change to a `State`, reported to the hooks registered with
+/// [`State::on_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateEvent {
+	/// A value was put for a type that was not present.
+	Inserted { type_id: TypeId, name: &'static str },
+	/// A value was put for a type that was already present, replacing it.
+	Overwritten { type_id: TypeId, name: &'static str },
+	/// A value was taken or dropped.
+	Removed { type_id: TypeId, name: &'static str },
+}
+
+impl StateEvent {
+	/// Returns the `TypeId` of the slot the event applies to.
+	pub fn type_id(&self) -> TypeId {
+		match *self {
+			StateEvent::Inserted { type_id, .. }
+			| StateEvent::Overwritten { type_id, .. }
+			| StateEvent::Removed { type_id, .. } => type_id,
+		}
+	}
+
+	/// Returns the name of the type of the value the event applies to, as
+	/// recorded when the value was put.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			StateEvent::Inserted { name, .. }
+			| StateEvent::Overwritten { name, .. }
+			| StateEvent::Removed { name, .. } => name,
+		}
+	}
+}
+
+/// The hooks registered with [`State::on_change`].
+#[derive(Default)]
+pub(crate) struct Hooks(Vec<Box<dyn Fn(StateEvent)>>);
+
+impl Hooks {
+	pub(crate) fn emit(&self, event: StateEvent) {
+		for hook in &self.0 {
+			hook(event);
+		}
+	}
+}
+
+impl State {
+	/// Registers a hook that is called with a [`StateEvent`] whenever a value
+	/// is inserted into, overwritten in or removed from the `State` storage.
+	///
+	/// Hooks are called before the change is applied, so a hook can veto a
+	/// change, such as overwriting a value of a certain type, by panicking.
+	pub fn on_change(&mut self, hook: impl Fn(StateEvent) + 'static) {
+		trace!(" registering change hook to state");
+		self.hooks.0.push(Box::new(hook));
+	}
+
+	/// Inserts `slot`, reporting whether it is inserted or overwrites an
+	/// existing slot to the hooks, and returns the slot it overwrites.
+	/// Panics if the existing slot is frozen.
+	pub(crate) fn insert_slot(&mut self, type_id: TypeId, mut slot: Slot) -> Option<Slot> {
+		self.settle_fallback();
+		self.assert_not_frozen(type_id);
+		if slot.priority.is_none() {
+			slot.priority = self.data.get(&type_id).and_then(|s| s.priority);
+		}
+		let name = slot.name;
+		self.hooks.emit(if self.data.contains_key(&type_id) {
+			StateEvent::Overwritten { type_id, name }
+		} else {
+			StateEvent::Inserted { type_id, name }
+		});
+		metrics!(self.metrics.put(name));
+		let replaced = self.data.insert(type_id, slot);
+		self.evict(Some(type_id));
+		replaced
+	}
+
+	/// Removes the slot with the given `TypeId`, reporting the removal to the
+	/// hooks if it was present.
+	pub(crate) fn remove_slot(&mut self, type_id: TypeId) -> Option<Slot> {
+		let name = self.data.get(&type_id)?.name;
+		self.hooks.emit(StateEvent::Removed { type_id, name });
+		self.data.remove(&type_id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::rc::Rc;
+	use core::cell::RefCell;
+
+	fn recorded(state: &mut State) -> Rc<RefCell<Vec<StateEvent>>> {
+		let events: Rc<RefCell<Vec<StateEvent>>> = Rc::default();
+		let log = events.clone();
+		state.on_change(move |event| log.borrow_mut().push(event));
+		events
+	}
+
+	#[test]
+	fn hooks_see_every_change() {
+		let mut state = State::default();
+		let events = recorded(&mut state);
+		state.put(1u8);
+		state.put(2u8);
+		state.take::<u8>();
+		let type_id = TypeId::of::<u8>();
+		let name = "u8";
+		assert_eq!(
+			*events.borrow(),
+			[
+				StateEvent::Inserted { type_id, name },
+				StateEvent::Overwritten { type_id, name },
+				StateEvent::Removed { type_id, name },
+			]
+		);
+		assert!(events
+			.borrow()
+			.iter()
+			.all(|e| e.type_id() == type_id && e.name() == name));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn panicking_hook_vetoes_the_change() {
+		let mut state = State::default();
+		state.put(1u8);
+		state.on_change(|event| {
+			if let StateEvent::Overwritten { .. } = event {
+				panic!("u8 is read-only");
+			}
+		});
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| state.put(2u8)));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<u8>(), &1);
+	}
+}
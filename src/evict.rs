@@ -0,0 +1,230 @@
+use crate::slot::Slot;
+use crate::MemSize;
+use crate::State;
+use core::any::TypeId;
+use core::cell::Cell;
+
+/// The capacity of a `State` created with [`State::with_limit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	/// The approximate number of bytes used by the values put with
+	/// [`put_sized`](State::put_sized) or
+	/// [`put_evictable`](State::put_evictable).
+	Bytes(usize),
+	/// The number of values in the `State`.
+	Entries(usize),
+}
+
+/// Which evictable value is dropped first when a `State` exceeds its
+/// [`Limit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+	/// The value that was borrowed least recently.
+	#[default]
+	Lru,
+	/// The value that was borrowed the fewest times.
+	Lfu,
+	/// The value that was put first.
+	Fifo,
+}
+
+/// The limit and policy of a capped `State`, together with the logical
+/// clock used to order accesses.
+#[derive(Debug)]
+pub(crate) struct Eviction {
+	limit: Limit,
+	policy: EvictionPolicy,
+	clock: Cell<u64>,
+}
+
+/// The access history of an evictable slot.
+#[derive(Debug)]
+pub(crate) struct Usage {
+	inserted: u64,
+	last_used: Cell<u64>,
+	uses: Cell<u64>,
+}
+
+impl State {
+	/// Creates an empty `State` that holds at most `limit`, dropping values
+	/// put with [`put_evictable`](State::put_evictable) according to `policy`
+	/// whenever a value is put and the limit is exceeded. Values put by any
+	/// other means are never evicted, but still count towards the limit.
+	/// The value being put is never evicted by that put, so a `State` with
+	/// nothing else to evict stays over its limit until
+	/// [`enforce_limit`](State::enforce_limit) is called.
+	pub fn with_limit(limit: Limit, policy: EvictionPolicy) -> Self {
+		State {
+			eviction: Some(Eviction {
+				limit,
+				policy,
+				clock: Cell::new(0),
+			}),
+			..State::default()
+		}
+	}
+
+	/// Puts a value into the `State` storage like
+	/// [`put_sized`](State::put_sized), and marks it as evictable, so it may
+	/// be dropped to keep the `State` within the limit given to
+	/// [`with_limit`](State::with_limit).
+	///
+	/// # Panics
+	///
+	/// If the existing value of type `T` has been frozen.
+	pub fn put_evictable<T: MemSize + 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting evictable record to state for type_id `{:?}`",
+			type_id
+		);
+		let mut slot = Slot::sized(t);
+		slot.usage = Some(Usage {
+			inserted: self.tick(),
+			last_used: Cell::new(0),
+			uses: Cell::new(0),
+		});
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Evicts values until the `State` is within its limit, returning the
+	/// number of values evicted. Does nothing if the `State` was not created
+	/// with [`with_limit`](State::with_limit).
+	pub fn enforce_limit(&mut self) -> usize {
+		self.evict(None)
+	}
+
+	/// Evicts values until the `State` is within its limit, sparing the
+	/// value with the given `TypeId`, such as the one that was just put.
+	pub(crate) fn evict(&mut self, spare: Option<TypeId>) -> usize {
+		let Some(eviction) = &self.eviction else {
+			return 0;
+		};
+		let (limit, policy) = (eviction.limit, eviction.policy);
+		let mut evicted = 0;
+		while self.exceeds(limit) {
+			let victim = self
+				.data
+				.iter()
+				.filter(|(type_id, slot)| Some(**type_id) != spare && !slot.frozen)
+				.filter_map(|(type_id, slot)| Some((*type_id, slot.usage.as_ref()?)))
+				.min_by_key(|(_, usage)| match policy {
+					EvictionPolicy::Lru => (usage.last_used.get(), usage.inserted),
+					EvictionPolicy::Lfu => (usage.uses.get(), usage.inserted),
+					EvictionPolicy::Fifo => (usage.inserted, 0),
+				})
+				.map(|(type_id, _)| type_id);
+			let Some(type_id) = victim else {
+				break;
+			};
+			trace!(" evicting state data for type_id `{:?}`", type_id);
+			self.remove_slot(type_id);
+			evicted += 1;
+		}
+		evicted
+	}
+
+	/// Records an access to the value with the given `TypeId` for the
+	/// eviction policy.
+	pub(crate) fn touch(&self, type_id: TypeId) {
+		if self.eviction.is_none() {
+			return;
+		}
+		if let Some(usage) = self.data.get(&type_id).and_then(|s| s.usage.as_ref()) {
+			usage.last_used.set(self.tick());
+			usage.uses.set(usage.uses.get() + 1);
+		}
+	}
+
+	fn exceeds(&self, limit: Limit) -> bool {
+		match limit {
+			Limit::Bytes(bytes) => self.memory_usage().total > bytes,
+			Limit::Entries(entries) => self.data.len() > entries,
+		}
+	}
+
+	fn tick(&self) -> u64 {
+		let Some(eviction) = &self.eviction else {
+			return 0;
+		};
+		let now = eviction.clock.get() + 1;
+		eviction.clock.set(now);
+		now
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+	use alloc::vec::Vec;
+
+	fn evictable(policy: EvictionPolicy, entries: usize) -> State {
+		let mut state = State::with_limit(Limit::Entries(entries), policy);
+		state.put_evictable(1u8);
+		state.put_evictable(2u16);
+		state.put_evictable(3u32);
+		state
+	}
+
+	#[test]
+	fn lru_evicts_the_value_borrowed_least_recently() {
+		let mut state = evictable(EvictionPolicy::Lru, 3);
+		state.borrow::<u32>();
+		state.borrow::<u8>();
+		state.borrow::<u16>();
+		state.put_evictable(4u64);
+		assert!(!state.has::<u32>());
+		state.borrow::<u64>();
+		state.put_evictable(5i8);
+		assert!(!state.has::<u8>());
+		assert!(state.has::<u16>() && state.has::<u64>() && state.has::<i8>());
+	}
+
+	#[test]
+	fn lfu_evicts_the_value_borrowed_fewest_times() {
+		let mut state = evictable(EvictionPolicy::Lfu, 3);
+		for _ in 0..3 {
+			state.borrow::<u8>();
+		}
+		state.borrow::<u16>();
+		state.borrow::<u32>();
+		state.borrow::<u32>();
+		state.put_evictable(4u64);
+		assert!(!state.has::<u16>());
+		state.put_evictable(5i8);
+		assert!(!state.has::<u64>());
+		assert!(state.has::<u8>() && state.has::<u32>() && state.has::<i8>());
+	}
+
+	#[test]
+	fn values_that_are_not_evictable_are_left_alone() {
+		let mut state = State::with_limit(Limit::Entries(2), EvictionPolicy::Lru);
+		state.put(1u8);
+		state.put_evictable(2u16);
+		state.put_evictable(3u32);
+		assert!(state.has::<u8>() && !state.has::<u16>());
+		state.freeze_type::<u32>();
+		state.put(4u64);
+		state.put(5i8);
+		assert_eq!(state.len(), 4);
+		assert_eq!(state.enforce_limit(), 0);
+	}
+
+	#[test]
+	fn the_value_being_put_is_spared() {
+		let mut state = State::with_limit(Limit::Entries(2), EvictionPolicy::Lfu);
+		state.put_evictable(1u8);
+		state.put_evictable(2u16);
+		state.borrow::<u8>();
+		state.borrow::<u16>();
+		state.put_evictable(3u32);
+		assert!(!state.has::<u8>() && state.has::<u16>() && state.has::<u32>());
+
+		let mut state = State::with_limit(Limit::Bytes(64), EvictionPolicy::Lru);
+		state.put_evictable(vec![0u8; 100]);
+		assert!(state.has::<Vec<u8>>());
+		assert_eq!(state.enforce_limit(), 1);
+		assert!(state.is_empty());
+	}
+}
@@ -0,0 +1,48 @@
+use crate::StateRead;
+use crate::StateWrite;
+
+/// Closure-based access to the values of any state container, keeping each
+/// borrow scoped to the closure.
+///
+/// Implemented for every [`StateRead`] container.
+pub trait StateExt: StateRead {
+	/// Borrows the value of type `T` for the duration of `f` and returns
+	/// its result.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the container.
+	fn with<T: 'static, R>(&self, f: impl FnOnce(&T) -> R) -> R {
+		f(self.borrow())
+	}
+
+	/// Borrows the value of type `T` for the duration of `f` and returns
+	/// its result, or `None` if no such value is present.
+	fn try_with<T: 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+		self.try_borrow().map(f)
+	}
+
+	/// Mutably borrows the value of type `T` for the duration of `f` and
+	/// returns its result.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the container.
+	fn with_mut<T: 'static, R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R
+	where
+		Self: StateWrite,
+	{
+		f(self.borrow_mut())
+	}
+
+	/// Mutably borrows the value of type `T` for the duration of `f` and
+	/// returns its result, or `None` if no such value is present.
+	fn try_with_mut<T: 'static, R>(&mut self, f: impl FnOnce(&mut T) -> R) -> Option<R>
+	where
+		Self: StateWrite,
+	{
+		self.try_borrow_mut().map(f)
+	}
+}
+
+impl<S: StateRead> StateExt for S {}
@@ -0,0 +1,35 @@
+use crate::State;
+use crate::StateError;
+
+/// A set of dependencies that are pulled out of a `State` together, usually
+/// a struct deriving `Extract` with the `derive` feature:
+///
+/// ```ignore
+/// #[derive(Extract)]
+/// struct HandlerDeps<'a> {
+///     db: &'a DbPool,
+///     cfg: &'a mut AppConfig,
+///     request_id: RequestId,
+/// }
+///
+/// let deps = HandlerDeps::extract(&mut state);
+/// ```
+///
+/// Derived implementations borrow fields of type `&'a T`, mutably borrow
+/// fields of type `&'a mut T`, and take fields of any other type.
+pub trait Extract<'a>: Sized {
+	/// Tries to pull the dependencies out of the `State` storage. Nothing is
+	/// taken if any of them is missing, frozen while it is to be taken or
+	/// mutably borrowed, or requested more than once.
+	fn try_extract(state: &'a mut State) -> Result<Self, StateError>;
+
+	/// Pulls the dependencies out of the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If any of the dependencies cannot be pulled out, as with
+	/// [`try_extract`](Extract::try_extract).
+	fn extract(state: &'a mut State) -> Self {
+		Self::try_extract(state).unwrap_or_else(|e| panic!("{}", e))
+	}
+}
@@ -0,0 +1,308 @@
+use crate::slot::Init;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateError;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::TypeId;
+use core::cell::Cell;
+
+/// A tuple of the types a factory reads, such as `(A, B)`, as declared with
+/// [`State::register_factory_with_deps`].
+pub trait Dependencies {
+	/// The `TypeId`s and names of the types, in tuple order.
+	fn dependencies() -> Vec<(TypeId, &'static str)>;
+}
+
+impl Dependencies for () {
+	fn dependencies() -> Vec<(TypeId, &'static str)> {
+		Vec::new()
+	}
+}
+
+macro_rules! impl_dependencies {
+	($($name:ident),+) => {
+		impl<$($name: 'static),+> Dependencies for ($($name,)+) {
+			fn dependencies() -> Vec<(TypeId, &'static str)> {
+				vec![$((TypeId::of::<$name>(), type_name::<$name>())),+]
+			}
+		}
+	};
+}
+
+impl_dependencies!(A);
+impl_dependencies!(A, B);
+impl_dependencies!(A, B, C);
+impl_dependencies!(A, B, C, D);
+impl_dependencies!(A, B, C, D, E);
+impl_dependencies!(A, B, C, D, E, F);
+impl_dependencies!(A, B, C, D, E, F, G);
+impl_dependencies!(A, B, C, D, E, F, G, H);
+
+impl State {
+	/// Registers a factory that constructs the value of type `T` the first
+	/// time it is accessed, for example by [`borrow`](State::borrow). The
+	/// factory may read other values from the `State`, and the value it
+	/// builds is cached in place of the factory.
+	///
+	/// Registering a factory replaces any existing value of type `T`, and
+	/// [`has`](State::has) reports the value as present from now on.
+	pub fn register_factory<T: 'static>(&mut self, factory: impl Fn(&State) -> T + 'static) {
+		let type_id = TypeId::of::<T>();
+		trace!(" registering factory to state for type_id `{:?}`", type_id);
+		self.insert_slot(
+			type_id,
+			Slot::lazy::<T>(Init::Factory(Rc::new(move |state| {
+				Box::new(factory(state))
+			}))),
+		);
+	}
+
+	/// Puts a value into the `State` storage that is only constructed by
+	/// calling `f` the first time it is accessed, for example by
+	/// [`borrow`](State::borrow). Expensive resources can be declared up
+	/// front this way but are only built if they are actually used.
+	pub fn put_lazy<T: 'static>(&mut self, f: impl FnOnce() -> T + 'static) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting lazy record to state for type_id `{:?}`",
+			type_id
+		);
+		let f = Cell::new(Some(f));
+		self.insert_slot(
+			type_id,
+			Slot::lazy::<T>(Init::Once(Rc::new(move |_| {
+				let f = f.take().expect("lazy value is constructed once");
+				Box::new(f())
+			}))),
+		);
+	}
+
+	/// Registers a factory like [`register_factory`](State::register_factory)
+	/// and declares the types `D` it reads, such as `(A, B)`, so that
+	/// [`initialize_all`](State::initialize_all) constructs them first.
+	pub fn register_factory_with_deps<T: 'static, D: Dependencies>(
+		&mut self,
+		factory: impl Fn(&State) -> T + 'static,
+	) {
+		self.register_factory(factory);
+		if let Some(slot) = self.data.get_mut(&TypeId::of::<T>()) {
+			slot.deps = D::dependencies();
+		}
+	}
+
+	/// Constructs every value that is still waiting on its factory, each
+	/// after the values its factory declared as dependencies. Nothing is
+	/// constructed if the dependencies form a cycle, which is reported with
+	/// [`StateError::Cycle`], or if a dependency is not present.
+	pub fn initialize_all(&self) -> Result<(), StateError> {
+		trace!(" initializing all state data");
+		let mut order = Vec::new();
+		let mut visited = BTreeSet::new();
+		let mut path = Vec::new();
+		for (type_id, slot) in self.data.iter() {
+			if slot.is_pending() {
+				self.visit(*type_id, slot.name, &mut path, &mut visited, &mut order)?;
+			}
+		}
+		for type_id in order {
+			self.force(type_id);
+		}
+		Ok(())
+	}
+
+	/// Appends the value with the given `TypeId` to `order` after its
+	/// dependencies, keeping the types being visited in `path` to detect
+	/// cycles.
+	fn visit(
+		&self,
+		type_id: TypeId,
+		name: &'static str,
+		path: &mut Vec<(TypeId, &'static str)>,
+		visited: &mut BTreeSet<TypeId>,
+		order: &mut Vec<TypeId>,
+	) -> Result<(), StateError> {
+		if visited.contains(&type_id) {
+			return Ok(());
+		}
+		if let Some(i) = path.iter().position(|(id, _)| *id == type_id) {
+			let mut names: Vec<_> = path[i..].iter().map(|(_, name)| *name).collect();
+			names.push(name);
+			return Err(StateError::Cycle { names });
+		}
+		let slot = self
+			.data
+			.get(&type_id)
+			.filter(|slot| !slot.is_expired())
+			.ok_or(StateError::MissingType { name })?;
+		path.push((type_id, name));
+		for (dep, dep_name) in &slot.deps {
+			self.visit(*dep, dep_name, path, visited, order)?;
+		}
+		path.pop();
+		visited.insert(type_id);
+		order.push(type_id);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(feature = "std")]
+	use alloc::string::String;
+	use core::cell::RefCell;
+	#[cfg(feature = "std")]
+	use core::panic::AssertUnwindSafe;
+	#[cfg(feature = "std")]
+	use std::panic::catch_unwind;
+
+	#[cfg(feature = "std")]
+	fn panic_message(f: impl FnOnce()) -> String {
+		let error = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+		match error.downcast_ref::<&str>() {
+			Some(message) => String::from(*message),
+			None => error.downcast_ref::<String>().unwrap().clone(),
+		}
+	}
+
+	#[test]
+	fn factory_constructs_the_value_on_first_access() {
+		let built = Rc::new(Cell::new(0));
+		let mut state = State::default();
+		let count = built.clone();
+		state.register_factory(move |state| {
+			count.set(count.get() + 1);
+			u16::from(*state.borrow::<u8>()) + 1
+		});
+		state.put(1u8);
+		assert!(state.has::<u16>());
+		assert_eq!(built.get(), 0);
+		assert_eq!(state.borrow::<u16>(), &2);
+		*state.borrow_mut::<u16>() += 1;
+		assert_eq!(state.take::<u16>(), 3);
+		assert_eq!(built.get(), 1);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn factory_reading_a_missing_value_panics() {
+		let mut state = State::default();
+		state.register_factory(|state| u16::from(*state.borrow::<u8>()));
+		let message = panic_message(|| {
+			state.borrow::<u16>();
+		});
+		assert!(
+			message.contains("required type u8 is not present"),
+			"{message}"
+		);
+		state.put(1u8);
+		assert_eq!(state.borrow::<u16>(), &1);
+	}
+
+	#[test]
+	fn lazy_value_is_constructed_once_when_used() {
+		let built = Rc::new(Cell::new(0));
+		let mut state = State::default();
+		let count = built.clone();
+		state.put_lazy(move || {
+			count.set(count.get() + 1);
+			1u8
+		});
+		assert!(state.has::<u8>());
+		assert_eq!(built.get(), 0);
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert_eq!(state.take::<u8>(), 1);
+		assert_eq!(built.get(), 1);
+		let count = built.clone();
+		state.put_lazy(move || {
+			count.set(count.get() + 1);
+			2u8
+		});
+		state.put(3u8);
+		assert_eq!(state.take::<u8>(), 3);
+		assert_eq!(built.get(), 1);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn lazy_value_panics_again_after_a_failed_construction() {
+		let mut state = State::default();
+		state.put_lazy(|| -> u8 { panic!("connection refused") });
+		let message = panic_message(|| {
+			state.borrow::<u8>();
+		});
+		assert_eq!(message, "connection refused");
+		let message = panic_message(|| {
+			state.borrow::<u8>();
+		});
+		assert!(message.contains("constructed once"), "{message}");
+	}
+
+	#[test]
+	fn initialize_all_constructs_dependencies_first() {
+		let order: Rc<RefCell<Vec<&str>>> = Rc::default();
+		let mut state = State::default();
+		let log = order.clone();
+		state.register_factory_with_deps::<u32, (u16, u8)>(move |state| {
+			log.borrow_mut().push("u32");
+			u32::from(*state.borrow::<u16>()) + u32::from(*state.borrow::<u8>())
+		});
+		let log = order.clone();
+		state.register_factory_with_deps::<u16, (u8,)>(move |state| {
+			log.borrow_mut().push("u16");
+			u16::from(*state.borrow::<u8>()) * 2
+		});
+		let log = order.clone();
+		state.register_factory(move |_| {
+			log.borrow_mut().push("u8");
+			1u8
+		});
+		state.initialize_all().unwrap();
+		assert_eq!(*order.borrow(), ["u8", "u16", "u32"]);
+		assert_eq!(state.borrow::<u32>(), &3);
+	}
+
+	#[test]
+	fn initialize_all_reports_cycles() {
+		let built = Rc::new(Cell::new(0));
+		let mut state = State::default();
+		let count = built.clone();
+		state.register_factory(move |_| {
+			count.set(count.get() + 1);
+			1u32
+		});
+		state.register_factory_with_deps::<u8, (u16,)>(|state| *state.borrow::<u16>() as u8);
+		state.register_factory_with_deps::<u16, (u8,)>(|state| u16::from(*state.borrow::<u8>()));
+		let result = state.initialize_all();
+		assert!(
+			matches!(&result, Err(StateError::Cycle { names }) if names == &["u8", "u16", "u8"]),
+			"{result:?}"
+		);
+		assert_eq!(built.get(), 0);
+	}
+
+	#[test]
+	fn initialize_all_reports_missing_dependencies() {
+		let built = Rc::new(Cell::new(0));
+		let mut state = State::default();
+		let count = built.clone();
+		state.register_factory_with_deps::<u16, (u8,)>(move |state| {
+			count.set(count.get() + 1);
+			u16::from(*state.borrow::<u8>())
+		});
+		let result = state.initialize_all();
+		assert!(
+			matches!(result, Err(StateError::MissingType { name: "u8" })),
+			"{result:?}"
+		);
+		assert_eq!(built.get(), 0);
+		state.put(1u8);
+		state.initialize_all().unwrap();
+		assert_eq!(built.get(), 1);
+	}
+}
@@ -0,0 +1,163 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::OnceCell;
+
+type Provider = Box<dyn Fn(TypeId) -> Option<Box<dyn Any>>>;
+
+/// A value produced by the fallback provider for a shared lookup. Values are
+/// kept in an append-only list, so that they can be added through a shared
+/// reference while earlier values are borrowed.
+struct Produced {
+	type_id: TypeId,
+	name: &'static str,
+	value: Box<dyn Any>,
+	next: OnceCell<Box<Produced>>,
+}
+
+/// The provider installed with [`State::set_fallback`] and the values it
+/// produced since the `State` was last accessed mutably.
+#[derive(Default)]
+pub(crate) struct Fallback {
+	provider: Option<Provider>,
+	cache: bool,
+	produced: OnceCell<Box<Produced>>,
+}
+
+impl Fallback {
+	pub(crate) fn is_set(&self) -> bool {
+		self.provider.is_some()
+	}
+
+	/// Calls the provider for type `T`, ignoring values of any other type.
+	pub(crate) fn produce<T: 'static>(&self) -> Option<Box<dyn Any>> {
+		let provider = self.provider.as_ref()?;
+		trace!(" consulting fallback for type_id `{:?}`", TypeId::of::<T>());
+		provider(TypeId::of::<T>()).filter(|value| value.is::<T>())
+	}
+
+	/// Returns the value of type `T` produced for an earlier shared lookup,
+	/// or calls the provider and keeps the value it produces.
+	pub(crate) fn lookup<T: 'static>(&self) -> Option<&T> {
+		let type_id = TypeId::of::<T>();
+		let mut cell = &self.produced;
+		while let Some(produced) = cell.get() {
+			if produced.type_id == type_id {
+				return produced.value.downcast_ref();
+			}
+			cell = &produced.next;
+		}
+		let value = self.produce::<T>()?;
+		// The provider has no access to the `State`, so the cell is still
+		// empty.
+		let _ = cell.set(Box::new(Produced {
+			type_id,
+			name: type_name::<T>(),
+			value,
+			next: OnceCell::new(),
+		}));
+		cell.get()?.value.downcast_ref()
+	}
+
+	/// Drops the produced values without putting them.
+	pub(crate) fn forget(&mut self) {
+		self.produced.take();
+	}
+}
+
+impl State {
+	/// Installs a provider that is consulted when a lookup misses, such as to
+	/// read values from the environment or an external service locator
+	/// before giving up. It is given the `TypeId` of the requested type, and
+	/// values of any other type than the requested one are ignored.
+	///
+	/// Values produced for a shared borrow are kept until the `State` is next
+	/// accessed mutably, and values produced for a mutable borrow until the
+	/// next mutable access after that, at which point they are dropped. Use
+	/// [`set_cached_fallback`](State::set_cached_fallback) to keep them in
+	/// the `State` storage instead. [`has`](State::has) and the iterators do
+	/// not consult the provider.
+	///
+	/// Replaces the previous provider.
+	pub fn set_fallback(&mut self, provider: impl Fn(TypeId) -> Option<Box<dyn Any>> + 'static) {
+		trace!(" installing fallback provider to state");
+		self.settle_fallback();
+		self.fallback.provider = Some(Box::new(provider));
+		self.fallback.cache = false;
+	}
+
+	/// Installs a provider that is consulted when a lookup misses, like
+	/// [`set_fallback`](State::set_fallback), but the values it produces are
+	/// put into the `State` storage, so the provider is only consulted once
+	/// for each type. Values produced for a shared borrow are put once the
+	/// `State` is next accessed mutably.
+	///
+	/// Replaces the previous provider.
+	pub fn set_cached_fallback(
+		&mut self,
+		provider: impl Fn(TypeId) -> Option<Box<dyn Any>> + 'static,
+	) {
+		trace!(" installing caching fallback provider to state");
+		self.settle_fallback();
+		self.fallback.provider = Some(Box::new(provider));
+		self.fallback.cache = true;
+	}
+
+	/// Removes the provider installed with [`set_fallback`](State::set_fallback)
+	/// or [`set_cached_fallback`](State::set_cached_fallback).
+	pub fn remove_fallback(&mut self) {
+		self.settle_fallback();
+		self.fallback.provider = None;
+	}
+
+	/// Puts the values produced for shared lookups into the `State` storage
+	/// if the provider caches them, or drops them otherwise. Called on every
+	/// mutable access that may insert or hand out values.
+	pub(crate) fn settle_fallback(&mut self) {
+		let mut next = self.fallback.produced.take();
+		while let Some(produced) = next {
+			let Produced {
+				type_id,
+				name,
+				value,
+				next: rest,
+			} = *produced;
+			next = rest.into_inner();
+			if self.fallback.cache && !self.data.contains_key(&type_id) {
+				self.insert_slot(type_id, Slot::named(value, name));
+			}
+		}
+	}
+
+	/// Consults the provider for a value of type `T` that is missing, for a
+	/// mutable borrow. A cached value is put into the `State` storage, while
+	/// any other value is kept aside, to be returned by
+	/// [`fallback_mut`](State::fallback_mut). Returns whether a value was
+	/// produced.
+	pub(crate) fn fill_fallback<T: 'static>(&mut self) -> bool {
+		let Some(value) = self.fallback.produce::<T>() else {
+			return false;
+		};
+		let (type_id, name) = (TypeId::of::<T>(), type_name::<T>());
+		if self.fallback.cache {
+			self.insert_slot(type_id, Slot::named(value, name));
+			return true;
+		}
+		self.fallback.produced = OnceCell::from(Box::new(Produced {
+			type_id,
+			name,
+			value,
+			next: OnceCell::new(),
+		}));
+		true
+	}
+
+	/// Returns the value of type `T` kept aside by
+	/// [`fill_fallback`](State::fill_fallback), if any.
+	pub(crate) fn fallback_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.fallback.produced.get_mut()?.value.downcast_mut()
+	}
+}
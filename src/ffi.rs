@@ -0,0 +1,184 @@
+//! A C interface to `State`, over an opaque handle with string keys and
+//! byte values, so embedders in other languages can share a container with
+//! their Rust plugins.
+//!
+//! The byte values are kept in the `State` alongside the typed values, and
+//! can be accessed from Rust with [`State::put_bytes`] and
+//! [`State::get_bytes`].
+//!
+//! ```c
+//! GothamState *state = gotham_state_new();
+//! gotham_state_put_bytes(state, "token", data, len);
+//! size_t out_len;
+//! const uint8_t *token = gotham_state_get_bytes(state, "token", &out_len);
+//! gotham_state_free(state);
+//! ```
+
+use crate::State;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ffi::c_char;
+use core::ffi::CStr;
+
+/// The byte values of a `State`, by key.
+#[derive(Default)]
+struct ByteStore(BTreeMap<Vec<u8>, Vec<u8>>);
+
+impl State {
+	/// Puts a byte value under `key`, overwriting the existing value with the
+	/// same key. Byte values are shared with the C interface of the `ffi`
+	/// feature.
+	pub fn put_bytes(&mut self, key: impl AsRef<[u8]>, bytes: impl Into<Vec<u8>>) {
+		if !self.has::<ByteStore>() {
+			self.put(ByteStore::default());
+		}
+		self.borrow_mut::<ByteStore>()
+			.0
+			.insert(key.as_ref().to_vec(), bytes.into());
+	}
+
+	/// Tries to borrow the byte value under `key`.
+	pub fn get_bytes(&self, key: impl AsRef<[u8]>) -> Option<&[u8]> {
+		let bytes = self.try_borrow::<ByteStore>()?.0.get(key.as_ref())?;
+		Some(bytes)
+	}
+
+	/// Removes the byte value under `key` and returns it.
+	pub fn remove_bytes(&mut self, key: impl AsRef<[u8]>) -> Option<Vec<u8>> {
+		self.try_borrow_mut::<ByteStore>()?.0.remove(key.as_ref())
+	}
+}
+
+/// Creates an empty `State`, to be freed with [`gotham_state_free`].
+#[no_mangle]
+pub extern "C" fn gotham_state_new() -> *mut State {
+	Box::into_raw(Box::default())
+}
+
+/// Frees a `State` created with [`gotham_state_new`]. Does nothing if
+/// `state` is null.
+///
+/// # Safety
+///
+/// `state` must be null or a handle returned by [`gotham_state_new`] that
+/// has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn gotham_state_free(state: *mut State) {
+	if !state.is_null() {
+		drop(Box::from_raw(state));
+	}
+}
+
+/// Copies `len` bytes from `data` into the `State` under the
+/// nul-terminated `key`, overwriting the existing value with the same key.
+/// Returns `false` without doing anything if `state` or `key` is null, or if
+/// `data` is null while `len` is not zero.
+///
+/// # Safety
+///
+/// `state` must be null or a live handle returned by [`gotham_state_new`],
+/// `key` must be null or point to a nul-terminated string, and `data` must be
+/// null or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn gotham_state_put_bytes(
+	state: *mut State,
+	key: *const c_char,
+	data: *const u8,
+	len: usize,
+) -> bool {
+	let (Some(state), false) = (state.as_mut(), key.is_null()) else {
+		return false;
+	};
+	let bytes = match (data.is_null(), len) {
+		(_, 0) => &[][..],
+		(true, _) => return false,
+		(false, _) => core::slice::from_raw_parts(data, len),
+	};
+	state.put_bytes(CStr::from_ptr(key).to_bytes(), bytes);
+	true
+}
+
+/// Returns a pointer to the bytes stored under the nul-terminated `key`,
+/// writing their length to `out_len`. Returns null if no value is stored
+/// under `key`, or if `state` or `key` is null.
+///
+/// The pointer stays valid until the value is overwritten or the `State` is
+/// freed.
+///
+/// # Safety
+///
+/// `state` must be null or a live handle returned by [`gotham_state_new`],
+/// `key` must be null or point to a nul-terminated string, and `out_len`
+/// must be null or point to writable memory for a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn gotham_state_get_bytes(
+	state: *const State,
+	key: *const c_char,
+	out_len: *mut usize,
+) -> *const u8 {
+	let (Some(state), false) = (state.as_ref(), key.is_null()) else {
+		return core::ptr::null();
+	};
+	let Some(bytes) = state.get_bytes(CStr::from_ptr(key).to_bytes()) else {
+		return core::ptr::null();
+	};
+	if let Some(out_len) = out_len.as_mut() {
+		*out_len = bytes.len();
+	}
+	bytes.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bytes_round_trip_through_the_handle() {
+		unsafe {
+			let state = gotham_state_new();
+			let data = [1u8, 2, 3];
+			assert!(gotham_state_put_bytes(
+				state,
+				c"token".as_ptr(),
+				data.as_ptr(),
+				data.len()
+			));
+			let mut len = 0;
+			let bytes = gotham_state_get_bytes(state, c"token".as_ptr(), &mut len);
+			assert_eq!(core::slice::from_raw_parts(bytes, len), data);
+			assert_eq!((*state).remove_bytes("token"), Some(data.to_vec()));
+			assert!(gotham_state_get_bytes(state, c"token".as_ptr(), &mut len).is_null());
+			gotham_state_free(state);
+		}
+	}
+
+	#[test]
+	fn null_arguments_are_rejected() {
+		unsafe {
+			let state = gotham_state_new();
+			let key = c"token".as_ptr();
+			assert!(!gotham_state_put_bytes(
+				core::ptr::null_mut(),
+				key,
+				[1u8].as_ptr(),
+				1
+			));
+			assert!(!gotham_state_put_bytes(
+				state,
+				core::ptr::null(),
+				[1u8].as_ptr(),
+				1
+			));
+			assert!(!gotham_state_put_bytes(state, key, core::ptr::null(), 1));
+			assert!(gotham_state_put_bytes(state, key, core::ptr::null(), 0));
+			let bytes = gotham_state_get_bytes(state, key, core::ptr::null_mut());
+			assert!(!bytes.is_null());
+			assert!(
+				gotham_state_get_bytes(core::ptr::null(), key, core::ptr::null_mut()).is_null()
+			);
+			gotham_state_free(state);
+			gotham_state_free(core::ptr::null_mut());
+		}
+	}
+}
@@ -0,0 +1,155 @@
+use crate::slot::Slot;
+use crate::State;
+use crate::StateError;
+use core::any::TypeId;
+use core::ops::Deref;
+
+/// A `State` that can no longer be changed, created with [`State::freeze`].
+///
+/// It dereferences to the frozen `State`, so only the accessors taking
+/// `&self`, such as [`has`](State::has) and [`borrow`](State::borrow), are
+/// available, and attempts to put, mutate or take values are rejected at
+/// compile time.
+#[derive(Debug)]
+pub struct FrozenState {
+	state: State,
+}
+
+impl State {
+	/// Freezes the whole `State`, so that for the rest of its lifetime it can
+	/// only be read from.
+	pub fn freeze(self) -> FrozenState {
+		trace!(" freezing state");
+		FrozenState { state: self }
+	}
+
+	/// Puts a value into the `State` storage that can never be replaced,
+	/// mutated or taken afterwards, as if it was frozen with
+	/// [`freeze_type`](State::freeze_type) right after being put.
+	///
+	/// # Panics
+	///
+	/// If the existing value of type `T` has been frozen.
+	pub fn put_immutable<T: 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting immutable record to state for type_id `{:?}`",
+			type_id
+		);
+		let mut slot = Slot::new(t);
+		slot.frozen = true;
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Freezes the value of type `T`, so that it can no longer be replaced,
+	/// mutated or removed, while the rest of the `State` can still change.
+	/// Returns whether a value of type `T` was present to be frozen.
+	///
+	/// Afterwards [`put`](State::put), [`borrow_mut`](State::borrow_mut) and
+	/// [`take`](State::take) panic for type `T`, and their `try_` and
+	/// `_checked` counterparts fail. The value is still dropped by
+	/// [`clear`](State::clear).
+	pub fn freeze_type<T: 'static>(&mut self) -> bool {
+		let type_id = TypeId::of::<T>();
+		trace!(" freezing state data for type_id `{:?}`", type_id);
+		match self.data.get_mut(&type_id) {
+			Some(slot) => {
+				slot.frozen = true;
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Determines if the value of type `T` has been frozen with
+	/// [`freeze_type`](State::freeze_type).
+	pub fn is_type_frozen<T: 'static>(&self) -> bool {
+		self.is_frozen(TypeId::of::<T>())
+	}
+
+	pub(crate) fn is_frozen(&self, type_id: TypeId) -> bool {
+		self.data.get(&type_id).is_some_and(|slot| slot.frozen)
+	}
+
+	/// Panics if the value with the given `TypeId` has been frozen.
+	pub(crate) fn assert_not_frozen(&self, type_id: TypeId) {
+		if let Some(slot) = self.data.get(&type_id).filter(|slot| slot.frozen) {
+			panic!("{}", StateError::Frozen { name: slot.name });
+		}
+	}
+}
+
+impl Deref for FrozenState {
+	type Target = State;
+
+	fn deref(&self) -> &State {
+		&self.state
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::StateRead;
+
+	#[test]
+	fn frozen_state_is_readable() {
+		let mut state = State::default();
+		state.put(1u8);
+		let state = state.freeze();
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert_eq!(StateRead::try_borrow::<u16>(&state), None);
+	}
+
+	#[test]
+	fn frozen_types_reject_changes() {
+		let mut state = State::default();
+		state.put(1u8);
+		state.put(1u16);
+		assert!(state.freeze_type::<u8>());
+		assert!(!state.freeze_type::<u32>());
+		assert!(state.is_type_frozen::<u8>() && !state.is_type_frozen::<u16>());
+		assert!(state.try_borrow_mut::<u8>().is_none());
+		assert!(state.try_take::<u8>().is_none());
+		assert!(matches!(
+			state.take_checked::<u8>(),
+			Err(StateError::Frozen { name: "u8" })
+		));
+		*state.borrow_mut::<u16>() += 1;
+		assert_eq!(state.borrow::<u8>(), &1);
+		state.clear();
+		assert!(!state.has::<u8>());
+	}
+
+	#[test]
+	fn immutable_values_are_frozen_from_the_start() {
+		let mut state = State::default();
+		state.put_immutable(1u8);
+		assert!(state.is_type_frozen::<u8>());
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert!(state.try_borrow_mut::<u8>().is_none());
+		assert!(state.try_take::<u8>().is_none());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn putting_an_immutable_value_twice_panics() {
+		let mut state = State::default();
+		state.put_immutable(1u8);
+		let result =
+			std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| state.put_immutable(2u8)));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<u8>(), &1);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn putting_over_a_frozen_type_panics() {
+		let mut state = State::default();
+		state.put(1u8);
+		state.freeze_type::<u8>();
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| state.put(2u8)));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<u8>(), &1);
+	}
+}
@@ -0,0 +1,64 @@
+use crate::State;
+
+/// A trait for accessing data that is stored in `State`.
+///
+/// This provides the `T::try_borrow_from(&state)` style of API, as an
+/// alternative to `state.try_borrow::<T>()`, for building extractors on top
+/// of this crate. It is implemented for every `'static` type.
+pub trait FromState: Sized + 'static {
+	/// Tries to borrow a value from the `State` storage.
+	fn try_borrow_from(state: &State) -> Option<&Self>;
+
+	/// Borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `Self` is not present in `State`.
+	fn borrow_from(state: &State) -> &Self;
+
+	/// Tries to mutably borrow a value from the `State` storage.
+	fn try_borrow_mut_from(state: &mut State) -> Option<&mut Self>;
+
+	/// Mutably borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `Self` is not present in `State`.
+	fn borrow_mut_from(state: &mut State) -> &mut Self;
+
+	/// Tries to move a value out of the `State` storage and return ownership.
+	fn try_take_from(state: &mut State) -> Option<Self>;
+
+	/// Moves a value out of the `State` storage and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `Self` is not present in `State`.
+	fn take_from(state: &mut State) -> Self;
+}
+
+impl<T: 'static> FromState for T {
+	fn try_borrow_from(state: &State) -> Option<&Self> {
+		state.try_borrow()
+	}
+
+	fn borrow_from(state: &State) -> &Self {
+		state.borrow()
+	}
+
+	fn try_borrow_mut_from(state: &mut State) -> Option<&mut Self> {
+		state.try_borrow_mut()
+	}
+
+	fn borrow_mut_from(state: &mut State) -> &mut Self {
+		state.borrow_mut()
+	}
+
+	fn try_take_from(state: &mut State) -> Option<Self> {
+		state.try_take()
+	}
+
+	fn take_from(state: &mut State) -> Self {
+		state.take()
+	}
+}
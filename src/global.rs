@@ -0,0 +1,37 @@
+use crate::SyncState;
+use std::sync::OnceLock;
+
+static GLOBAL: OnceLock<SyncState> = OnceLock::new();
+
+/// Initializes the process-wide [`SyncState`] returned by [`global`], calling
+/// `f` to populate it. The global state can only be initialized once;
+/// afterwards `f` is not called and `false` is returned.
+///
+/// If several threads race to initialize the global state, exactly one of
+/// them calls its `f`, and the others wait for it to finish.
+pub fn init_global(f: impl FnOnce(&mut SyncState)) -> bool {
+	let mut initialized = false;
+	GLOBAL.get_or_init(|| {
+		trace!(" initializing global state");
+		let mut state = SyncState::default();
+		f(&mut state);
+		initialized = true;
+		state
+	});
+	initialized
+}
+
+/// Returns the process-wide [`SyncState`], or `None` if [`init_global`] has
+/// not been called yet.
+pub fn try_global() -> Option<&'static SyncState> {
+	GLOBAL.get()
+}
+
+/// Returns the process-wide [`SyncState`].
+///
+/// # Panics
+///
+/// If [`init_global`] has not been called yet.
+pub fn global() -> &'static SyncState {
+	try_global().expect("global State container has not been initialized with init_global")
+}
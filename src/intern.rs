@@ -0,0 +1,75 @@
+use crate::lock::RwLock;
+use crate::State;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use std::sync::OnceLock;
+
+/// A type name interned in a table shared by every `State` in the process,
+/// so diagnostics can refer to types with a single word instead of a
+/// string.
+///
+/// Ids are assigned in the order names are first interned, and stay valid
+/// for the lifetime of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NameId(u32);
+
+#[derive(Default)]
+struct Table {
+	names: Vec<&'static str>,
+	ids: BTreeMap<&'static str, NameId>,
+}
+
+static TABLE: OnceLock<RwLock<Table>> = OnceLock::new();
+
+impl NameId {
+	/// Interns `name`, returning the id it was given when first interned.
+	///
+	/// # Panics
+	///
+	/// If more than `u32::MAX` distinct names are interned.
+	pub fn intern(name: &'static str) -> NameId {
+		let table = TABLE.get_or_init(RwLock::default);
+		if let Some(id) = table.read().ids.get(name) {
+			return *id;
+		}
+		let mut table = table.write();
+		if let Some(id) = table.ids.get(name) {
+			return *id;
+		}
+		let id = NameId(u32::try_from(table.names.len()).expect("too many interned names"));
+		table.names.push(name);
+		table.ids.insert(name, id);
+		id
+	}
+
+	/// Interns the name of type `T`.
+	pub fn of<T: ?Sized>() -> NameId {
+		NameId::intern(core::any::type_name::<T>())
+	}
+
+	/// Returns the interned name.
+	pub fn as_str(self) -> &'static str {
+		let table = TABLE.get().expect("id was interned").read();
+		table.names[self.0 as usize]
+	}
+}
+
+impl fmt::Display for NameId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl State {
+	/// Returns an iterator over the interned names of the types of the
+	/// values in the `State` storage, as recorded when each value was put.
+	///
+	/// Each slot only keeps the `&'static str` returned by
+	/// `core::any::type_name`, which points into the binary, so the names
+	/// are never copied per entry; interning maps them to compact ids that
+	/// can be stored or compared across many states.
+	pub fn name_ids(&self) -> impl Iterator<Item = NameId> + '_ {
+		self.type_names().map(NameId::intern)
+	}
+}
@@ -0,0 +1,83 @@
+use crate::State;
+use core::any::type_name;
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+
+/// A handle to the slot of the value of type `T` in a `State`, obtained with
+/// [`State::key`].
+///
+/// Accessing a value through its key with [`State::get`] goes straight to
+/// the slot instead of looking its type up. The key stays valid while the
+/// value is replaced with [`put`](State::put), but once the value is removed,
+/// or the `State` is cleared, the key is stale and never resolves again, even
+/// if a value of type `T` is put anew.
+pub struct Key<T> {
+	index: usize,
+	generation: u32,
+	marker: PhantomData<fn() -> T>,
+}
+
+impl State {
+	/// Returns a [`Key`] addressing the slot of the value of type `T`, or
+	/// `None` if no value of type `T` is present.
+	///
+	/// A key must only be used with the `State` it was obtained from.
+	pub fn key<T: 'static>(&self) -> Option<Key<T>> {
+		let (index, generation) = self.data.handle_of(&TypeId::of::<T>())?;
+		Some(Key {
+			index,
+			generation,
+			marker: PhantomData,
+		})
+	}
+
+	/// Borrows the value addressed by `key`. Returns `None` if the key is
+	/// stale.
+	pub fn get<T: 'static>(&self, key: Key<T>) -> Option<&T> {
+		let (type_id, slot) = self.data.get_at(key.index, key.generation)?;
+		if *type_id != TypeId::of::<T>() || slot.is_expired() {
+			return None;
+		}
+		slot.get(self).and_then(|v| v.downcast_ref())
+	}
+
+	/// Mutably borrows the value addressed by `key`. Returns `None` if the key
+	/// is stale or if the value has been frozen.
+	pub fn get_mut<T: 'static>(&mut self, key: Key<T>) -> Option<&mut T> {
+		let (type_id, slot) = self.data.get_at(key.index, key.generation)?;
+		if *type_id != TypeId::of::<T>() || slot.is_expired() || slot.frozen {
+			return None;
+		}
+		// Construct the value first if it is still waiting on its factory.
+		slot.get(self)?;
+		let (_, slot) = self.data.get_at_mut(key.index, key.generation)?;
+		slot.get_mut().and_then(|v| v.downcast_mut())
+	}
+}
+
+impl<T> Clone for Key<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for Key<T> {}
+
+impl<T> PartialEq for Key<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.index == other.index && self.generation == other.generation
+	}
+}
+
+impl<T> Eq for Key<T> {}
+
+impl<T> fmt::Debug for Key<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Key")
+			.field("type", &type_name::<T>())
+			.field("index", &self.index)
+			.field("generation", &self.generation)
+			.finish()
+	}
+}
@@ -0,0 +1,221 @@
+#[cfg(feature = "std")]
+use crate::lock::Mutex;
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use alloc::format;
+use core::any::type_name;
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+/// Marker whose `TypeId` addresses the slot for values of type `T` stored
+/// under the key type `K`.
+struct Keyed<K, T>(PhantomData<fn() -> (K, T)>);
+
+pub(crate) fn keyed_type_id<K: 'static, T: 'static>() -> TypeId {
+	TypeId::of::<Keyed<K, T>>()
+}
+
+/// Returns the name recorded for values of type `T` under the key type `K`,
+/// such as `u32 under key app::Primary`.
+#[cfg(feature = "std")]
+fn keyed_name<K: 'static, T: 'static>() -> &'static str {
+	static NAMES: Mutex<BTreeMap<TypeId, &'static str>> = Mutex::new(BTreeMap::new());
+	// One name is leaked for each pair of types, so that it can be recorded
+	// like the name of any other type.
+	NAMES
+		.lock()
+		.entry(keyed_type_id::<K, T>())
+		.or_insert_with(|| format!("{} under key {}", type_name::<T>(), type_name::<K>()).leak())
+}
+
+/// Creates the slot for a value of type `T` stored under the key type `K`.
+#[cfg_attr(not(feature = "std"), allow(clippy::extra_unused_type_parameters))]
+pub(crate) fn keyed_slot<K: 'static, T: 'static>(t: T) -> Slot {
+	#[cfg(feature = "std")]
+	let name = keyed_name::<K, T>();
+	// Without `std` there is no lock to intern names with, so only the name
+	// of the value type is recorded.
+	#[cfg(not(feature = "std"))]
+	let name = type_name::<T>();
+	Slot::named(Box::new(t), name)
+}
+
+impl State {
+	/// Puts a value into the `State` storage under the key type `K`. One value
+	/// of each type is retained per key, so several values of the same type
+	/// can coexist under distinct keys. Successive calls to `put_keyed` with
+	/// the same key will overwrite the existing value of the same type.
+	pub fn put_keyed<K: 'static, T: 'static>(&mut self, t: T) {
+		let type_id = keyed_type_id::<K, T>();
+		trace!(
+			" inserting keyed record to state for type_id `{:?}`",
+			type_id
+		);
+		self.insert_slot(type_id, keyed_slot::<K, T>(t));
+	}
+
+	/// Determines if a value of type `T` exists under the key type `K`.
+	pub fn has_keyed<K: 'static, T: 'static>(&self) -> bool {
+		self.data
+			.get(&keyed_type_id::<K, T>())
+			.is_some_and(|s| !s.is_expired())
+	}
+
+	/// Tries to borrow a value stored under the key type `K`.
+	pub fn try_borrow_keyed<K: 'static, T: 'static>(&self) -> Option<&T> {
+		let type_id = keyed_type_id::<K, T>();
+		trace!(" borrowing keyed state data for type_id `{:?}`", type_id);
+		self.data
+			.get(&type_id)
+			.filter(|s| !s.is_expired())
+			.and_then(|s| s.get(self))
+			.and_then(|v| v.downcast_ref())
+	}
+
+	/// Borrows a value stored under the key type `K`.
+	pub fn borrow_keyed<K: 'static, T: 'static>(&self) -> &T {
+		self.try_borrow_keyed::<K, T>()
+			.unwrap_or_else(|| missing_keyed::<K, T>())
+	}
+
+	/// Tries to mutably borrow a value stored under the key type `K`.
+	/// Returns `None` if the value has been frozen.
+	pub fn try_borrow_keyed_mut<K: 'static, T: 'static>(&mut self) -> Option<&mut T> {
+		let type_id = keyed_type_id::<K, T>();
+		trace!(
+			" mutably borrowing keyed state data for type_id `{:?}`",
+			type_id
+		);
+		self.expire(type_id);
+		if self.is_frozen(type_id) || !self.force(type_id) {
+			return None;
+		}
+		self.data
+			.get_mut(&type_id)
+			.and_then(Slot::get_mut)
+			.and_then(|v| v.downcast_mut())
+	}
+
+	/// Mutably borrows a value stored under the key type `K`.
+	pub fn borrow_keyed_mut<K: 'static, T: 'static>(&mut self) -> &mut T {
+		self.try_borrow_keyed_mut::<K, T>()
+			.unwrap_or_else(|| missing_keyed::<K, T>())
+	}
+
+	/// Tries to move a value stored under the key type `K` out of the `State`
+	/// storage and return ownership. Returns `None` if the value has been
+	/// frozen.
+	pub fn try_take_keyed<K: 'static, T: 'static>(&mut self) -> Option<T> {
+		let type_id = keyed_type_id::<K, T>();
+		trace!(
+			" taking ownership from keyed state data for type_id `{:?}`",
+			type_id
+		);
+		self.expire(type_id);
+		if self.is_frozen(type_id) || !self.force(type_id) {
+			return None;
+		}
+		self.remove_slot(type_id)
+			.and_then(Slot::into_value)
+			.and_then(|b| b.downcast().ok())
+			.map(|b| *b)
+	}
+
+	/// Moves a value stored under the key type `K` out of the `State` storage
+	/// and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present under the key `K`.
+	pub fn take_keyed<K: 'static, T: 'static>(&mut self) -> T {
+		self.try_take_keyed::<K, T>()
+			.unwrap_or_else(|| missing_keyed::<K, T>())
+	}
+}
+
+fn missing_keyed<K: 'static, T: 'static>() -> ! {
+	panic!(
+		"required type {} is not present under key {} in State container",
+		type_name::<T>(),
+		type_name::<K>()
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(feature = "std")]
+	use alloc::vec::Vec;
+
+	struct Primary;
+	struct Replica;
+
+	#[test]
+	fn values_coexist_under_distinct_keys() {
+		let mut state = State::default();
+		state.put_keyed::<Primary, _>(1u32);
+		state.put_keyed::<Replica, _>(2u32);
+		assert!(!state.has::<u32>());
+		assert_eq!(state.borrow_keyed::<Primary, u32>(), &1);
+		*state.borrow_keyed_mut::<Replica, u32>() += 1;
+		assert_eq!(state.take_keyed::<Replica, u32>(), 3);
+		assert!(!state.has_keyed::<Replica, u32>());
+		assert!(state.has_keyed::<Primary, u32>());
+	}
+
+	#[test]
+	fn frozen_keyed_values_are_not_handed_out_mutably() {
+		let mut state = State::default();
+		state.put_keyed::<Primary, _>(1u32);
+		let type_id = keyed_type_id::<Primary, u32>();
+		state.data.get_mut(&type_id).unwrap().frozen = true;
+		assert_eq!(state.try_borrow_keyed_mut::<Primary, u32>(), None);
+		assert_eq!(state.try_take_keyed::<Primary, u32>(), None);
+		assert_eq!(state.borrow_keyed::<Primary, u32>(), &1);
+	}
+
+	#[test]
+	fn lazy_keyed_values_are_constructed_for_mutable_access() {
+		use crate::slot::Init;
+		use alloc::rc::Rc;
+
+		let mut state = State::default();
+		let type_id = keyed_type_id::<Primary, u32>();
+		let init = Init::Factory(Rc::new(|_| Box::new(1u32)));
+		state.insert_slot(type_id, Slot::lazy::<u32>(init));
+		*state.borrow_keyed_mut::<Primary, u32>() += 1;
+		assert_eq!(state.borrow_keyed::<Primary, u32>(), &2);
+		state.insert_slot(
+			type_id,
+			Slot::lazy::<u32>(Init::Factory(Rc::new(|_| Box::new(5u32)))),
+		);
+		assert_eq!(state.take_keyed::<Primary, u32>(), 5);
+		assert!(!state.has_keyed::<Primary, u32>());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn names_show_the_value_type_and_the_key() {
+		let mut state = State::default();
+		state.put_keyed::<Primary, _>(1u32);
+		let names: Vec<_> = state.type_names().collect();
+		assert_eq!(names, ["u32 under key gotham_state::keyed::tests::Primary"]);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn keyed_values_expire() {
+		let mut state = State::default();
+		state.put_keyed_with_ttl::<Primary, _>(1u32, std::time::Duration::ZERO);
+		assert!(!state.has_keyed::<Primary, u32>());
+		assert_eq!(state.try_borrow_keyed::<Primary, u32>(), None);
+		assert_eq!(state.try_borrow_keyed_mut::<Primary, u32>(), None);
+		assert!(state.is_empty());
+		state.put_keyed_with_ttl::<Primary, _>(2u32, std::time::Duration::ZERO);
+		assert_eq!(state.try_take_keyed::<Primary, u32>(), None);
+	}
+}
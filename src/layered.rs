@@ -0,0 +1,246 @@
+use crate::missing;
+use crate::State;
+use alloc::vec::Vec;
+
+/// A request-scoped `State` layered on top of one or more parent states.
+///
+/// Reads check the local layer first and then walk up the parent chain,
+/// while writes always go to the local layer, so the parents are never
+/// modified.
+#[derive(Debug)]
+pub struct LayeredState<'p> {
+	local: State,
+	parent: Parent<'p>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Parent<'p> {
+	State(&'p State),
+	Layered(&'p LayeredState<'p>),
+}
+
+impl State {
+	/// Creates an empty `LayeredState` whose reads fall back to `parent`.
+	pub fn with_parent(parent: &State) -> LayeredState<'_> {
+		LayeredState::new(parent)
+	}
+
+	/// Runs `f` with a temporary layer on top of this `State`. Reads inside
+	/// the closure see the values of this `State`, while anything put into
+	/// the layer is discarded when the closure returns.
+	pub fn scope<R>(&self, f: impl FnOnce(&mut LayeredState<'_>) -> R) -> R {
+		f(&mut LayeredState::new(self))
+	}
+
+	/// Runs `f` with a temporary layer on top of this `State`, like
+	/// [`scope`](State::scope), but moves everything put into the layer into
+	/// this `State` when the closure returns, overwriting existing values of
+	/// the same types.
+	pub fn scope_merge<R>(&mut self, f: impl FnOnce(&mut LayeredState<'_>) -> R) -> R {
+		let mut layer = LayeredState::new(self);
+		let result = f(&mut layer);
+		for (type_id, slot) in layer.into_local().data {
+			self.insert_slot(type_id, slot);
+		}
+		result
+	}
+}
+
+impl<'p> LayeredState<'p> {
+	/// Creates an empty `LayeredState` whose reads fall back to `parent`.
+	pub fn new(parent: &'p State) -> Self {
+		LayeredState {
+			local: State::default(),
+			parent: Parent::State(parent),
+		}
+	}
+
+	/// Creates an empty `LayeredState` whose reads fall back to this one, and
+	/// from there to the rest of the parent chain.
+	pub fn child(&self) -> LayeredState<'_> {
+		LayeredState {
+			local: State::default(),
+			parent: Parent::Layered(self),
+		}
+	}
+
+	/// Puts a value into the local layer. Values of the same type in parent
+	/// layers are shadowed, not overwritten.
+	pub fn put<T: 'static>(&mut self, t: T) {
+		self.local.put(t);
+	}
+
+	/// Determines if the current value exists in the local layer or any of
+	/// its parents.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.local.has::<T>() || self.parent.has::<T>()
+	}
+
+	/// Tries to borrow a value from the local layer, falling back to the
+	/// parent chain.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		self.local.try_borrow().or_else(|| self.parent.try_borrow())
+	}
+
+	/// Borrows a value from the local layer, falling back to the parent
+	/// chain.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in any layer.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: 'static>(&self) -> &T {
+		match self.try_borrow() {
+			Some(value) => value,
+			None => missing::<T>(Some(&self.local), self.type_names()),
+		}
+	}
+
+	/// Tries to mutably borrow a value from the local layer. Values in parent
+	/// layers cannot be borrowed mutably.
+	pub fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.local.try_borrow_mut()
+	}
+
+	/// Mutably borrows a value from the local layer.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the local layer, or if it
+	/// has been frozen.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		self.local.borrow_mut()
+	}
+
+	/// Tries to move a value out of the local layer and return ownership.
+	pub fn try_take<T: 'static>(&mut self) -> Option<T> {
+		self.local.try_take()
+	}
+
+	/// Moves a value out of the local layer and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in the local layer.
+	#[track_caller]
+	pub fn take<T: 'static>(&mut self) -> T {
+		self.local.take()
+	}
+
+	/// Returns the local layer.
+	pub fn local(&self) -> &State {
+		&self.local
+	}
+
+	/// Returns the local layer mutably.
+	pub fn local_mut(&mut self) -> &mut State {
+		&mut self.local
+	}
+
+	/// Detaches the local layer from its parents.
+	pub fn into_local(self) -> State {
+		self.local
+	}
+
+	/// Returns the names of the types in every layer, starting with the
+	/// local one.
+	fn type_names(&self) -> Vec<&'static str> {
+		let mut names: Vec<_> = self.local.type_names().collect();
+		match self.parent {
+			Parent::State(state) => names.extend(state.type_names()),
+			Parent::Layered(layered) => names.extend(layered.type_names()),
+		}
+		names
+	}
+}
+
+impl<'p> Parent<'p> {
+	fn has<T: 'static>(self) -> bool {
+		match self {
+			Parent::State(state) => state.has::<T>(),
+			Parent::Layered(layered) => layered.has::<T>(),
+		}
+	}
+
+	fn try_borrow<T: 'static>(self) -> Option<&'p T> {
+		match self {
+			Parent::State(state) => state.try_borrow(),
+			Parent::Layered(layered) => layered.try_borrow(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(feature = "std")]
+	use alloc::string::String;
+
+	#[test]
+	fn reads_fall_back_to_the_parent_chain() {
+		let mut root = State::default();
+		root.put(1u8);
+		root.put(1u16);
+		let mut layer = State::with_parent(&root);
+		layer.put(2u16);
+		let mut child = layer.child();
+		child.put(3u32);
+		assert_eq!(child.borrow::<u8>(), &1);
+		assert_eq!(child.borrow::<u16>(), &2);
+		assert!(child.has::<u32>() && !layer.has::<u32>());
+		assert!(child.try_borrow_mut::<u8>().is_none());
+		*child.borrow_mut::<u32>() += 1;
+		assert_eq!(child.take::<u32>(), 4);
+		assert_eq!(root.borrow::<u16>(), &1);
+	}
+
+	#[test]
+	fn scopes_discard_or_merge_their_layer() {
+		let mut state = State::default();
+		state.put(1u8);
+		let read = state.scope(|layer| {
+			layer.put(2u8);
+			layer.put(1u16);
+			*layer.borrow::<u8>()
+		});
+		assert_eq!(read, 2);
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert!(!state.has::<u16>());
+		state.scope_merge(|layer| {
+			layer.put(2u8);
+			layer.put(1u16);
+		});
+		assert_eq!(state.borrow::<u8>(), &2);
+		assert_eq!(state.borrow::<u16>(), &1);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn merging_over_a_frozen_value_panics() {
+		let mut state = State::default();
+		state.put_immutable(1u8);
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			state.scope_merge(|layer| layer.put(2u8));
+		}));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<u8>(), &1);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn missing_values_panic_with_every_layer() {
+		let mut root = State::default();
+		root.put(1u8);
+		let mut layer = LayeredState::new(&root);
+		layer.put(1u16);
+		let error = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			layer.borrow::<u32>();
+		}))
+		.unwrap_err();
+		let message = error.downcast_ref::<String>().unwrap();
+		assert!(message.contains("stored types: [u16, u8]"), "{message}");
+	}
+}
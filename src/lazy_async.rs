@@ -0,0 +1,199 @@
+use crate::fail_at;
+use crate::lock::Mutex;
+use crate::slot::Init;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateError;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::OnceCell;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::future::Future;
+use core::panic::Location;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+
+type InitFuture = Pin<Box<dyn Future<Output = Box<dyn Any>>>>;
+
+/// The pending asynchronous construction of a slot's value.
+///
+/// Whichever task polls the value first drives the future, and every task
+/// waiting on the value is woken once it makes progress.
+pub(crate) struct AsyncInit {
+	future: RefCell<Option<InitFuture>>,
+	waiters: Arc<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters(Mutex<Vec<Waker>>);
+
+impl State {
+	/// Puts a value into the `State` storage that is constructed by awaiting
+	/// the future returned by `f` the first time it is requested with
+	/// [`get_async`](State::get_async). The future is awaited exactly once,
+	/// even if several tasks request the value concurrently.
+	///
+	/// Until the future has completed, the synchronous accessors such as
+	/// [`try_borrow`](State::try_borrow) treat the value as absent.
+	pub fn put_lazy_async<T, F, Fut>(&mut self, f: F)
+	where
+		T: 'static,
+		F: FnOnce() -> Fut + 'static,
+		Fut: Future<Output = T> + 'static,
+	{
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting async lazy record to state for type_id `{:?}`",
+			type_id
+		);
+		let future: InitFuture = Box::pin(async move {
+			let value: Box<dyn Any> = Box::new(f().await);
+			value
+		});
+		let init = AsyncInit {
+			future: RefCell::new(Some(future)),
+			waiters: Arc::default(),
+		};
+		self.insert_slot(type_id, Slot::lazy::<T>(Init::Async(init)));
+	}
+
+	/// Tries to borrow a value from the `State` storage, waiting for its
+	/// asynchronous construction to finish if it was put with
+	/// [`put_lazy_async`](State::put_lazy_async). Resolves to `None` if no
+	/// value of type `T` is present.
+	pub async fn try_get_async<T: 'static>(&self) -> Option<&T> {
+		let type_id = TypeId::of::<T>();
+		trace!(" awaiting state data for type_id `{:?}`", type_id);
+		let slot = self.data.get(&type_id)?;
+		poll_fn(|cx| slot.poll_get(self, cx))
+			.await
+			.and_then(|v| v.downcast_ref())
+	}
+
+	/// Borrows a value from the `State` storage, waiting for its
+	/// asynchronous construction to finish if it was put with
+	/// [`put_lazy_async`](State::put_lazy_async).
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`.
+	#[track_caller]
+	pub fn get_async<T: 'static>(&self) -> impl Future<Output = &T> + '_ {
+		let caller = Location::caller();
+		async move {
+			match self.try_get_async().await {
+				Some(value) => value,
+				None => fail_at(
+					StateError::missing::<T>(),
+					Some(self),
+					self.type_names(),
+					caller,
+				),
+			}
+		}
+	}
+}
+
+impl AsyncInit {
+	/// Drives the future, storing its output in `value` once it completes.
+	pub(crate) fn poll(&self, value: &OnceCell<Box<dyn Any>>, cx: &mut Context<'_>) -> Poll<()> {
+		self.waiters.register(cx.waker());
+		// The future is already being polled further up the stack, which will
+		// wake this task once it makes progress.
+		let Ok(mut slot) = self.future.try_borrow_mut() else {
+			return Poll::Pending;
+		};
+		let Some(future) = slot.as_mut() else {
+			return Poll::Ready(());
+		};
+		let waker = Waker::from(self.waiters.clone());
+		match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+			Poll::Ready(output) => {
+				*slot = None;
+				let _ = value.set(output);
+				self.waiters.wake_all();
+				Poll::Ready(())
+			},
+			Poll::Pending => Poll::Pending,
+		}
+	}
+}
+
+impl Waiters {
+	fn register(&self, waker: &Waker) {
+		let mut waiters = self.0.lock();
+		if !waiters.iter().any(|w| w.will_wake(waker)) {
+			waiters.push(waker.clone());
+		}
+	}
+
+	fn wake_all(&self) {
+		let waiters = core::mem::take(&mut *self.0.lock());
+		waiters.into_iter().for_each(Waker::wake);
+	}
+}
+
+impl Wake for Waiters {
+	fn wake(self: Arc<Self>) {
+		self.wake_all();
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		self.wake_all();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::String;
+	use core::panic::AssertUnwindSafe;
+	use std::panic::catch_unwind;
+
+	fn pending_state() -> State {
+		let mut state = State::default();
+		state.put_lazy_async(core::future::pending::<u32>);
+		state
+	}
+
+	fn panic_message(f: impl FnOnce()) -> String {
+		let error = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+		error.downcast_ref::<String>().unwrap().clone()
+	}
+
+	#[test]
+	fn pending_value_is_missing() {
+		let mut state = pending_state();
+		assert!(state.try_borrow::<u32>().is_none());
+		assert!(state.try_borrow_mut::<u32>().is_none());
+		assert!(state.try_borrow_many::<(&mut u32,)>().is_none());
+		assert!(state.borrow_mut_checked::<u32>().is_err());
+	}
+
+	#[test]
+	fn pending_value_panics_as_missing() {
+		let missing = "required type u32 is not present in State container";
+		let mut state = pending_state();
+		let message = panic_message(|| {
+			let _ = state.borrow_mut::<u32>();
+		});
+		assert!(message.contains(missing), "{message}");
+		let mut state = pending_state();
+		let message = panic_message(|| {
+			let _ = state.entry::<u32>();
+		});
+		assert!(message.contains(missing), "{message}");
+		let mut state = pending_state();
+		let message = panic_message(|| {
+			let _ = state.borrow_many::<(&u32,)>();
+		});
+		assert!(message.contains(missing), "{message}");
+	}
+}
@@ -6,83 +6,706 @@
 // https://github.com/gotham-rs/gotham/blob/bcbbf8923789e341b7a0e62c59909428ca4e22e2/gotham/src/state/mod.rs
 // Copyright 2017 Gotham Project Developers. MIT license.
 
-use log::trace;
-use std::any::type_name;
-use std::any::Any;
-use std::any::TypeId;
-use std::collections::BTreeMap;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[derive(Default, Debug)]
+extern crate alloc;
+
+/// Forwards to `log::trace!` when the `log` feature is enabled, and
+/// otherwise only type-checks the arguments.
+macro_rules! trace {
+	($($arg:tt)+) => {{
+		#[cfg(feature = "log")]
+		log::trace!($($arg)+);
+		#[cfg(not(feature = "log"))]
+		let _ = format_args!($($arg)+);
+	}};
+}
+
+/// Updates the access counters when the `metrics` feature is enabled.
+macro_rules! metrics {
+	($($update:tt)+) => {
+		#[cfg(feature = "metrics")]
+		$($update)+;
+	};
+}
+
+mod access;
+pub mod anymap;
+mod arc;
+#[cfg(feature = "async")]
+mod async_state;
+mod builder;
+mod collect;
+#[cfg(feature = "std")]
+mod concurrent;
+mod cow;
+#[cfg(feature = "debug-endpoint")]
+mod debug;
+mod diff;
+mod entry;
+mod error;
+mod event;
+mod evict;
+mod ext;
+mod extract;
+mod factory;
+mod fallback;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod freeze;
+mod from_state;
+#[cfg(feature = "global")]
+mod global;
+#[cfg(feature = "std")]
+mod intern;
+mod key;
+mod keyed;
+mod layered;
+#[cfg(feature = "async")]
+mod lazy_async;
+#[cfg(feature = "std")]
+mod lock;
+mod macros;
+mod many;
+mod map;
+mod merge;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod missing_handler;
+mod multi;
+mod named;
+mod option;
+mod pool;
+mod priority;
+mod prototype;
+mod ptr;
+#[cfg(feature = "unstable")]
+mod raw;
+mod replace;
+#[cfg(feature = "request-id")]
+mod request_id;
+#[cfg(feature = "resources")]
+mod resource;
+mod restrict;
+mod scoped;
+#[cfg(feature = "send-audit")]
+mod send_audit;
+mod shared;
+mod size;
+mod slab;
+mod slot;
+#[cfg(feature = "small")]
+mod small_map;
+mod snapshot;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(feature = "std")]
+mod swap;
+mod sync;
+#[cfg(feature = "task-local")]
+mod task_local;
+mod teardown;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "global")]
+mod thread_local;
+#[cfg(feature = "track-borrows")]
+mod tracked;
+mod trait_object;
+mod transaction;
+mod transfer;
+#[cfg(feature = "std")]
+mod ttl;
+mod typed;
+mod typed_builder;
+mod view;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use access::StateRead;
+pub use access::StateWrite;
+#[cfg(feature = "async")]
+pub use async_state::AsyncState;
+#[cfg(feature = "async")]
+pub use async_state::OwnedMut;
+#[cfg(feature = "async")]
+pub use async_state::OwnedRef;
+#[cfg(feature = "async")]
+pub use async_state::ReadGuard;
+#[cfg(feature = "async")]
+pub use async_state::WriteGuard;
+pub use builder::StateBuilder;
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentState;
+#[cfg(feature = "std")]
+pub use concurrent::PoisonPolicy;
+#[cfg(feature = "std")]
+pub use concurrent::VersionConflict;
+pub use cow::CowState;
+pub use diff::StateDiff;
+pub use entry::Entry;
+pub use entry::OccupiedEntry;
+pub use entry::VacantEntry;
+pub use error::StateError;
+pub use event::StateEvent;
+pub use evict::EvictionPolicy;
+pub use evict::Limit;
+pub use ext::StateExt;
+pub use extract::Extract;
+pub use factory::Dependencies;
+pub use freeze::FrozenState;
+pub use from_state::FromState;
+#[cfg(feature = "global")]
+pub use global::global;
+#[cfg(feature = "global")]
+pub use global::init_global;
+#[cfg(feature = "global")]
+pub use global::try_global;
+#[cfg(feature = "derive")]
+pub use gotham_state_derive::Extract;
+#[cfg(feature = "std")]
+pub use intern::NameId;
+pub use key::Key;
+pub use layered::LayeredState;
+pub use many::BorrowMany;
+pub use many::Fetch;
+pub use merge::MergePolicy;
+#[cfg(feature = "metrics")]
+pub use metrics::TypeMetrics;
+#[cfg(feature = "std")]
+pub use missing_handler::set_global_missing_handler;
+pub use missing_handler::MissingHandler;
+pub use pool::PooledState;
+pub use pool::StatePool;
+pub use ptr::SharedPtr;
+#[cfg(feature = "request-id")]
+pub use request_id::RequestId;
+#[cfg(feature = "resources")]
+pub use resource::Resource;
+#[cfg(feature = "resources")]
+pub use resource::ResourceId;
+#[cfg(feature = "resources")]
+pub use resource::ResourceTable;
+pub use restrict::Restricted;
+pub use restrict::TypeSet;
+pub use scoped::Scope;
+pub use scoped::ScopedState;
+#[doc(hidden)]
+#[cfg(feature = "send-audit")]
+pub use send_audit::probe as __send_audit;
+#[cfg(feature = "send-audit")]
+pub use send_audit::SendAudit;
+pub use shared::SharedState;
+pub use size::MemSize;
+pub use size::MemoryUsage;
+#[cfg(feature = "bench-internals")]
+pub use slab::InternalCounters;
+pub use snapshot::StateSnapshot;
+#[cfg(feature = "stats")]
+pub use stats::EntryStats;
+pub use sync::SyncState;
+#[cfg(feature = "task-local")]
+pub use task_local::TaskLocalFuture;
+pub use teardown::TeardownOrder;
+#[cfg(feature = "global")]
+pub use thread_local::thread_local;
+#[cfg(feature = "global")]
+pub use thread_local::ThreadLocalState;
+#[cfg(feature = "track-borrows")]
+pub use tracked::BorrowRecord;
+#[cfg(feature = "track-borrows")]
+pub use tracked::Tracked;
+#[cfg(feature = "track-borrows")]
+pub use tracked::TrackedMut;
+pub use transaction::Transaction;
+pub use typed_builder::Contains;
+pub use typed_builder::ContainsAll;
+pub use typed_builder::HCons;
+pub use typed_builder::HNil;
+pub use typed_builder::Here;
+pub use typed_builder::PutAll;
+pub use typed_builder::There;
+pub use typed_builder::TypedStateBuilder;
+pub use view::StateView;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::any::TypeId;
+use core::fmt;
+use core::panic::Location;
+use event::Hooks;
+use slab::Slab;
+use slot::Slot;
+
+#[derive(Default)]
 pub struct State {
-	data: BTreeMap<TypeId, Box<dyn Any>>,
+	data: Slab<Slot>,
+	hooks: Hooks,
+	aliases: BTreeMap<String, TypeId>,
+	eviction: Option<evict::Eviction>,
+	fallback: fallback::Fallback,
+	missing_handler: Option<MissingHandler>,
+	#[cfg(feature = "metrics")]
+	metrics: metrics::Metrics,
+	#[cfg(feature = "track-borrows")]
+	tracker: tracked::Tracker,
 }
 
 impl State {
+	/// Creates an empty `State` with room for at least `capacity` values
+	/// without reallocating. Only storage backends that preallocate, such as
+	/// the one selected by the `hashmap` feature, make use of the capacity.
+	pub fn with_capacity(capacity: usize) -> Self {
+		State {
+			data: Slab::with_capacity(capacity),
+			..State::default()
+		}
+	}
+
+	/// Reserves room for at least `additional` more values without
+	/// reallocating. Only storage backends that preallocate make use of
+	/// this.
+	pub fn reserve(&mut self, additional: usize) {
+		self.data.reserve(additional);
+	}
+
+	/// Returns the counters of the operations performed on the storage of
+	/// the `State`, for benchmarks comparing storage backends.
+	#[cfg(feature = "bench-internals")]
+	pub fn internal_counters(&self) -> InternalCounters {
+		self.data.counters()
+	}
+
 	/// Puts a value into the `State` storage. One value of each type is retained.
 	/// Successive calls to `put` will overwrite the existing value of the same
 	/// type.
+	///
+	/// # Panics
+	///
+	/// If the existing value of type `T` has been frozen.
 	pub fn put<T: 'static>(&mut self, t: T) {
 		let type_id = TypeId::of::<T>();
 		trace!(" inserting record to state for type_id `{:?}`", type_id);
-		self.data.insert(type_id, Box::new(t));
+		self.insert_slot(type_id, Slot::new(t));
+	}
+
+	/// Tries to put a value into the `State` storage without overwriting an
+	/// existing value of the same type. If a value of type `T` is already
+	/// present, the new value is handed back in `Err`.
+	pub fn try_put<T: 'static>(&mut self, t: T) -> Result<(), T> {
+		if self.has::<T>() {
+			return Err(t);
+		}
+		self.put(t);
+		Ok(())
 	}
 
 	/// Determines if the current value exists in `State` storage.
 	pub fn has<T: 'static>(&self) -> bool {
 		let type_id = TypeId::of::<T>();
-		self.data.get(&type_id).is_some()
+		self.data.get(&type_id).is_some_and(|s| !s.is_expired())
 	}
 
 	/// Tries to borrow a value from the `State` storage.
 	pub fn try_borrow<T: 'static>(&self) -> Option<&T> {
 		let type_id = TypeId::of::<T>();
 		trace!(" borrowing state data for type_id `{:?}`", type_id);
-		self.data.get(&type_id).and_then(|b| b.downcast_ref())
+		self.touch(type_id);
+		#[cfg(feature = "stats")]
+		self.record_access(type_id);
+		let value = self
+			.data
+			.get(&type_id)
+			.filter(|s| !s.is_expired())
+			.and_then(|s| s.get(self))
+			.and_then(|v| v.downcast_ref())
+			.or_else(|| self.fallback.lookup());
+		metrics!(self
+			.metrics
+			.lookup(core::any::type_name::<T>(), value.is_some()));
+		value
 	}
 
 	/// Borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`. The message names
+	/// the caller and the types that are present.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
 	pub fn borrow<T: 'static>(&self) -> &T {
-		self.try_borrow().unwrap_or_else(|| missing::<T>())
+		match self.try_borrow() {
+			Some(value) => value,
+			None => self.fail(StateError::missing::<T>()),
+		}
 	}
 
-	/// Tries to mutably borrow a value from the `State` storage.
+	/// Borrows a value from the `State` storage, returning an error instead
+	/// of panicking if it is not present.
+	pub fn borrow_checked<T: 'static>(&self) -> Result<&T, StateError> {
+		self.try_borrow().ok_or_else(StateError::missing::<T>)
+	}
+
+	/// Tries to mutably borrow a value from the `State` storage. Returns
+	/// `None` if the value has been frozen.
 	pub fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
 		let type_id = TypeId::of::<T>();
 		trace!(" mutably borrowing state data for type_id `{:?}`", type_id);
-		self.data.get_mut(&type_id).and_then(|b| b.downcast_mut())
+		self.settle_fallback();
+		self.expire(type_id);
+		if self.fallback.is_set() && !self.data.contains_key(&type_id) {
+			self.fill_fallback::<T>();
+			if !self.data.contains_key(&type_id) {
+				return self.fallback_mut();
+			}
+		}
+		if self.is_frozen(type_id) {
+			return None;
+		}
+		self.force(type_id);
+		self.touch(type_id);
+		#[cfg(feature = "stats")]
+		self.record_access(type_id);
+		let value = self
+			.data
+			.get_mut(&type_id)
+			.and_then(Slot::get_mut)
+			.and_then(|v| v.downcast_mut());
+		metrics!(self
+			.metrics
+			.lookup(core::any::type_name::<T>(), value.is_some()));
+		value
 	}
 
 	/// Mutably borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`, or if it has been
+	/// frozen. The message names the caller and the types that are present.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
 	pub fn borrow_mut<T: 'static>(&mut self) -> &mut T {
-		self.try_borrow_mut().unwrap_or_else(|| missing::<T>())
+		let type_id = TypeId::of::<T>();
+		self.settle_fallback();
+		self.expire(type_id);
+		if !self.data.contains_key(&type_id) {
+			if !self.fill_fallback::<T>() {
+				self.fail(StateError::missing::<T>());
+			}
+			if !self.data.contains_key(&type_id) {
+				return self.fallback_mut().expect("fallback produced the value");
+			}
+		}
+		if self.is_frozen(type_id) {
+			self.fail(StateError::frozen::<T>());
+		}
+		if !self.force(type_id) {
+			self.fail(StateError::missing::<T>());
+		}
+		self.try_borrow_mut()
+			.expect("value is constructed and not frozen")
+	}
+
+	/// Mutably borrows a value from the `State` storage, returning an error
+	/// instead of panicking if it is not present.
+	pub fn borrow_mut_checked<T: 'static>(&mut self) -> Result<&mut T, StateError> {
+		if self.is_frozen(TypeId::of::<T>()) {
+			return Err(StateError::frozen::<T>());
+		}
+		self.try_borrow_mut().ok_or_else(StateError::missing::<T>)
 	}
 
 	/// Tries to move a value out of the `State` storage and return ownership.
+	/// Returns `None` if the value has been frozen.
 	pub fn try_take<T: 'static>(&mut self) -> Option<T> {
 		let type_id = TypeId::of::<T>();
 		trace!(
 			" taking ownership from state data for type_id `{:?}`",
 			type_id
 		);
-		self.data
-			.remove(&type_id)
+		self.settle_fallback();
+		self.expire(type_id);
+		if !self.data.contains_key(&type_id) {
+			if let Some(value) = self.fallback.produce::<T>() {
+				return value.downcast().ok().map(|b| *b);
+			}
+		}
+		if self.is_frozen(type_id) || !self.force(type_id) {
+			metrics!(self.metrics.take(core::any::type_name::<T>(), false));
+			return None;
+		}
+		let value = self
+			.remove_slot(type_id)
+			.and_then(Slot::into_value)
 			.and_then(|b| b.downcast().ok())
-			.map(|b| *b)
+			.map(|b| *b);
+		metrics!(self
+			.metrics
+			.take(core::any::type_name::<T>(), value.is_some()));
+		value
+	}
+
+	/// Gets the slot for type `T` for in-place manipulation, such as
+	/// inserting a value only if none is present yet.
+	///
+	/// # Panics
+	///
+	/// If the value of type `T` has been frozen, or if it is still being
+	/// constructed asynchronously.
+	#[track_caller]
+	pub fn entry<T: 'static>(&mut self) -> Entry<'_, T> {
+		let type_id = TypeId::of::<T>();
+		trace!(" accessing state entry for type_id `{:?}`", type_id);
+		self.expire(type_id);
+		self.assert_not_frozen(type_id);
+		if self.data.contains_key(&type_id) && !self.force(type_id) {
+			self.fail(StateError::missing::<T>());
+		}
+		Entry::new(self)
+	}
+
+	/// Mutably borrows a value from the `State` storage, putting
+	/// `T::default()` first if it is not present.
+	pub fn borrow_or_insert_default<T: Default + 'static>(&mut self) -> &mut T {
+		self.entry().or_default()
+	}
+
+	/// Mutably borrows a value from the `State` storage, putting the result of
+	/// `f` first if it is not present.
+	pub fn borrow_or_insert_with<T: 'static>(&mut self, f: impl FnOnce() -> T) -> &mut T {
+		self.entry().or_insert_with(f)
+	}
+
+	/// Mutably borrows a value from the `State` storage, putting the result of
+	/// `f` first if it is not present. If `f` fails, nothing is put and the
+	/// error is returned.
+	pub fn try_get_or_insert_with<T: 'static, E>(
+		&mut self,
+		f: impl FnOnce() -> Result<T, E>,
+	) -> Result<&mut T, E> {
+		self.entry().or_try_insert_with(f)
+	}
+
+	/// Moves a value out of the `State` storage and returns ownership, or
+	/// returns `T::default()` if it is not present.
+	pub fn take_or_default<T: Default + 'static>(&mut self) -> T {
+		self.try_take().unwrap_or_default()
+	}
+
+	/// Moves a value out of the `State` storage and returns ownership, or
+	/// returns the result of `f` if it is not present.
+	pub fn take_or_else<T: 'static>(&mut self, f: impl FnOnce() -> T) -> T {
+		self.try_take().unwrap_or_else(f)
 	}
 
 	/// Moves a value out of the `State` storage and returns ownership.
 	///
 	/// # Panics
 	///
-	/// If a value of type `T` is not present in `State`.
+	/// If a value of type `T` is not present in `State`, or if it has been
+	/// frozen. The message names the caller and the types that are present.
+	#[track_caller]
 	pub fn take<T: 'static>(&mut self) -> T {
-		self.try_take().unwrap_or_else(|| missing::<T>())
+		match self.take_checked() {
+			Ok(value) => value,
+			Err(e) => self.fail(e),
+		}
+	}
+
+	/// Moves a value out of the `State` storage and returns ownership,
+	/// returning an error instead of panicking if it is not present.
+	pub fn take_checked<T: 'static>(&mut self) -> Result<T, StateError> {
+		if self.is_frozen(TypeId::of::<T>()) {
+			return Err(StateError::frozen::<T>());
+		}
+		self.try_take().ok_or_else(StateError::missing::<T>)
+	}
+
+	/// Removes and drops the value of type `T` from the `State` storage.
+	/// Returns whether a value was present.
+	pub fn remove<T: 'static>(&mut self) -> bool {
+		self.remove_by_type_id(TypeId::of::<T>())
+	}
+
+	/// Removes and drops the value with the given `TypeId` from the `State`
+	/// storage, without needing to name its type. Returns whether a value
+	/// was present.
+	///
+	/// # Panics
+	///
+	/// If the value has been frozen.
+	pub fn remove_by_type_id(&mut self, type_id: TypeId) -> bool {
+		trace!(" removing state data for type_id `{:?}`", type_id);
+		self.assert_not_frozen(type_id);
+		self.remove_slot(type_id).is_some()
+	}
+
+	/// Drops every value in the `State` storage, so the container can be
+	/// reused. Frozen values are dropped as well. Values are dropped in
+	/// reverse insertion order, as when the `State` itself is dropped.
+	pub fn clear(&mut self) {
+		trace!(" clearing state data");
+		self.fallback.forget();
+		for (type_id, slot) in self.data.iter() {
+			self.hooks.emit(StateEvent::Removed {
+				type_id: *type_id,
+				name: slot.name,
+			});
+		}
+		self.data.clear();
+		self.aliases.clear();
+		#[cfg(feature = "track-borrows")]
+		self.tracker.clear();
+	}
+
+	/// Returns the number of values in the `State` storage.
+	pub fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Determines if the `State` storage holds no values.
+	pub fn is_empty(&self) -> bool {
+		self.data.is_empty()
+	}
+
+	/// Returns an iterator over the `TypeId`s of the values in the `State`
+	/// storage.
+	pub fn type_ids(&self) -> impl Iterator<Item = TypeId> + '_ {
+		self.data.keys().copied()
+	}
+
+	/// Returns an iterator over the values in the `State` storage, together
+	/// with their `TypeId`s. Values registered with a factory that have not
+	/// been constructed yet and values whose time-to-live has elapsed are
+	/// skipped.
+	pub fn iter(&self) -> impl Iterator<Item = (TypeId, &dyn Any)> + '_ {
+		self.data
+			.iter()
+			.filter(|(_, slot)| !slot.is_expired())
+			.filter_map(|(type_id, slot)| Some((*type_id, slot.try_get()?)))
+	}
+
+	/// Returns an iterator over the names of the types of the values in the
+	/// `State` storage, as recorded when each value was put.
+	pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+		self.data.values().map(|slot| slot.name)
+	}
+
+	/// Renders a human-readable listing of the types of the values in the
+	/// `State` storage, one per line.
+	pub fn dump(&self) -> String {
+		let mut out = format!("State with {} entries", self.len());
+		for name in self.type_names() {
+			out.push_str("\n  ");
+			out.push_str(name);
+		}
+		out
+	}
+}
+
+impl State {
+	/// Constructs the value with the given `TypeId` if it is still waiting on
+	/// its factory, so that it can be accessed mutably or moved out. Returns
+	/// whether a constructed value is present.
+	pub(crate) fn force(&self, type_id: TypeId) -> bool {
+		self.data
+			.get(&type_id)
+			.and_then(|slot| slot.get(self))
+			.is_some()
+	}
+
+	/// Panics with `error`, naming the caller and the types of the values
+	/// in the `State` storage.
+	#[track_caller]
+	pub(crate) fn fail(&self, error: StateError) -> ! {
+		metrics!(if let StateError::MissingType { name } = error {
+			self.metrics.panic(name)
+		});
+		fail(error, Some(self), self.type_names())
+	}
+
+	/// Drops the value with the given `TypeId` if its time-to-live has
+	/// elapsed.
+	pub(crate) fn expire(&mut self, type_id: TypeId) {
+		if self.data.get(&type_id).is_some_and(Slot::is_expired) {
+			trace!(" dropping expired state data for type_id `{:?}`", type_id);
+			self.remove_slot(type_id);
+		}
+	}
+}
+
+impl fmt::Debug for State {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("State")
+			.field("types", &self.type_names().collect::<Vec<_>>())
+			.finish()
 	}
 }
 
-fn missing<T: 'static>() -> ! {
+/// Panics with `error` for a container holding values of the `stored`
+/// types. A missing value is reported by the missing handler of `state`, the
+/// `State` the container is built on, or by the global handler, which is
+/// given an empty `State` for containers that are not built on one.
+/// Otherwise the message names the caller, the stored types and the ones
+/// that are close to the requested type.
+#[track_caller]
+pub(crate) fn fail(
+	error: StateError,
+	state: Option<&State>,
+	stored: impl IntoIterator<Item = &'static str>,
+) -> ! {
+	fail_at(error, state, stored, Location::caller())
+}
+
+/// Panics like [`fail`], naming `caller` as the caller, for async methods,
+/// which cannot track their caller themselves.
+pub(crate) fn fail_at(
+	error: StateError,
+	state: Option<&State>,
+	stored: impl IntoIterator<Item = &'static str>,
+	caller: &'static Location<'static>,
+) -> ! {
+	let handler = match state {
+		Some(state) => state.missing_handler(),
+		None => missing_handler::global(),
+	};
+	if let (StateError::MissingType { name }, Some(handler)) = (&error, handler) {
+		let message = match state {
+			Some(state) => handler(name, state),
+			None => handler(name, &State::default()),
+		};
+		panic!("{} (called at {})", message, caller);
+	}
+	let names: Vec<_> = stored.into_iter().collect();
+	let suggestions = match error {
+		StateError::MissingType { name } => {
+			let near = error::near_misses(name, names.iter().copied());
+			match near.is_empty() {
+				true => String::new(),
+				false => format!("; did you mean {}?", near.join(" or ")),
+			}
+		},
+		_ => String::new(),
+	};
 	panic!(
-		"required type {} is not present in State container",
-		type_name::<T>()
+		"{} (called at {}, stored types: [{}]){}",
+		error,
+		caller,
+		names.join(", "),
+		suggestions
 	);
 }
+
+/// Panics because a value of type `T` is missing, as reported by [`fail`].
+#[track_caller]
+pub(crate) fn missing<T: 'static>(
+	state: Option<&State>,
+	stored: impl IntoIterator<Item = &'static str>,
+) -> ! {
+	fail(StateError::missing::<T>(), state, stored)
+}
@@ -0,0 +1,52 @@
+//! The locks used by the thread-safe containers.
+//!
+//! Every container locks through these wrappers, so the locks share one
+//! poison-free interface: a lock that was held while a thread panicked is
+//! acquired as usual, and the containers keep their values consistent by
+//! only holding the locks for short, non-panicking sections.
+
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::RwLockReadGuard;
+use std::sync::RwLockWriteGuard;
+
+/// A poison-free reader-writer lock.
+#[derive(Default, Debug)]
+pub(crate) struct RwLock<T>(std::sync::RwLock<T>);
+
+/// A poison-free mutual exclusion lock.
+#[derive(Default, Debug)]
+pub(crate) struct Mutex<T>(std::sync::Mutex<T>);
+
+impl<T> RwLock<T> {
+	pub(crate) const fn new(value: T) -> Self {
+		RwLock(std::sync::RwLock::new(value))
+	}
+
+	pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+		self.0.read().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+		self.0.write().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl<T> Mutex<T> {
+	pub(crate) const fn new(value: T) -> Self {
+		Mutex(std::sync::Mutex::new(value))
+	}
+
+	pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+		self.0.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Determines if a thread panicked while holding the lock.
+	pub(crate) fn is_poisoned(&self) -> bool {
+		self.0.is_poisoned()
+	}
+
+	pub(crate) fn clear_poison(&self) {
+		self.0.clear_poison();
+	}
+}
@@ -0,0 +1,79 @@
+/// Puts several values into a `State` at once, such as
+/// `state_put!(state, config, pool)`. Values for keyed storage follow a
+/// semicolon as `Key = value` pairs, such as
+/// `state_put!(state, config; Primary = primary_pool, Replica = replica_pool)`,
+/// and are put with [`put_keyed`](crate::State::put_keyed).
+///
+/// `state` may be a `State` or a `&mut State`, and is evaluated once for
+/// every value.
+///
+/// ```
+/// use gotham_state::state_put;
+/// use gotham_state::State;
+///
+/// struct Primary;
+///
+/// fn middleware(state: &mut State) {
+///     state_put!(state, 1u8, 2u16; Primary = 3u32);
+/// }
+///
+/// let mut state = State::default();
+/// middleware(&mut state);
+/// state_put!(state, 4u64);
+/// assert_eq!(state.borrow::<u16>(), &2);
+/// assert_eq!(state.borrow_keyed::<Primary, u32>(), &3);
+/// assert_eq!(state.len(), 4);
+/// ```
+#[macro_export]
+macro_rules! state_put {
+	($state:expr $(,)?) => {{
+		let _ = &$state;
+	}};
+	($state:expr, $($value:expr),+ $(,)?) => {{
+		$($state.put($value);)+
+	}};
+	($state:expr, $($value:expr),+ ; $($key:ty = $keyed:expr),+ $(,)?) => {{
+		$($state.put($value);)+
+		$($state.put_keyed::<$key, _>($keyed);)+
+	}};
+	($state:expr; $($key:ty = $keyed:expr),+ $(,)?) => {{
+		$($state.put_keyed::<$key, _>($keyed);)+
+	}};
+}
+
+/// Borrows several values from a `State` at once with mixed mutability and
+/// binds them to local variables, such as
+/// `state_borrow!(state => { db: &DbPool, cfg: &mut Config })`.
+///
+/// This expands to [`borrow_many`](crate::State::borrow_many), so `state`
+/// must be a mutable `State` or a `&mut State`, and every type must be a
+/// `&T` or `&mut T` reference.
+///
+/// ```
+/// use gotham_state::state_borrow;
+/// use gotham_state::State;
+///
+/// fn middleware(state: &mut State) -> u16 {
+///     state_borrow!(state => { limit: &u8, hits: &mut u16 });
+///     *hits += u16::from(*limit);
+///     *hits
+/// }
+///
+/// let mut state = State::default();
+/// state.put(2u8);
+/// state.put(1u16);
+/// assert_eq!(middleware(&mut state), 3);
+/// state_borrow!(state => { hits: &u16 });
+/// assert_eq!(*hits, 3);
+/// ```
+///
+/// # Panics
+///
+/// If any of the values is not present in `State`, if a type is listed more
+/// than once, or if a frozen value is borrowed mutably.
+#[macro_export]
+macro_rules! state_borrow {
+	($state:expr => { $($name:ident: $ty:ty),+ $(,)? }) => {
+		let ($($name,)+) = $state.borrow_many::<($($ty,)+)>();
+	};
+}
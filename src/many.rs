@@ -0,0 +1,269 @@
+use crate::State;
+use crate::StateError;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+
+/// A shared or mutable reference to a value in a `State`, as requested in a
+/// [`BorrowMany`] tuple.
+pub trait Fetch<'a>: Sized {
+	/// The `TypeId` of the referenced value.
+	fn type_id() -> TypeId;
+
+	/// The name of the type of the referenced value.
+	fn type_name() -> &'static str;
+
+	/// Whether the referenced value is borrowed mutably.
+	fn is_mut() -> bool;
+
+	/// Converts the slot of the referenced value into the reference.
+	fn fetch(value: &'a mut dyn Any) -> Option<Self>;
+}
+
+impl<'a, T: 'static> Fetch<'a> for &'a T {
+	fn type_id() -> TypeId {
+		TypeId::of::<T>()
+	}
+
+	fn type_name() -> &'static str {
+		type_name::<T>()
+	}
+
+	fn is_mut() -> bool {
+		false
+	}
+
+	fn fetch(value: &'a mut dyn Any) -> Option<Self> {
+		value.downcast_ref()
+	}
+}
+
+impl<'a, T: 'static> Fetch<'a> for &'a mut T {
+	fn type_id() -> TypeId {
+		TypeId::of::<T>()
+	}
+
+	fn type_name() -> &'static str {
+		type_name::<T>()
+	}
+
+	fn is_mut() -> bool {
+		true
+	}
+
+	fn fetch(value: &'a mut dyn Any) -> Option<Self> {
+		value.downcast_mut()
+	}
+}
+
+/// A tuple of [`Fetch`] references that can be borrowed from a `State` at
+/// once with [`State::borrow_many`], such as `(&A, &mut B)`.
+pub trait BorrowMany<'a>: Sized {
+	/// The `TypeId`s of the referenced values, in tuple order.
+	fn type_ids() -> Vec<TypeId>;
+
+	/// The names of the types of the referenced values, in tuple order.
+	fn type_names() -> Vec<&'static str>;
+
+	/// Whether each referenced value is borrowed mutably, in tuple order.
+	fn mutability() -> Vec<bool>;
+
+	/// Converts the slots of the referenced values, in tuple order, into the
+	/// tuple of references.
+	fn from_slots(slots: Vec<Option<&'a mut dyn Any>>) -> Option<Self>;
+}
+
+macro_rules! impl_borrow_many {
+	($($name:ident),+) => {
+		impl<'a, $($name: Fetch<'a>),+> BorrowMany<'a> for ($($name,)+) {
+			fn type_ids() -> Vec<TypeId> {
+				vec![$($name::type_id()),+]
+			}
+
+			fn type_names() -> Vec<&'static str> {
+				vec![$($name::type_name()),+]
+			}
+
+			fn mutability() -> Vec<bool> {
+				vec![$($name::is_mut()),+]
+			}
+
+			fn from_slots(slots: Vec<Option<&'a mut dyn Any>>) -> Option<Self> {
+				let mut slots = slots.into_iter();
+				Some(($($name::fetch(slots.next()??)?,)+))
+			}
+		}
+	};
+}
+
+impl_borrow_many!(A);
+impl_borrow_many!(A, B);
+impl_borrow_many!(A, B, C);
+impl_borrow_many!(A, B, C, D);
+impl_borrow_many!(A, B, C, D, E);
+impl_borrow_many!(A, B, C, D, E, F);
+impl_borrow_many!(A, B, C, D, E, F, G);
+impl_borrow_many!(A, B, C, D, E, F, G, H);
+
+impl State {
+	/// Tries to borrow several values from the `State` storage at once, with
+	/// mixed mutability, such as `state.try_borrow_many::<(&A, &mut B)>()`.
+	/// Returns `None` if any of the values is missing, if a type is listed
+	/// more than once, or if a frozen value is requested mutably.
+	pub fn try_borrow_many<'a, B: BorrowMany<'a>>(&'a mut self) -> Option<B> {
+		let type_ids = B::type_ids();
+		trace!(" borrowing many state data for type_ids `{:?}`", type_ids);
+		for (type_id, is_mut) in type_ids.iter().zip(B::mutability()) {
+			self.expire(*type_id);
+			if is_mut && self.is_frozen(*type_id) {
+				return None;
+			}
+			self.force(*type_id);
+		}
+		let mut slots: Vec<_> = type_ids.iter().map(|_| None).collect();
+		for (type_id, slot) in self.data.iter_mut() {
+			if let Some(i) = type_ids.iter().position(|id| id == type_id) {
+				slots[i] = slot.get_mut();
+			}
+		}
+		B::from_slots(slots)
+	}
+
+	/// Borrows several values from the `State` storage at once, with mixed
+	/// mutability, such as `state.borrow_many::<(&A, &mut B)>()`.
+	///
+	/// # Panics
+	///
+	/// If any of the values is not present in `State`, if a type is listed
+	/// more than once, or if a frozen value is requested mutably.
+	#[track_caller]
+	pub fn borrow_many<'a, B: BorrowMany<'a>>(&'a mut self) -> B {
+		let type_ids = B::type_ids();
+		let type_names = B::type_names();
+		let mutability = B::mutability();
+		for (i, type_id) in type_ids.iter().enumerate() {
+			if type_ids[..i].contains(type_id) {
+				panic!(
+					"required type {} is borrowed more than once from State container",
+					type_names[i]
+				);
+			}
+			self.expire(*type_id);
+			// A value still being constructed asynchronously is missing too.
+			if !self.force(*type_id) {
+				self.fail(StateError::MissingType {
+					name: type_names[i],
+				});
+			}
+			if mutability[i] && self.is_frozen(*type_id) {
+				self.fail(StateError::Frozen {
+					name: type_names[i],
+				});
+			}
+		}
+		self.try_borrow_many().expect("every slot is present")
+	}
+
+	/// Tries to mutably borrow two values of different types from the `State`
+	/// storage at once. Returns `None` if either value is missing or if `T`
+	/// and `U` are the same type.
+	pub fn try_split_mut<T: 'static, U: 'static>(&mut self) -> Option<(&mut T, &mut U)> {
+		self.try_borrow_many()
+	}
+
+	/// Mutably borrows two values of different types from the `State` storage
+	/// at once.
+	///
+	/// # Panics
+	///
+	/// If either value is not present in `State`, or if `T` and `U` are the
+	/// same type.
+	#[track_caller]
+	pub fn split_mut<T: 'static, U: 'static>(&mut self) -> (&mut T, &mut U) {
+		assert_ne!(
+			TypeId::of::<T>(),
+			TypeId::of::<U>(),
+			"split_mut requires two distinct types, got {} twice",
+			type_name::<T>()
+		);
+		self.borrow_many()
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use alloc::string::String;
+	use core::panic::AssertUnwindSafe;
+	use std::panic::catch_unwind;
+
+	fn panic_message(f: impl FnOnce()) -> String {
+		let error = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+		match error.downcast_ref::<&str>() {
+			Some(message) => String::from(*message),
+			None => error.downcast_ref::<String>().unwrap().clone(),
+		}
+	}
+
+	fn state() -> State {
+		let mut state = State::default();
+		state.put(1u8);
+		state.put(2u16);
+		state
+	}
+
+	#[test]
+	fn borrows_several_values() {
+		let mut state = state();
+		let (a, b) = state.borrow_many::<(&u8, &mut u16)>();
+		*b += u16::from(*a);
+		let (a, b) = state.split_mut::<u8, u16>();
+		*a += 1;
+		assert_eq!((*a, *b), (2, 3));
+	}
+
+	#[test]
+	fn duplicate_types_panic() {
+		let mut state = state();
+		let message = panic_message(|| {
+			state.borrow_many::<(&u8, &mut u8)>();
+		});
+		assert!(message.contains("borrowed more than once"), "{message}");
+		let message = panic_message(|| {
+			state.split_mut::<u8, u8>();
+		});
+		assert!(message.contains("requires two distinct types"), "{message}");
+	}
+
+	#[test]
+	fn missing_types_panic_at_the_caller() {
+		let mut state = state();
+		let message = panic_message(|| {
+			state.borrow_many::<(&u8, &u32)>();
+		});
+		assert!(
+			message.contains("required type u32 is not present"),
+			"{message}"
+		);
+		assert!(message.contains("many.rs"), "{message}");
+		let message = panic_message(|| {
+			state.split_mut::<u8, u32>();
+		});
+		assert!(message.contains("many.rs"), "{message}");
+	}
+
+	#[test]
+	fn frozen_values_are_only_borrowed_shared() {
+		let mut state = state();
+		state.freeze_type::<u8>();
+		assert!(state.try_borrow_many::<(&mut u8, &u16)>().is_none());
+		let (a, _) = state.borrow_many::<(&u8, &mut u16)>();
+		assert_eq!(*a, 1);
+		let message = panic_message(|| {
+			state.borrow_many::<(&mut u8, &u16)>();
+		});
+		assert!(message.contains("frozen"), "{message}");
+	}
+}
@@ -0,0 +1,84 @@
+//! The map backing the state containers, keyed by `TypeId`.
+//!
+//! The map is a `BTreeMap` by default, or a `HashMap` with a pass-through
+//! hasher if the `hashmap` feature is enabled, trading ordered iteration for
+//! faster lookups. With the `small` feature, the first few entries are kept
+//! inline and only spill into that map once there are more of them.
+
+use core::any::TypeId;
+
+#[cfg(not(feature = "hashmap"))]
+pub(crate) type Base<V> = alloc::collections::BTreeMap<TypeId, V>;
+
+#[cfg(feature = "hashmap")]
+pub(crate) type Base<V> =
+	std::collections::HashMap<TypeId, V, core::hash::BuildHasherDefault<TypeIdHasher>>;
+
+#[cfg(not(feature = "small"))]
+pub(crate) type Map<V> = Base<V>;
+
+#[cfg(feature = "small")]
+pub(crate) use crate::small_map::SmallMap as Map;
+
+/// Creates an empty map with room for at least `capacity` entries, if the
+/// map supports preallocation.
+pub(crate) fn with_capacity<V>(capacity: usize) -> Map<V> {
+	#[cfg(feature = "small")]
+	return Map::with_capacity(capacity);
+	#[cfg(not(feature = "small"))]
+	return base_with_capacity(capacity);
+}
+
+/// Reserves room for at least `additional` more entries, if the map supports
+/// preallocation.
+pub(crate) fn reserve<V>(map: &mut Map<V>, additional: usize) {
+	#[cfg(feature = "small")]
+	map.reserve(additional);
+	#[cfg(not(feature = "small"))]
+	base_reserve(map, additional);
+}
+
+pub(crate) fn base_with_capacity<V>(capacity: usize) -> Base<V> {
+	#[cfg(feature = "hashmap")]
+	return Base::with_capacity_and_hasher(capacity, Default::default());
+	#[cfg(not(feature = "hashmap"))]
+	{
+		// A `BTreeMap` allocates its nodes on demand.
+		let _ = capacity;
+		Base::default()
+	}
+}
+
+pub(crate) fn base_reserve<V>(map: &mut Base<V>, additional: usize) {
+	#[cfg(feature = "hashmap")]
+	map.reserve(additional);
+	#[cfg(not(feature = "hashmap"))]
+	let _ = (map, additional);
+}
+
+/// A hasher for `TypeId`s, which are already uniformly distributed hashes,
+/// so they are used as is instead of being hashed again.
+#[cfg(feature = "hashmap")]
+#[derive(Default)]
+pub(crate) struct TypeIdHasher(u64);
+
+#[cfg(feature = "hashmap")]
+impl core::hash::Hasher for TypeIdHasher {
+	fn finish(&self) -> u64 {
+		self.0
+	}
+
+	fn write_u64(&mut self, n: u64) {
+		self.0 ^= n;
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		// `TypeId` only writes whole integers, but fold any other input in
+		// case that changes.
+		for chunk in bytes.chunks(8) {
+			let mut buf = [0; 8];
+			buf[..chunk.len()].copy_from_slice(chunk);
+			self.0 = self.0.rotate_left(5) ^ u64::from_ne_bytes(buf);
+		}
+	}
+}
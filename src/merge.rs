@@ -0,0 +1,129 @@
+use crate::State;
+use crate::StateError;
+use alloc::vec::Vec;
+
+/// How [`State::merge`] resolves values whose type is present in both
+/// containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+	/// Values from the merged container replace existing values.
+	#[default]
+	Overwrite,
+	/// Existing values are kept and the merged values are dropped.
+	KeepExisting,
+	/// The merge fails with [`StateError::Conflict`] listing every type
+	/// present in both containers, and nothing is merged.
+	Error,
+}
+
+impl State {
+	/// Moves every value of `other` into this `State`, resolving values whose
+	/// type is present in both containers according to `policy`. The aliases
+	/// of `other` are carried over too, replacing aliases of the same name
+	/// unless `policy` is [`KeepExisting`](MergePolicy::KeepExisting).
+	///
+	/// Fails with [`StateError::Frozen`] if a value of `other` would replace
+	/// a frozen value, and nothing is merged.
+	pub fn merge(&mut self, mut other: State, policy: MergePolicy) -> Result<(), StateError> {
+		trace!(" merging state data with policy `{:?}`", policy);
+		match policy {
+			MergePolicy::Overwrite => {},
+			MergePolicy::KeepExisting => {
+				other
+					.data
+					.retain(|type_id, _| !self.data.contains_key(type_id));
+			},
+			MergePolicy::Error => {
+				let names: Vec<_> = other
+					.data
+					.iter()
+					.filter(|(type_id, _)| self.data.contains_key(type_id))
+					.map(|(_, slot)| slot.name)
+					.collect();
+				if !names.is_empty() {
+					return Err(StateError::Conflict { names });
+				}
+			},
+		}
+		if let Some((_, slot)) = other
+			.data
+			.iter()
+			.find(|(type_id, _)| self.is_frozen(**type_id))
+		{
+			return Err(StateError::Frozen { name: slot.name });
+		}
+		for (type_id, slot) in core::mem::take(&mut other.data) {
+			self.insert_slot(type_id, slot);
+		}
+		for (alias, type_id) in core::mem::take(&mut other.aliases) {
+			if policy == MergePolicy::KeepExisting {
+				self.aliases.entry(alias).or_insert(type_id);
+			} else {
+				self.aliases.insert(alias, type_id);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn states() -> (State, State) {
+		let mut state = State::default();
+		state.put(1u8);
+		state.put_named("width", 1u16);
+		let mut other = State::default();
+		other.put(2u8);
+		other.put_named("height", 2u32);
+		(state, other)
+	}
+
+	#[test]
+	fn overwrite_replaces_existing_values() {
+		let (mut state, other) = states();
+		state.merge(other, MergePolicy::Overwrite).unwrap();
+		assert_eq!(state.borrow::<u8>(), &2);
+		assert_eq!(state.borrow::<u16>(), &1);
+		assert_eq!(state.try_borrow_named::<u32>("height"), Some(&2));
+		assert_eq!(state.try_borrow_named::<u16>("width"), Some(&1));
+	}
+
+	#[test]
+	fn keep_existing_drops_merged_values() {
+		let (mut state, mut other) = states();
+		other.put_named("width", 2u8);
+		state.merge(other, MergePolicy::KeepExisting).unwrap();
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert_eq!(state.borrow::<u32>(), &2);
+		assert_eq!(state.try_borrow_named::<u16>("width"), Some(&1));
+		assert!(state.has_named("height"));
+	}
+
+	#[test]
+	fn error_merges_nothing_on_conflicts() {
+		let (mut state, other) = states();
+		let result = state.merge(other, MergePolicy::Error);
+		assert!(matches!(result, Err(StateError::Conflict { names }) if names == ["u8"]));
+		assert!(!state.has::<u32>() && !state.has_named("height"));
+		let (mut state, mut other) = states();
+		other.take::<u8>();
+		state.merge(other, MergePolicy::Error).unwrap();
+		assert_eq!(state.borrow::<u32>(), &2);
+	}
+
+	#[test]
+	fn frozen_values_are_not_replaced() {
+		let (mut state, other) = states();
+		state.freeze_type::<u8>();
+		let result = state.merge(other, MergePolicy::Overwrite);
+		assert!(matches!(result, Err(StateError::Frozen { name: "u8" })));
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert!(!state.has::<u32>());
+		let (mut state, other) = states();
+		state.freeze_type::<u8>();
+		state.merge(other, MergePolicy::KeepExisting).unwrap();
+		assert_eq!(state.borrow::<u32>(), &2);
+	}
+}
@@ -0,0 +1,100 @@
+use crate::State;
+use alloc::collections::BTreeMap;
+use core::cell::RefCell;
+
+/// Access counters for one type in a `State`, as returned by
+/// [`State::metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeMetrics {
+	/// The number of values put.
+	pub puts: u64,
+	/// The number of borrows that found a value.
+	pub hits: u64,
+	/// The number of borrows and takes that found no value.
+	pub misses: u64,
+	/// The number of values taken.
+	pub takes: u64,
+	/// The number of panics because no value was present.
+	pub panics: u64,
+}
+
+/// The counters of every type accessed in a `State`, kept in a `RefCell` so
+/// that shared borrows of the `State` can update them.
+#[derive(Default)]
+pub(crate) struct Metrics(RefCell<BTreeMap<&'static str, TypeMetrics>>);
+
+impl Metrics {
+	fn update(&self, name: &'static str, f: impl FnOnce(&mut TypeMetrics)) {
+		f(self.0.borrow_mut().entry(name).or_default());
+	}
+
+	pub(crate) fn put(&self, name: &'static str) {
+		self.update(name, |m| m.puts += 1);
+	}
+
+	pub(crate) fn lookup(&self, name: &'static str, hit: bool) {
+		self.update(name, |m| match hit {
+			true => m.hits += 1,
+			false => m.misses += 1,
+		});
+	}
+
+	pub(crate) fn take(&self, name: &'static str, hit: bool) {
+		self.update(name, |m| match hit {
+			true => m.takes += 1,
+			false => m.misses += 1,
+		});
+	}
+
+	pub(crate) fn panic(&self, name: &'static str) {
+		self.update(name, |m| m.panics += 1);
+	}
+}
+
+impl State {
+	/// Returns the access counters of every type that has been put into or
+	/// requested from this `State`, keyed by type name. Only the primary
+	/// accessors, such as [`put`](State::put), [`try_borrow`](State::try_borrow),
+	/// [`try_borrow_mut`](State::try_borrow_mut) and
+	/// [`try_take`](State::try_take), and the methods built on them are
+	/// counted.
+	pub fn metrics(&self) -> BTreeMap<&'static str, TypeMetrics> {
+		self.metrics.0.borrow().clone()
+	}
+
+	/// Resets every access counter of this `State` to zero.
+	pub fn reset_metrics(&self) {
+		self.metrics.0.borrow_mut().clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counts_accesses_by_type() {
+		let mut state = State::default();
+		state.put(1u8);
+		state.put(2u8);
+		assert_eq!(state.try_borrow::<u8>(), Some(&2));
+		assert_eq!(state.try_borrow::<u16>(), None);
+		*state.try_borrow_mut::<u8>().unwrap() += 1;
+		assert_eq!(state.try_take::<u8>(), Some(3));
+		assert_eq!(state.try_take::<u8>(), None);
+		let metrics = state.metrics();
+		assert_eq!(
+			metrics["u8"],
+			TypeMetrics {
+				puts: 2,
+				hits: 2,
+				misses: 1,
+				takes: 1,
+				panics: 0,
+			}
+		);
+		assert_eq!(metrics["u16"].misses, 1);
+		state.reset_metrics();
+		assert!(state.metrics().is_empty());
+	}
+}
@@ -0,0 +1,89 @@
+use crate::State;
+use alloc::string::String;
+
+/// Formats the panic message for a missing value, given the name of the
+/// requested type and the `State` it was requested from, as installed with
+/// [`State::set_missing_handler`].
+pub type MissingHandler = fn(&'static str, &State) -> String;
+
+#[cfg(feature = "std")]
+static GLOBAL: crate::lock::RwLock<Option<MissingHandler>> = crate::lock::RwLock::new(None);
+
+/// Installs a handler formatting the panic message of every `State` that has
+/// no handler of its own when a requested value is missing, such as to add
+/// a link to internal docs or the setup steps for well-known types.
+/// Containers that are not built on a `State`, such as
+/// [`SyncState`](crate::SyncState), use it too, and pass it an empty `State`.
+/// Replaces the previous global handler, and `None` restores the default
+/// message.
+#[cfg(feature = "std")]
+pub fn set_global_missing_handler(handler: Option<MissingHandler>) {
+	*GLOBAL.write() = handler;
+}
+
+impl State {
+	/// Installs a handler formatting the panic message of this `State` when a
+	/// requested value is missing, taking precedence over the handler
+	/// installed with [`set_global_missing_handler`]. `None` restores the
+	/// default message, or the global handler if one is installed.
+	///
+	/// The message is followed by the location of the caller.
+	pub fn set_missing_handler(&mut self, handler: Option<MissingHandler>) {
+		self.missing_handler = handler;
+	}
+
+	/// Returns the handler formatting the panic message when a requested
+	/// value is missing, if one is installed for this `State` or globally.
+	pub(crate) fn missing_handler(&self) -> Option<MissingHandler> {
+		self.missing_handler.or_else(global)
+	}
+}
+
+/// Returns the handler installed with [`set_global_missing_handler`], if any.
+pub(crate) fn global() -> Option<MissingHandler> {
+	#[cfg(feature = "std")]
+	return *GLOBAL.read();
+	#[cfg(not(feature = "std"))]
+	return None;
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use crate::StateRead;
+	use crate::SyncState;
+	use alloc::format;
+	use core::panic::AssertUnwindSafe;
+	use std::panic::catch_unwind;
+
+	fn panic_message(f: impl FnOnce()) -> String {
+		let error = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+		error.downcast_ref::<String>().unwrap().clone()
+	}
+
+	fn read<S: StateRead>(state: &S) -> u32 {
+		*state.borrow::<u32>()
+	}
+
+	#[test]
+	fn handler_formats_generic_borrows() {
+		let mut state = State::default();
+		state.set_missing_handler(Some(|name, _| format!("no {name} here")));
+		let message = panic_message(|| {
+			read(&state);
+		});
+		assert!(message.starts_with("no u32 here (called at "), "{message}");
+		assert!(message.contains("missing_handler.rs"), "{message}");
+	}
+
+	#[test]
+	fn containers_without_a_state_name_their_stored_types() {
+		let mut state = SyncState::default();
+		state.put(1u8);
+		let message = panic_message(|| {
+			state.borrow::<u32>();
+		});
+		assert!(message.contains("stored types: [u8]"), "{message}");
+		assert!(message.contains("missing_handler.rs"), "{message}");
+	}
+}
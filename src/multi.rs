@@ -0,0 +1,36 @@
+use crate::State;
+use alloc::vec::Vec;
+
+/// The slot holding every value of type `T` added with [`State::push`],
+/// kept apart from the single value of type `T` put with [`State::put`].
+struct Multi<T>(Vec<T>);
+
+impl State {
+	/// Appends a value to the collection of values of type `T` in the `State`
+	/// storage, for one-to-many registration such as a list of middleware
+	/// descriptors. The collection is separate from the single value of type
+	/// `T` managed by [`put`](State::put).
+	pub fn push<T: 'static>(&mut self, t: T) {
+		self.entry::<Multi<T>>()
+			.or_insert_with(|| Multi(Vec::new()))
+			.0
+			.push(t);
+	}
+
+	/// Returns an iterator over the values of type `T` added with
+	/// [`push`](State::push), in the order they were added.
+	pub fn iter_all<T: 'static>(&self) -> impl Iterator<Item = &T> + '_ {
+		self.try_borrow::<Multi<T>>()
+			.map(|multi| multi.0.as_slice())
+			.unwrap_or_default()
+			.iter()
+	}
+
+	/// Moves every value of type `T` added with [`push`](State::push) out of
+	/// the `State` storage, in the order they were added.
+	pub fn drain_all<T: 'static>(&mut self) -> Vec<T> {
+		self.try_take::<Multi<T>>()
+			.map(|multi| multi.0)
+			.unwrap_or_default()
+	}
+}
@@ -0,0 +1,49 @@
+use crate::State;
+use alloc::string::String;
+use core::any::TypeId;
+
+impl State {
+	/// Puts a value into the `State` storage like [`put`](State::put), and
+	/// registers `alias` as a human-readable name for its type, such as
+	/// `"db"`, for reflection and debugging. An alias that was registered
+	/// for another type is moved to `T`.
+	pub fn put_named<T: 'static>(&mut self, alias: impl Into<String>, t: T) {
+		let alias = alias.into();
+		trace!(" inserting record to state with alias `{}`", alias);
+		self.put(t);
+		self.aliases.insert(alias, TypeId::of::<T>());
+	}
+
+	/// Determines if a value is present for the given alias.
+	pub fn has_named(&self, alias: &str) -> bool {
+		self.aliases
+			.get(alias)
+			.is_some_and(|type_id| self.data.get(type_id).is_some_and(|s| !s.is_expired()))
+	}
+
+	/// Returns the name of the type registered under the given alias, if a
+	/// value of that type is present.
+	pub fn type_name_of(&self, alias: &str) -> Option<&'static str> {
+		let type_id = self.aliases.get(alias)?;
+		let slot = self.data.get(type_id).filter(|s| !s.is_expired())?;
+		Some(slot.name)
+	}
+
+	/// Tries to borrow the value registered under the given alias. Returns
+	/// `None` if no value is present for the alias or if it is not of type
+	/// `T`.
+	pub fn try_borrow_named<T: 'static>(&self, alias: &str) -> Option<&T> {
+		match self.aliases.get(alias) {
+			Some(type_id) if *type_id == TypeId::of::<T>() => self.try_borrow(),
+			_ => None,
+		}
+	}
+
+	/// Returns an iterator over the aliases whose values are present, in
+	/// alphabetical order, together with the names of their types.
+	pub fn aliases(&self) -> impl Iterator<Item = (&str, &'static str)> + '_ {
+		self.aliases
+			.keys()
+			.filter_map(|alias| Some((alias.as_str(), self.type_name_of(alias)?)))
+	}
+}
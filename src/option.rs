@@ -0,0 +1,55 @@
+use crate::State;
+
+impl State {
+	/// Puts `Some` value of type `T` into the `State` storage, or removes the
+	/// value of type `T` for `None`. A stored `Option<T>` is removed in
+	/// either case, so the value is only ever stored as `T`.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` or `Option<T>` has been frozen.
+	pub fn put_option<T: 'static>(&mut self, t: Option<T>) {
+		self.remove::<Option<T>>();
+		match t {
+			Some(t) => self.put(t),
+			None => {
+				self.remove::<T>();
+			},
+		}
+	}
+
+	/// Borrows the value of type `T` from the `State` storage, falling back
+	/// to the contents of a stored `Option<T>`, so an absent value and a
+	/// stored `None` are treated alike.
+	pub fn borrow_option<T: 'static>(&self) -> Option<&T> {
+		self.try_borrow()
+			.or_else(|| self.try_borrow::<Option<T>>()?.as_ref())
+	}
+
+	/// Moves the value of type `T` out of the `State` storage, falling back
+	/// to the contents of a stored `Option<T>`, which is removed as well.
+	pub fn take_option<T: 'static>(&mut self) -> Option<T> {
+		let t = self.try_take::<T>();
+		let option = self.try_take::<Option<T>>().flatten();
+		t.or(option)
+	}
+
+	/// Migrates a stored `Option<T>` to the `T` representation, putting its
+	/// contents as `T` unless a `T` is already present. Returns whether an
+	/// `Option<T>` was present.
+	///
+	/// # Panics
+	///
+	/// If the stored `Option<T>` has been frozen.
+	pub fn normalize_option<T: 'static>(&mut self) -> bool {
+		if !self.has::<Option<T>>() {
+			return false;
+		}
+		if let Some(t) = self.take::<Option<T>>() {
+			if !self.has::<T>() {
+				self.put(t);
+			}
+		}
+		true
+	}
+}
@@ -0,0 +1,144 @@
+use crate::State;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+/// A pool of `State` containers that are recycled instead of reallocated,
+/// for workloads that create many short-lived states.
+///
+/// Containers are handed out by [`acquire`](StatePool::acquire) and returned
+/// to the pool when the [`PooledState`] guard is dropped.
+#[derive(Default)]
+pub struct StatePool {
+	idle: RefCell<Vec<State>>,
+}
+
+/// A `State` borrowed from a [`StatePool`]. It dereferences to the `State`,
+/// and clears it and returns it to the pool when dropped.
+pub struct PooledState<'a> {
+	state: Option<State>,
+	pool: &'a StatePool,
+}
+
+impl StatePool {
+	/// Creates an empty pool.
+	pub fn new() -> Self {
+		StatePool::default()
+	}
+
+	/// Takes an empty `State` from the pool, or creates a new one if the pool
+	/// has none left.
+	pub fn acquire(&self) -> PooledState<'_> {
+		let state = self.idle.borrow_mut().pop().unwrap_or_default();
+		trace!(" acquiring state from pool");
+		PooledState {
+			state: Some(state),
+			pool: self,
+		}
+	}
+
+	/// Returns the number of containers waiting in the pool to be reused.
+	pub fn idle(&self) -> usize {
+		self.idle.borrow().len()
+	}
+
+	/// Drops every container waiting in the pool.
+	pub fn shrink(&self) {
+		self.idle.borrow_mut().clear();
+	}
+}
+
+impl PooledState<'_> {
+	/// Detaches the `State` from the pool, so that it is not returned to the
+	/// pool when dropped.
+	pub fn into_inner(mut self) -> State {
+		self.state.take().expect("state is present until dropped")
+	}
+}
+
+impl Deref for PooledState<'_> {
+	type Target = State;
+
+	fn deref(&self) -> &State {
+		self.state.as_ref().expect("state is present until dropped")
+	}
+}
+
+impl DerefMut for PooledState<'_> {
+	fn deref_mut(&mut self) -> &mut State {
+		self.state.as_mut().expect("state is present until dropped")
+	}
+}
+
+impl Drop for PooledState<'_> {
+	fn drop(&mut self) {
+		if let Some(mut state) = self.state.take() {
+			trace!(" returning state to pool");
+			// Only the storage is reused, to keep its capacity. Everything
+			// else the last user configured, such as hooks and handlers,
+			// must not leak to the next one.
+			state.clear();
+			let state = State {
+				data: core::mem::take(&mut state.data),
+				..State::default()
+			};
+			self.pool.idle.borrow_mut().push(state);
+		}
+	}
+}
+
+impl fmt::Debug for StatePool {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("StatePool")
+			.field("idle", &self.idle())
+			.finish()
+	}
+}
+
+impl fmt::Debug for PooledState<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::boxed::Box;
+	#[cfg(feature = "std")]
+	use alloc::string::String;
+
+	#[test]
+	fn fallback_does_not_leak_to_next_user() {
+		let pool = StatePool::new();
+		let mut state = pool.acquire();
+		state.set_fallback(|_| Some(Box::new(42u32)));
+		assert_eq!(state.try_borrow::<u32>(), Some(&42));
+		drop(state);
+
+		let state = pool.acquire();
+		assert_eq!(pool.idle(), 0);
+		assert_eq!(state.try_borrow::<u32>(), None);
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn configuration_does_not_leak_to_next_user() {
+		let pool = StatePool::new();
+		let mut state = pool.acquire();
+		state.set_missing_handler(Some(|name, _| alloc::format!("no {name}")));
+		state.on_change(|_| panic!("hook of the last user"));
+		drop(state);
+
+		let mut state = pool.acquire();
+		state.put(1u8);
+		let message = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			state.borrow::<u16>();
+		}))
+		.unwrap_err();
+		let message = message.downcast_ref::<String>().unwrap();
+		assert!(message.contains("stored types: [u8]"), "{message}");
+	}
+}
@@ -0,0 +1,85 @@
+use crate::slab::Prioritized;
+use crate::slot::Slot;
+use crate::State;
+use core::any::TypeId;
+
+impl State {
+	/// Puts a value into the `State` storage like [`put`](State::put), with
+	/// a `priority` that orders it before the values with a higher priority
+	/// when iterating, such as with [`iter`](State::iter) and
+	/// [`type_names`](State::type_names), and after them when tearing down
+	/// with [`shutdown`](State::shutdown) in reverse order and when
+	/// dropping. Values put by other means have priority `0`, and values
+	/// with the same priority are ordered by insertion.
+	///
+	/// Overwriting the value with [`put`](State::put) keeps its priority.
+	///
+	/// # Panics
+	///
+	/// If the existing value of type `T` has been frozen.
+	pub fn put_with_priority<T: 'static>(&mut self, t: T, priority: i32) {
+		let type_id = TypeId::of::<T>();
+		let mut slot = Slot::new(t);
+		slot.priority = Some(priority);
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Returns the priority of the value of type `T`, if present.
+	pub fn priority<T: 'static>(&self) -> Option<i32> {
+		self.data.get(&TypeId::of::<T>()).map(Prioritized::priority)
+	}
+
+	/// Sets the priority of the value of type `T`, returning whether one is
+	/// present.
+	pub fn set_priority<T: 'static>(&mut self, priority: i32) -> bool {
+		let type_id = TypeId::of::<T>();
+		let Some(slot) = self.data.get_mut(&type_id) else {
+			return false;
+		};
+		slot.priority = Some(priority);
+		self.data.reorder(&type_id);
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::MergePolicy;
+	use alloc::vec::Vec;
+
+	#[test]
+	fn iterates_by_priority_then_insertion() {
+		let mut state = State::default();
+		state.put_with_priority(1u8, 1);
+		state.put(2u16);
+		state.put_with_priority(3u32, -1);
+		state.put(4u64);
+		let names: Vec<_> = state.type_names().collect();
+		assert_eq!(names, ["u32", "u16", "u64", "u8"]);
+	}
+
+	#[test]
+	fn overwriting_keeps_priority() {
+		let mut state = State::default();
+		state.put_with_priority(1u8, 3);
+		state.put(2u8);
+		assert_eq!(state.priority::<u8>(), Some(3));
+		assert!(state.set_priority::<u8>(-2));
+		assert_eq!(state.priority::<u8>(), Some(-2));
+		assert!(!state.set_priority::<u16>(1));
+	}
+
+	#[test]
+	fn priority_survives_merge() {
+		let mut a = State::default();
+		a.put_with_priority(1u16, 7);
+		a.put(2u8);
+		let mut b = State::default();
+		b.put(3u32);
+		b.merge(a, MergePolicy::Overwrite).unwrap();
+		assert_eq!(b.priority::<u16>(), Some(7));
+		let names: Vec<_> = b.type_names().collect();
+		assert_eq!(names, ["u32", "u8", "u16"]);
+	}
+}
@@ -0,0 +1,28 @@
+use crate::State;
+
+impl State {
+	/// Creates a fresh `State` from this one used as a prototype, such as one
+	/// for each worker in a worker-per-core server. Values put with
+	/// [`put_cloneable`](State::put_cloneable) are cloned, and values
+	/// registered with [`register_factory`](State::register_factory) are
+	/// constructed again by their factory on first access in the new `State`.
+	/// Other values and change hooks are not copied.
+	pub fn spawn(&self) -> State {
+		trace!(" spawning state data from prototype");
+		let data = self
+			.data
+			.iter()
+			.filter(|(_, slot)| !slot.is_expired())
+			.filter_map(|(type_id, slot)| Some((*type_id, slot.respawn()?)))
+			.collect();
+		State {
+			data,
+			..State::default()
+		}
+	}
+
+	/// Creates a fresh `State` from `prototype`. See [`spawn`](State::spawn).
+	pub fn from_prototype(prototype: &State) -> State {
+		prototype.spawn()
+	}
+}
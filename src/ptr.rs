@@ -0,0 +1,39 @@
+use crate::State;
+#[cfg(not(feature = "arc"))]
+use alloc::rc::Rc as Ptr;
+#[cfg(feature = "arc")]
+use alloc::sync::Arc as Ptr;
+
+/// The reference-counted pointer used by [`State::put_rc`]: an `Rc` by
+/// default, for single-threaded runtimes, or an `Arc` with the `arc`
+/// feature, for multithreaded servers.
+///
+/// Code written against `SharedPtr` works unchanged with either, as both
+/// pointers share the same API for cloning and dereferencing.
+pub type SharedPtr<T> = Ptr<T>;
+
+impl State {
+	/// Puts a value into the `State` storage wrapped in a [`SharedPtr`], so
+	/// it can be handed out with [`get_rc`](State::get_rc). The value is
+	/// stored as a `SharedPtr<T>`, and can also be borrowed as such.
+	pub fn put_rc<T: 'static>(&mut self, t: T) {
+		self.put(SharedPtr::new(t));
+	}
+
+	/// Tries to clone the `SharedPtr<T>` stored in the `State` storage,
+	/// whether it was put with [`put_rc`](State::put_rc) or as a
+	/// `SharedPtr<T>`.
+	pub fn try_get_rc<T: 'static>(&self) -> Option<SharedPtr<T>> {
+		self.try_borrow::<SharedPtr<T>>().cloned()
+	}
+
+	/// Clones the `SharedPtr<T>` stored in the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `SharedPtr<T>` is not present in `State`.
+	#[track_caller]
+	pub fn get_rc<T: 'static>(&self) -> SharedPtr<T> {
+		self.borrow::<SharedPtr<T>>().clone()
+	}
+}
@@ -0,0 +1,62 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+use core::any::Any;
+use core::any::TypeId;
+
+impl State {
+	/// Borrows the value with the given `TypeId` from the `State` storage
+	/// without naming its type.
+	pub fn get_raw(&self, type_id: &TypeId) -> Option<&dyn Any> {
+		trace!(" borrowing raw state data for type_id `{:?}`", type_id);
+		self.data
+			.get(type_id)
+			.filter(|s| !s.is_expired())
+			.and_then(|s| s.get(self))
+	}
+
+	/// Mutably borrows the value with the given `TypeId` from the `State`
+	/// storage without naming its type. Returns `None` if the value has been
+	/// frozen.
+	pub fn get_raw_mut(&mut self, type_id: &TypeId) -> Option<&mut dyn Any> {
+		trace!(
+			" mutably borrowing raw state data for type_id `{:?}`",
+			type_id
+		);
+		self.expire(*type_id);
+		if self.is_frozen(*type_id) {
+			return None;
+		}
+		self.force(*type_id);
+		self.data.get_mut(type_id).and_then(Slot::get_mut)
+	}
+
+	/// Puts a boxed value into the `State` storage under the given `TypeId`
+	/// without naming its type. Since the type name is not known, the value
+	/// is listed as `<unknown>` by [`type_names`](State::type_names).
+	///
+	/// # Panics
+	///
+	/// If `value` is not of the type identified by `type_id`, or if the
+	/// existing value has been frozen.
+	pub fn insert_raw(&mut self, type_id: TypeId, value: Box<dyn Any>) {
+		trace!(" inserting raw record to state for type_id `{:?}`", type_id);
+		assert_eq!(
+			(*value).type_id(),
+			type_id,
+			"value put with insert_raw does not match its TypeId"
+		);
+		self.insert_slot(type_id, Slot::named(value, "<unknown>"));
+	}
+
+	/// Moves the value with the given `TypeId` out of the `State` storage
+	/// without naming its type. Returns `None` if the value has been frozen.
+	pub fn remove_raw(&mut self, type_id: &TypeId) -> Option<Box<dyn Any>> {
+		trace!(" removing raw state data for type_id `{:?}`", type_id);
+		self.expire(*type_id);
+		if self.is_frozen(*type_id) || !self.force(*type_id) {
+			return None;
+		}
+		self.remove_slot(*type_id).and_then(Slot::into_value)
+	}
+}
@@ -0,0 +1,153 @@
+use crate::State;
+use core::any::type_name;
+use core::ptr;
+
+impl State {
+	/// Replaces the value of type `T` in the `State` storage with the result
+	/// of calling `f` with it by value. The value stays in its slot, so
+	/// whatever was recorded when it was put, such as a clone function or a
+	/// time-to-live, is kept.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`, or if it has been
+	/// frozen. If `f` panics the process is aborted, since there is no value
+	/// left to put back; use [`replace_with_or_else`](State::replace_with_or_else)
+	/// to recover instead.
+	#[track_caller]
+	pub fn replace_with<T: 'static>(&mut self, f: impl FnOnce(T) -> T) {
+		self.replace_with_or_else(
+			|| {
+				panic!(
+					"replace_with closure for {} panicked, aborting",
+					type_name::<T>()
+				)
+			},
+			f,
+		);
+	}
+
+	/// Replaces the value of type `T` in the `State` storage with the result
+	/// of calling `f` with it by value. If `f` panics, the result of
+	/// `recover` is put back before the panic continues.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`, or if it has been
+	/// frozen. If both `f` and `recover` panic, the process is aborted.
+	#[track_caller]
+	pub fn replace_with_or_else<T: 'static>(
+		&mut self,
+		recover: impl FnOnce() -> T,
+		f: impl FnOnce(T) -> T,
+	) {
+		replace(self.borrow_mut(), recover, |t| Ok::<_, (T, ())>(f(t))).ok();
+	}
+
+	/// Replaces the value of type `T` in the `State` storage with the result
+	/// of calling `f` with it by value. If `f` fails, it hands the original
+	/// value back together with the error, and the original is put back.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`, or if it has been
+	/// frozen. If `f` panics the process is aborted, as with
+	/// [`replace_with`](State::replace_with).
+	#[track_caller]
+	pub fn try_replace_with<T: 'static, E>(
+		&mut self,
+		f: impl FnOnce(T) -> Result<T, (T, E)>,
+	) -> Result<(), E> {
+		let recover = || {
+			panic!(
+				"try_replace_with closure for {} panicked, aborting",
+				type_name::<T>()
+			)
+		};
+		replace(self.borrow_mut(), recover, f)
+	}
+}
+
+/// Moves `value` into `f` and writes back what it returns, either the new
+/// value or the original one together with an error.
+fn replace<T, E>(
+	value: &mut T,
+	recover: impl FnOnce() -> T,
+	f: impl FnOnce(T) -> Result<T, (T, E)>,
+) -> Result<(), E> {
+	/// Fills the moved-out value with the result of `recover` if `f` unwinds.
+	struct Hole<T, R: FnOnce() -> T> {
+		/// Points to the value, which is uninitialized while `recover` is set.
+		value: *mut T,
+		recover: Option<R>,
+	}
+
+	impl<T, R: FnOnce() -> T> Drop for Hole<T, R> {
+		fn drop(&mut self) {
+			if let Some(recover) = self.recover.take() {
+				// SAFETY: `recover` is only still set if `f` unwound, so the
+				// value was moved out and nothing was written back, and the
+				// `&mut T` it points to is still borrowed by `replace`.
+				// Writing does not drop the moved-out value twice. If
+				// `recover` panics too, that panic happens while unwinding
+				// and aborts the process, so the uninitialized value is never
+				// observed.
+				unsafe { ptr::write(self.value, recover()) };
+			}
+		}
+	}
+
+	let value: *mut T = value;
+	let mut hole = Hole {
+		value,
+		recover: Some(recover),
+	};
+	// SAFETY: the value is moved out of a valid `&mut T`, and is always
+	// written back before the borrow ends: the result of `f` below, or the
+	// result of `recover` when `hole` is dropped while unwinding.
+	let (new, result) = match f(unsafe { ptr::read(value) }) {
+		Ok(new) => (new, Ok(())),
+		Err((original, e)) => (original, Err(e)),
+	};
+	hole.recover = None;
+	// SAFETY: the value was moved out above and nothing was written back.
+	unsafe { ptr::write(value, new) };
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::String;
+
+	#[test]
+	fn replaces_by_value() {
+		let mut state = State::default();
+		state.put(String::from("a"));
+		state.replace_with(|s: String| s + "b");
+		assert_eq!(state.borrow::<String>(), "ab");
+		let result = state.try_replace_with(|s: String| Err::<String, _>((s, "failed")));
+		assert_eq!(result, Err("failed"));
+		assert_eq!(state.borrow::<String>(), "ab");
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn panicking_closure_puts_back_the_recovered_value() {
+		let mut state = State::default();
+		state.put(String::from("a"));
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			state.replace_with_or_else(
+				|| String::from("recovered"),
+				|s: String| {
+					drop(s);
+					panic!("closure failed");
+				},
+			);
+		}));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<String>(), "recovered");
+		state.replace_with(|s: String| s + " again");
+		assert_eq!(state.borrow::<String>(), "recovered again");
+	}
+}
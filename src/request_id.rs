@@ -0,0 +1,79 @@
+use crate::State;
+use core::fmt;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+
+/// A correlation id for the request a `State` belongs to, formatted as a
+/// version 4 UUID.
+///
+/// Generated ids are random but not cryptographically secure, so they must
+/// not be used as secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId(u128);
+
+impl RequestId {
+	/// Generates a new random id.
+	pub fn generate() -> Self {
+		let bits = (u128::from(random_u64()) << 64) | u128::from(random_u64());
+		// Set the version to 4 and the variant to RFC 4122.
+		let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+		let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+		RequestId(bits)
+	}
+
+	/// Creates an id from its 128-bit representation, such as an id received
+	/// from an upstream service.
+	pub fn from_u128(bits: u128) -> Self {
+		RequestId(bits)
+	}
+
+	/// Returns the 128-bit representation of the id.
+	pub fn as_u128(&self) -> u128 {
+		self.0
+	}
+}
+
+impl fmt::Display for RequestId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let bits = self.0;
+		write!(
+			f,
+			"{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+			bits >> 96,
+			(bits >> 80) & 0xffff,
+			(bits >> 64) & 0xffff,
+			(bits >> 48) & 0xffff,
+			bits & 0xffff_ffff_ffff
+		)
+	}
+}
+
+impl State {
+	/// Creates a `State` holding a newly generated [`RequestId`].
+	pub fn with_request_id() -> Self {
+		let mut state = State::default();
+		state.put(RequestId::generate());
+		state
+	}
+
+	/// Returns the [`RequestId`] of the request this `State` belongs to.
+	///
+	/// # Panics
+	///
+	/// If a `RequestId` is not present in `State`.
+	pub fn request_id(&self) -> RequestId {
+		*self.borrow::<RequestId>()
+	}
+}
+
+/// Returns 64 random bits from the standard library's randomly seeded
+/// hasher, without depending on a random number generator crate.
+fn random_u64() -> u64 {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+	hasher.finish()
+}
@@ -0,0 +1,149 @@
+//! A registry of resources keyed by integer ids, companion to the type map
+//! for embedders building JS runtimes, where handles such as files and
+//! sockets are passed to scripts as numbers.
+
+use crate::State;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use core::any::Any;
+use core::fmt;
+
+/// The id of a resource in a [`ResourceTable`].
+pub type ResourceId = u32;
+
+/// A value that can be held in a [`ResourceTable`].
+pub trait Resource: Any {
+	/// Returns a name for the resource, for diagnostics.
+	fn name(&self) -> &str {
+		core::any::type_name::<Self>()
+	}
+
+	/// Releases the resource when it is removed with
+	/// [`close`](ResourceTable::close). Other clones of the `Rc` may still
+	/// be alive, so the resource should stop operations that are in flight.
+	fn close(self: Rc<Self>) {}
+}
+
+struct Entry {
+	any: Rc<dyn Any>,
+	resource: Rc<dyn Resource>,
+}
+
+/// A table of resources keyed by [`ResourceId`], as returned by
+/// [`State::resources`].
+///
+/// Ids are assigned in increasing order and are not reused.
+#[derive(Default)]
+pub struct ResourceTable {
+	index: BTreeMap<ResourceId, Entry>,
+	next_rid: ResourceId,
+}
+
+impl State {
+	/// Returns the resource table of the `State`, creating an empty table
+	/// the first time.
+	pub fn resources(&mut self) -> &mut ResourceTable {
+		if !self.has::<ResourceTable>() {
+			self.put(ResourceTable::default());
+		}
+		self.borrow_mut()
+	}
+
+	/// Tries to borrow the resource table of the `State`, if it has been
+	/// created.
+	pub fn try_resources(&self) -> Option<&ResourceTable> {
+		self.try_borrow()
+	}
+}
+
+impl ResourceTable {
+	/// Adds a resource to the table, returning its id.
+	pub fn add<T: Resource>(&mut self, resource: T) -> ResourceId {
+		self.add_rc(Rc::new(resource))
+	}
+
+	/// Adds a resource that is already held in an `Rc` to the table,
+	/// returning its id.
+	///
+	/// # Panics
+	///
+	/// If all `u32::MAX` ids have been assigned.
+	pub fn add_rc<T: Resource>(&mut self, resource: Rc<T>) -> ResourceId {
+		let rid = self.next_rid;
+		self.next_rid = rid.checked_add(1).expect("resource ids exhausted");
+		let entry = Entry {
+			any: resource.clone(),
+			resource,
+		};
+		self.index.insert(rid, entry);
+		rid
+	}
+
+	/// Determines if a resource with the given id is in the table.
+	pub fn has(&self, rid: ResourceId) -> bool {
+		self.index.contains_key(&rid)
+	}
+
+	/// Returns the number of resources in the table.
+	pub fn len(&self) -> usize {
+		self.index.len()
+	}
+
+	/// Determines if the table holds no resources.
+	pub fn is_empty(&self) -> bool {
+		self.index.is_empty()
+	}
+
+	/// Gets the resource with the given id. Returns `None` if no resource
+	/// with that id is in the table, or if it is not of type `T`.
+	pub fn get<T: Resource>(&self, rid: ResourceId) -> Option<Rc<T>> {
+		self.index.get(&rid)?.any.clone().downcast().ok()
+	}
+
+	/// Gets the resource with the given id, whatever its type.
+	pub fn get_any(&self, rid: ResourceId) -> Option<Rc<dyn Resource>> {
+		self.index.get(&rid).map(|entry| entry.resource.clone())
+	}
+
+	/// Removes the resource with the given id from the table and returns it,
+	/// without closing it. Returns `None`, leaving the table unchanged, if
+	/// no resource with that id is in the table, or if it is not of type
+	/// `T`.
+	pub fn take<T: Resource>(&mut self, rid: ResourceId) -> Option<Rc<T>> {
+		let resource = self.get(rid)?;
+		self.index.remove(&rid);
+		Some(resource)
+	}
+
+	/// Removes the resource with the given id from the table, whatever its
+	/// type, and returns it without closing it.
+	pub fn take_any(&mut self, rid: ResourceId) -> Option<Rc<dyn Resource>> {
+		self.index.remove(&rid).map(|entry| entry.resource)
+	}
+
+	/// Removes the resource with the given id from the table and closes it.
+	/// Returns `false` if no resource with that id is in the table.
+	pub fn close(&mut self, rid: ResourceId) -> bool {
+		match self.take_any(rid) {
+			Some(resource) => {
+				resource.close();
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Returns an iterator over the ids of the resources in the table,
+	/// together with their names.
+	pub fn names(&self) -> impl Iterator<Item = (ResourceId, &str)> + '_ {
+		self.index
+			.iter()
+			.map(|(rid, entry)| (*rid, entry.resource.name()))
+	}
+}
+
+impl fmt::Debug for ResourceTable {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_map().entries(self.names()).finish()
+	}
+}
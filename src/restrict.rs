@@ -0,0 +1,132 @@
+use crate::State;
+use crate::StateError;
+use crate::StateRead;
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+
+/// A list of types, written as a tuple such as `(A, B)`, that a
+/// [`Restricted`] view permits access to.
+pub trait TypeSet {
+	/// Determines if the type with the given `TypeId` is in the list.
+	fn contains(type_id: TypeId) -> bool;
+}
+
+impl TypeSet for () {
+	fn contains(_: TypeId) -> bool {
+		false
+	}
+}
+
+macro_rules! impl_type_set {
+	($($name:ident),+) => {
+		impl<$($name: 'static),+> TypeSet for ($($name,)+) {
+			fn contains(type_id: TypeId) -> bool {
+				$(type_id == TypeId::of::<$name>())||+
+			}
+		}
+	};
+}
+
+impl_type_set!(A);
+impl_type_set!(A, B);
+impl_type_set!(A, B, C);
+impl_type_set!(A, B, C, D);
+impl_type_set!(A, B, C, D, E);
+impl_type_set!(A, B, C, D, E, F);
+impl_type_set!(A, B, C, D, E, F, G);
+impl_type_set!(A, B, C, D, E, F, G, H);
+
+/// A read-only view of a `State` that only permits access to the types in
+/// `L`, as returned by [`State::restrict`].
+///
+/// Values of any other type are treated as absent, so a plugin handed a
+/// `Restricted` view cannot observe unrelated state.
+pub struct Restricted<'s, L> {
+	state: &'s State,
+	types: PhantomData<fn() -> L>,
+}
+
+impl State {
+	/// Returns a read-only view of the `State` that only permits access to
+	/// the types listed in `L`, such as `(Config, Database)`.
+	pub fn restrict<L: TypeSet>(&self) -> Restricted<'_, L> {
+		Restricted {
+			state: self,
+			types: PhantomData,
+		}
+	}
+}
+
+impl<'s, L: TypeSet> Restricted<'s, L> {
+	/// Determines if type `T` may be accessed through the view.
+	pub fn permits<T: 'static>(&self) -> bool {
+		L::contains(TypeId::of::<T>())
+	}
+
+	/// Determines if a value of type `T` exists in the `State` storage and
+	/// may be accessed through the view.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.permits::<T>() && self.state.has::<T>()
+	}
+
+	/// Tries to borrow a value from the `State` storage. Returns `None` if
+	/// type `T` may not be accessed through the view.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&'s T> {
+		match self.permits::<T>() {
+			true => self.state.try_borrow(),
+			false => None,
+		}
+	}
+
+	/// Borrows a value from the `State` storage, returning an error instead
+	/// of panicking if it is not present or may not be accessed through the
+	/// view.
+	pub fn borrow_checked<T: 'static>(&self) -> Result<&'s T, StateError> {
+		if !self.permits::<T>() {
+			return Err(StateError::restricted::<T>());
+		}
+		self.state.borrow_checked()
+	}
+
+	/// Borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`, or if type `T` may
+	/// not be accessed through the view.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: 'static>(&self) -> &'s T {
+		if !self.permits::<T>() {
+			panic!("{}", StateError::restricted::<T>());
+		}
+		self.state.borrow()
+	}
+}
+
+impl<L> Clone for Restricted<'_, L> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<L> Copy for Restricted<'_, L> {}
+
+impl<L> fmt::Debug for Restricted<'_, L> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Restricted")
+			.field("types", &core::any::type_name::<L>())
+			.finish_non_exhaustive()
+	}
+}
+
+impl<L: TypeSet> StateRead for Restricted<'_, L> {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		Restricted::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		Restricted::has::<T>(self)
+	}
+}
@@ -0,0 +1,138 @@
+use crate::missing;
+use crate::State;
+use crate::StateRead;
+
+/// The lifecycle phase a value in a [`ScopedState`] belongs to, from the
+/// longest-lived to the shortest-lived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+	/// Values that live as long as the application, such as configuration and
+	/// connection pools.
+	Application,
+	/// Values that live as long as a client connection.
+	Connection,
+	/// Values that only live for a single request.
+	Request,
+}
+
+impl Scope {
+	/// The scopes in lookup order, from the shortest-lived to the
+	/// longest-lived.
+	const LOOKUP: [Scope; 3] = [Scope::Request, Scope::Connection, Scope::Application];
+}
+
+/// A container holding a separate `State` for each [`Scope`], so values of
+/// different lifetimes can be threaded around as one container.
+///
+/// Lookups search the request scope first, then the connection scope, then
+/// the application scope, so a shorter-lived value shadows a longer-lived
+/// value of the same type.
+#[derive(Debug, Default)]
+pub struct ScopedState {
+	scopes: [State; 3],
+}
+
+impl ScopedState {
+	/// Creates a `ScopedState` with the given application scope and empty
+	/// connection and request scopes.
+	pub fn new(application: State) -> Self {
+		ScopedState {
+			scopes: [application, State::default(), State::default()],
+		}
+	}
+
+	/// Returns the `State` of `scope`.
+	pub fn scope(&self, scope: Scope) -> &State {
+		&self.scopes[scope as usize]
+	}
+
+	/// Returns the `State` of `scope` mutably, such as to put values into it.
+	pub fn scope_mut(&mut self, scope: Scope) -> &mut State {
+		&mut self.scopes[scope as usize]
+	}
+
+	/// Drops the values of `scope`, such as at the end of a request, and
+	/// returns them.
+	pub fn end_scope(&mut self, scope: Scope) -> State {
+		trace!(" ending {:?} scope of scoped state", scope);
+		core::mem::take(self.scope_mut(scope))
+	}
+
+	/// Returns the shortest-lived scope holding a value of type `T`.
+	pub fn scope_of<T: 'static>(&self) -> Option<Scope> {
+		Scope::LOOKUP
+			.into_iter()
+			.find(|scope| self.scope(*scope).has::<T>())
+	}
+
+	/// Determines if a value of type `T` exists in any scope.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.scope_of::<T>().is_some()
+	}
+
+	/// Tries to borrow the value of type `T` from the shortest-lived scope
+	/// holding one.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		Scope::LOOKUP
+			.into_iter()
+			.find_map(|scope| self.scope(scope).try_borrow())
+	}
+
+	/// Borrows the value of type `T` from the shortest-lived scope holding
+	/// one.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in any scope.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: 'static>(&self) -> &T {
+		match self.try_borrow() {
+			Some(value) => value,
+			None => self.missing::<T>(),
+		}
+	}
+
+	/// Tries to mutably borrow the value of type `T` from the shortest-lived
+	/// scope holding one.
+	pub fn try_borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		let scope = self.scope_of::<T>()?;
+		self.scope_mut(scope).try_borrow_mut()
+	}
+
+	/// Mutably borrows the value of type `T` from the shortest-lived scope
+	/// holding one.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in any scope, or if it has
+	/// been frozen.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow_mut<T: 'static>(&mut self) -> &mut T {
+		match self.scope_of::<T>() {
+			Some(scope) => self.scope_mut(scope).borrow_mut(),
+			None => self.missing::<T>(),
+		}
+	}
+
+	/// Panics because a value of type `T` is in no scope, reported by the
+	/// missing handler of the request scope.
+	#[track_caller]
+	fn missing<T: 'static>(&self) -> ! {
+		let names = Scope::LOOKUP
+			.into_iter()
+			.flat_map(|scope| self.scope(scope).type_names());
+		missing::<T>(Some(self.scope(Scope::Request)), names)
+	}
+}
+
+impl StateRead for ScopedState {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		ScopedState::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		ScopedState::has::<T>(self)
+	}
+}
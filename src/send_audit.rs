@@ -0,0 +1,147 @@
+//! Detection of whether the values in a `State` are `Send` and `Sync`, for
+//! users preparing to move them into a [`SyncState`](crate::SyncState).
+//!
+//! Auto traits cannot be detected inside a generic function such as
+//! [`State::put`], so values are only audited when they are put with
+//! [`put_audited!`](crate::put_audited), which inspects the concrete type at
+//! the call site. Values put by any other means are reported as unknown.
+
+use crate::slot::Slot;
+use crate::State;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+/// Whether the type of a value in a `State` is `Send` and `Sync`, as
+/// returned by [`State::send_audit`]. Both are `None` if the value was not
+/// put with [`put_audited!`](crate::put_audited).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendAudit {
+	/// The name of the type of the value.
+	pub name: &'static str,
+	/// Whether the type is `Send`.
+	pub send: Option<bool>,
+	/// Whether the type is `Sync`.
+	pub sync: Option<bool>,
+}
+
+/// Puts a value into a `State` like [`State::put`], and records whether its
+/// type is `Send` and `Sync` for [`State::send_audit`].
+///
+/// ```ignore
+/// put_audited!(state, Rc::new(Config::default()));
+/// state.assert_all_send(); // panics, naming `Rc<Config>`
+/// ```
+#[macro_export]
+macro_rules! put_audited {
+	($state:expr, $value:expr $(,)?) => {{
+		#[allow(unused_imports)]
+		use $crate::__send_audit::{IsSend, IsSync, NotSend, NotSync};
+		let value = $value;
+		let probe = $crate::__send_audit::probe(&value);
+		let send = (&probe).is_send();
+		let sync = (&probe).is_sync();
+		$crate::State::__put_audited(&mut $state, value, send, sync);
+	}};
+}
+
+/// The helpers of [`put_audited!`], which pick a method returning `true` if
+/// the probed type implements the auto trait and fall back to one returning
+/// `false` through auto-referencing otherwise.
+#[doc(hidden)]
+pub mod probe {
+	use super::PhantomData;
+
+	pub struct Probe<T>(PhantomData<T>);
+
+	pub fn probe<T>(_: &T) -> Probe<T> {
+		Probe(PhantomData)
+	}
+
+	pub trait IsSend {
+		fn is_send(&self) -> bool {
+			true
+		}
+	}
+
+	impl<T: Send> IsSend for Probe<T> {}
+
+	pub trait NotSend {
+		fn is_send(&self) -> bool {
+			false
+		}
+	}
+
+	impl<T> NotSend for &Probe<T> {}
+
+	pub trait IsSync {
+		fn is_sync(&self) -> bool {
+			true
+		}
+	}
+
+	impl<T: Sync> IsSync for Probe<T> {}
+
+	pub trait NotSync {
+		fn is_sync(&self) -> bool {
+			false
+		}
+	}
+
+	impl<T> NotSync for &Probe<T> {}
+}
+
+impl State {
+	#[doc(hidden)]
+	pub fn __put_audited<T: 'static>(&mut self, t: T, send: bool, sync: bool) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting audited record to state for type_id `{:?}`",
+			type_id
+		);
+		let mut slot = Slot::new(t);
+		slot.send_sync = Some((send, sync));
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Reports for every value in the `State` storage whether its type is
+	/// `Send` and `Sync`, as recorded by [`put_audited!`](crate::put_audited).
+	pub fn send_audit(&self) -> Vec<SendAudit> {
+		self.data
+			.values()
+			.map(|slot| SendAudit {
+				name: slot.name,
+				send: slot.send_sync.map(|(send, _)| send),
+				sync: slot.send_sync.map(|(_, sync)| sync),
+			})
+			.collect()
+	}
+
+	/// Checks that every value in the `State` storage is known to be `Send`.
+	///
+	/// # Panics
+	///
+	/// If a value is not `Send`, or was not put with
+	/// [`put_audited!`](crate::put_audited). The message names the types of
+	/// the offending values.
+	#[track_caller]
+	pub fn assert_all_send(&self) {
+		let offending: Vec<_> = self
+			.send_audit()
+			.into_iter()
+			.filter(|audit| audit.send != Some(true))
+			.map(|audit| match audit.send {
+				Some(_) => String::from(audit.name),
+				None => format!("{} (unaudited)", audit.name),
+			})
+			.collect();
+		if !offending.is_empty() {
+			panic!(
+				"types {} are not Send in State container",
+				offending.join(", ")
+			);
+		}
+	}
+}
@@ -0,0 +1,129 @@
+use crate::fail;
+use crate::map::Map;
+use crate::missing;
+use crate::StateError;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::Ref;
+use core::cell::RefCell;
+use core::cell::RefMut;
+
+/// A state container whose slots are individually guarded by a `RefCell`,
+/// so entries can be mutated through a shared reference to the container.
+#[derive(Default, Debug)]
+pub struct SharedState {
+	data: Map<(&'static str, RefCell<Box<dyn Any>>)>,
+}
+
+impl SharedState {
+	/// Puts a value into the `SharedState` storage. One value of each type is
+	/// retained. Successive calls to `put` will overwrite the existing value
+	/// of the same type.
+	pub fn put<T: 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting record to shared state for type_id `{:?}`",
+			type_id
+		);
+		self.data
+			.insert(type_id, (type_name::<T>(), RefCell::new(Box::new(t))));
+	}
+
+	/// Determines if the current value exists in `SharedState` storage.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.data.contains_key(&TypeId::of::<T>())
+	}
+
+	/// Tries to borrow a value from the `SharedState` storage. Fails if the
+	/// value is missing or currently mutably borrowed.
+	pub fn try_borrow<T: 'static>(&self) -> Result<Ref<'_, T>, StateError> {
+		let type_id = TypeId::of::<T>();
+		trace!(" borrowing shared state data for type_id `{:?}`", type_id);
+		let (_, cell) = self
+			.data
+			.get(&type_id)
+			.ok_or_else(StateError::missing::<T>)?;
+		let guard = cell.try_borrow().map_err(|_| StateError::conflict::<T>())?;
+		Ref::filter_map(guard, |b| b.downcast_ref()).map_err(|_| StateError::missing::<T>())
+	}
+
+	/// Borrows a value from the `SharedState` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present or is currently mutably
+	/// borrowed.
+	#[track_caller]
+	pub fn borrow<T: 'static>(&self) -> Ref<'_, T> {
+		match self.try_borrow() {
+			Ok(value) => value,
+			Err(e) => fail(e, None, self.type_names()),
+		}
+	}
+
+	/// Tries to mutably borrow a value from the `SharedState` storage. Fails
+	/// if the value is missing or currently borrowed.
+	pub fn try_borrow_mut<T: 'static>(&self) -> Result<RefMut<'_, T>, StateError> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" mutably borrowing shared state data for type_id `{:?}`",
+			type_id
+		);
+		let (_, cell) = self
+			.data
+			.get(&type_id)
+			.ok_or_else(StateError::missing::<T>)?;
+		let guard = cell
+			.try_borrow_mut()
+			.map_err(|_| StateError::conflict::<T>())?;
+		RefMut::filter_map(guard, |b| b.downcast_mut()).map_err(|_| StateError::missing::<T>())
+	}
+
+	/// Mutably borrows a value from the `SharedState` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present or is currently borrowed.
+	#[track_caller]
+	pub fn borrow_mut<T: 'static>(&self) -> RefMut<'_, T> {
+		match self.try_borrow_mut() {
+			Ok(value) => value,
+			Err(e) => fail(e, None, self.type_names()),
+		}
+	}
+
+	/// Tries to move a value out of the `SharedState` storage and return
+	/// ownership.
+	pub fn try_take<T: 'static>(&mut self) -> Option<T> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" taking ownership from shared state data for type_id `{:?}`",
+			type_id
+		);
+		self.data
+			.remove(&type_id)
+			.and_then(|(_, cell)| cell.into_inner().downcast().ok())
+			.map(|b| *b)
+	}
+
+	/// Moves a value out of the `SharedState` storage and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `SharedState`.
+	#[track_caller]
+	pub fn take<T: 'static>(&mut self) -> T {
+		if !self.has::<T>() {
+			missing::<T>(None, self.type_names())
+		}
+		self.try_take().expect("value was checked above")
+	}
+
+	/// The names of the types stored in the `SharedState`.
+	fn type_names(&self) -> Vec<&'static str> {
+		self.data.values().map(|(name, _)| *name).collect()
+	}
+}
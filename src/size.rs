@@ -0,0 +1,122 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::mem::size_of;
+
+/// Types that can report the approximate amount of heap memory they own,
+/// for the size accounting of [`State::put_sized`].
+pub trait MemSize {
+	/// Returns the approximate number of bytes of heap memory owned by the
+	/// value, not counting the value itself.
+	fn heap_size(&self) -> usize;
+}
+
+/// The approximate memory usage of the values in a `State`, as returned by
+/// [`State::memory_usage`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+	/// The total number of bytes used by the measured values.
+	pub total: usize,
+	/// The names of the types of the measured values, with the number of
+	/// bytes used by each.
+	pub entries: Vec<(&'static str, usize)>,
+	/// The number of values that were not measured, because they were not
+	/// put with [`put_sized`](State::put_sized) or have not been constructed.
+	pub unmeasured: usize,
+}
+
+impl State {
+	/// Puts a value into the `State` storage like [`put`](State::put), and
+	/// records how to measure its memory usage for
+	/// [`memory_usage`](State::memory_usage).
+	pub fn put_sized<T: MemSize + 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting sized record to state for type_id `{:?}`",
+			type_id
+		);
+		self.insert_slot(type_id, Slot::sized(t));
+	}
+
+	/// Returns the approximate number of bytes used by the value of type `T`,
+	/// including the heap memory it owns, if it was put with
+	/// [`put_sized`](State::put_sized).
+	pub fn mem_size<T: 'static>(&self) -> Option<usize> {
+		self.data.get(&TypeId::of::<T>())?.mem_size()
+	}
+
+	/// Measures the approximate memory usage of every value that was put
+	/// with [`put_sized`](State::put_sized), such as to enforce a memory
+	/// budget for cached data.
+	pub fn memory_usage(&self) -> MemoryUsage {
+		let mut usage = MemoryUsage::default();
+		for slot in self.data.values() {
+			match slot.mem_size() {
+				Some(size) => {
+					usage.total += size;
+					usage.entries.push((slot.name, size));
+				},
+				None => usage.unmeasured += 1,
+			}
+		}
+		usage
+	}
+}
+
+macro_rules! impl_mem_size_without_heap {
+	($($ty:ty),+) => {
+		$(impl MemSize for $ty {
+			fn heap_size(&self) -> usize {
+				0
+			}
+		})+
+	};
+}
+
+impl_mem_size_without_heap!(
+	(),
+	bool,
+	char,
+	u8,
+	u16,
+	u32,
+	u64,
+	u128,
+	usize,
+	i8,
+	i16,
+	i32,
+	i64,
+	i128,
+	isize,
+	f32,
+	f64,
+	&'static str
+);
+
+impl MemSize for String {
+	fn heap_size(&self) -> usize {
+		self.capacity()
+	}
+}
+
+impl<T: MemSize> MemSize for Vec<T> {
+	fn heap_size(&self) -> usize {
+		self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+	}
+}
+
+impl<T: MemSize> MemSize for Box<T> {
+	fn heap_size(&self) -> usize {
+		size_of::<T>() + (**self).heap_size()
+	}
+}
+
+impl<T: MemSize> MemSize for Option<T> {
+	fn heap_size(&self) -> usize {
+		self.as_ref().map_or(0, T::heap_size)
+	}
+}
@@ -0,0 +1,613 @@
+//! The storage of `State`, which keeps its slots at stable indices so they
+//! can be addressed by [`Key`](crate::Key) without a map lookup.
+//!
+//! A map from `TypeId` to index locates the slots, and indices freed by
+//! removals are reused by later insertions. Every index has a generation
+//! which is bumped when its entry is removed, so that handles to the removed
+//! entry can tell it apart from later entries at the same index. The free
+//! indices are linked through their vacant buckets. With the `small`
+//! feature, the first few buckets are kept inline, like the entries of the
+//! map.
+//!
+//! Every entry also records when it was inserted, and its value carries a
+//! priority. Entries are iterated by ascending priority and then in
+//! insertion order, both by reference and by value, and are dropped in the
+//! reverse of that order, so values that were put later, and may depend on
+//! the values put before them, are dropped first. The occupied entries are
+//! linked in that order through their buckets, so it is followed without
+//! sorting or allocating.
+
+use crate::map;
+use crate::map::Map;
+#[cfg(feature = "small")]
+use crate::small_map::SmallVec;
+#[cfg(not(feature = "small"))]
+use alloc::vec::Vec;
+use core::any::TypeId;
+#[cfg(feature = "bench-internals")]
+use core::cell::Cell;
+use core::fmt;
+use core::mem;
+
+#[cfg(not(feature = "small"))]
+type Buckets<V> = Vec<Bucket<V>>;
+
+#[cfg(feature = "small")]
+type Buckets<V> = SmallVec<Bucket<V>>;
+
+pub(crate) struct Slab<V> {
+	index: Map<usize>,
+	entries: Buckets<V>,
+	/// The first and last occupied entries in iteration order.
+	head: Option<usize>,
+	tail: Option<usize>,
+	/// The first vacant bucket awaiting reuse.
+	free: Option<usize>,
+	next_seq: u64,
+	#[cfg(feature = "bench-internals")]
+	counters: Counters,
+}
+
+/// Counters of the operations performed on the storage of a `State`, as
+/// returned by [`State::internal_counters`](crate::State::internal_counters)
+/// with the `bench-internals` feature.
+#[cfg(feature = "bench-internals")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InternalCounters {
+	/// The number of lookups of a `TypeId` in the index map.
+	pub lookups: u64,
+	/// The number of entries inserted, not counting overwrites.
+	pub inserts: u64,
+	/// The number of insertions that reused the index of a removed entry.
+	pub reused: u64,
+	/// The number of entries removed.
+	pub removals: u64,
+	/// The number of indices allocated, occupied or free.
+	pub indices: usize,
+	/// The number of free indices awaiting reuse.
+	pub free: usize,
+}
+
+#[cfg(feature = "bench-internals")]
+#[derive(Default)]
+struct Counters {
+	lookups: Cell<u64>,
+	inserts: Cell<u64>,
+	reused: Cell<u64>,
+	removals: Cell<u64>,
+}
+
+/// Increments one of the counters of a slab with the `bench-internals`
+/// feature.
+macro_rules! count {
+	($slab:expr, $counter:ident) => {
+		#[cfg(feature = "bench-internals")]
+		$slab
+			.counters
+			.$counter
+			.set($slab.counters.$counter.get() + 1);
+	};
+}
+
+/// A value stored in a slab, which carries the priority ordering it among
+/// the other entries.
+pub(crate) trait Prioritized {
+	fn priority(&self) -> i32;
+}
+
+struct Bucket<V> {
+	generation: u32,
+	/// When the entry was inserted or last overwritten, relative to the
+	/// other entries.
+	seq: u64,
+	/// The priority of the value as of its last insertion or
+	/// [`reorder`](Slab::reorder).
+	priority: i32,
+	/// The neighbouring occupied entries in iteration order. While the
+	/// bucket is vacant, `next` is the next vacant bucket instead.
+	prev: Option<usize>,
+	next: Option<usize>,
+	entry: Option<(TypeId, V)>,
+}
+
+impl<V> Slab<V> {
+	pub(crate) fn with_capacity(capacity: usize) -> Self {
+		Slab {
+			index: map::with_capacity(capacity),
+			entries: Buckets::with_capacity(capacity),
+			head: None,
+			tail: None,
+			free: None,
+			next_seq: 0,
+			#[cfg(feature = "bench-internals")]
+			counters: Counters::default(),
+		}
+	}
+
+	/// Returns the operation counters of the slab.
+	#[cfg(feature = "bench-internals")]
+	pub(crate) fn counters(&self) -> InternalCounters {
+		InternalCounters {
+			lookups: self.counters.lookups.get(),
+			inserts: self.counters.inserts.get(),
+			reused: self.counters.reused.get(),
+			removals: self.counters.removals.get(),
+			indices: self.entries.len(),
+			free: self.vacant(),
+		}
+	}
+
+	pub(crate) fn reserve(&mut self, additional: usize) {
+		map::reserve(&mut self.index, additional);
+		self.entries
+			.reserve(additional.saturating_sub(self.vacant()));
+	}
+
+	/// Returns the number of vacant buckets.
+	fn vacant(&self) -> usize {
+		self.entries.len() - self.index.len()
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		self.index.len()
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.index.is_empty()
+	}
+
+	/// Returns the index of the entry for `key`, together with the current
+	/// generation of that index.
+	pub(crate) fn handle_of(&self, key: &TypeId) -> Option<(usize, u32)> {
+		count!(self, lookups);
+		let index = *self.index.get(key)?;
+		Some((index, self.entries[index].generation))
+	}
+
+	/// Returns the entry at `index` if it is occupied and has not been
+	/// removed since `generation`.
+	pub(crate) fn get_at(&self, index: usize, generation: u32) -> Option<(&TypeId, &V)> {
+		let bucket = self.entries.get(index)?;
+		if bucket.generation != generation {
+			return None;
+		}
+		let (k, v) = bucket.entry.as_ref()?;
+		Some((k, v))
+	}
+
+	/// Returns the entry at `index` mutably if it is occupied and has not
+	/// been removed since `generation`.
+	pub(crate) fn get_at_mut(
+		&mut self,
+		index: usize,
+		generation: u32,
+	) -> Option<(&TypeId, &mut V)> {
+		let bucket = self.entries.get_mut(index)?;
+		if bucket.generation != generation {
+			return None;
+		}
+		let (k, v) = bucket.entry.as_mut()?;
+		Some((&*k, v))
+	}
+
+	pub(crate) fn get(&self, key: &TypeId) -> Option<&V> {
+		count!(self, lookups);
+		let index = *self.index.get(key)?;
+		self.entries[index].entry.as_ref().map(|(_, v)| v)
+	}
+
+	pub(crate) fn get_mut(&mut self, key: &TypeId) -> Option<&mut V> {
+		count!(self, lookups);
+		let index = *self.index.get(key)?;
+		self.entries[index].entry.as_mut().map(|(_, v)| v)
+	}
+
+	pub(crate) fn contains_key(&self, key: &TypeId) -> bool {
+		count!(self, lookups);
+		self.index.contains_key(key)
+	}
+
+	pub(crate) fn remove(&mut self, key: &TypeId) -> Option<V> {
+		let index = self.index.remove(key)?;
+		self.vacate(index)
+	}
+
+	pub(crate) fn retain(&mut self, mut f: impl FnMut(&TypeId, &mut V) -> bool) {
+		for index in 0..self.entries.len() {
+			let Some((k, v)) = &mut self.entries[index].entry else {
+				continue;
+			};
+			if !f(k, v) {
+				let key = *k;
+				self.index.remove(&key);
+				self.vacate(index);
+			}
+		}
+	}
+
+	/// Removes every entry, dropping them in reverse iteration order.
+	pub(crate) fn clear(&mut self) {
+		self.index.clear();
+		while let Some(index) = self.tail {
+			self.vacate(index);
+		}
+	}
+
+	/// Links the entry at `index` into the iteration order, after the last
+	/// entry that orders before it. Entries are mostly put in order, so the
+	/// search starts from the end.
+	fn link(&mut self, index: usize) {
+		let key = self.entries[index].order_key();
+		let mut prev = self.tail;
+		while let Some(candidate) = prev {
+			if self.entries[candidate].order_key() < key {
+				break;
+			}
+			prev = self.entries[candidate].prev;
+		}
+		let next = match prev {
+			Some(prev) => self.entries[prev].next.replace(index),
+			None => self.head.replace(index),
+		};
+		match next {
+			Some(next) => self.entries[next].prev = Some(index),
+			None => self.tail = Some(index),
+		}
+		let bucket = &mut self.entries[index];
+		bucket.prev = prev;
+		bucket.next = next;
+	}
+
+	/// Removes the entry at `index` from the iteration order.
+	fn unlink(&mut self, index: usize) {
+		let bucket = &mut self.entries[index];
+		let (prev, next) = (bucket.prev.take(), bucket.next.take());
+		match prev {
+			Some(prev) => self.entries[prev].next = next,
+			None => self.head = next,
+		}
+		match next {
+			Some(next) => self.entries[next].prev = prev,
+			None => self.tail = prev,
+		}
+	}
+
+	/// Empties the entry at `index` and marks the index for reuse under a
+	/// new generation.
+	fn vacate(&mut self, index: usize) -> Option<V> {
+		self.entries[index].entry.as_ref()?;
+		self.unlink(index);
+		let bucket = &mut self.entries[index];
+		let (_, value) = bucket.entry.take()?;
+		count!(self, removals);
+		bucket.generation = bucket.generation.wrapping_add(1);
+		bucket.next = self.free.replace(index);
+		Some(value)
+	}
+
+	pub(crate) fn occupied(&mut self, key: TypeId) -> Option<OccupiedEntry<'_, V>> {
+		self.contains_key(&key)
+			.then_some(OccupiedEntry { slab: self, key })
+	}
+
+	/// Returns an iterator over the entries by ascending priority and then
+	/// in insertion order.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = (&TypeId, &V)> + '_ {
+		core::iter::successors(self.head, |index| self.entries[*index].next)
+			.filter_map(|index| self.entries[index].entry.as_ref())
+			.map(|(k, v)| (k, v))
+	}
+
+	/// Returns an iterator over the entries mutably, in no particular order.
+	pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&TypeId, &mut V)> + '_ {
+		self.entries
+			.iter_mut()
+			.filter_map(|bucket| bucket.entry.as_mut())
+			.map(|(k, v)| (&*k, v))
+	}
+
+	pub(crate) fn keys(&self) -> impl Iterator<Item = &TypeId> + '_ {
+		self.iter().map(|(k, _)| k)
+	}
+
+	pub(crate) fn values(&self) -> impl Iterator<Item = &V> + '_ {
+		self.iter().map(|(_, v)| v)
+	}
+}
+
+impl<V: Prioritized> Slab<V> {
+	pub(crate) fn insert(&mut self, key: TypeId, value: V) -> Option<V> {
+		let seq = self.next_seq;
+		self.next_seq += 1;
+		if let Some(&index) = self.index.get(&key) {
+			count!(self, lookups);
+			self.unlink(index);
+			let bucket = &mut self.entries[index];
+			bucket.seq = seq;
+			bucket.priority = value.priority();
+			let (_, existing) = bucket.entry.as_mut().expect("indexed entry is present");
+			let existing = mem::replace(existing, value);
+			self.link(index);
+			return Some(existing);
+		}
+		count!(self, inserts);
+		let index = match self.free {
+			Some(index) => {
+				count!(self, reused);
+				let bucket = &mut self.entries[index];
+				self.free = bucket.next.take();
+				bucket.seq = seq;
+				bucket.priority = value.priority();
+				bucket.entry = Some((key, value));
+				index
+			},
+			None => {
+				self.entries.push(Bucket {
+					generation: 0,
+					seq,
+					priority: value.priority(),
+					prev: None,
+					next: None,
+					entry: Some((key, value)),
+				});
+				self.entries.len() - 1
+			},
+		};
+		self.index.insert(key, index);
+		self.link(index);
+		None
+	}
+
+	/// Moves the entry for `key` to the position given by the current
+	/// priority of its value.
+	pub(crate) fn reorder(&mut self, key: &TypeId) {
+		let Some(&index) = self.index.get(key) else {
+			return;
+		};
+		let bucket = &mut self.entries[index];
+		if let Some((_, value)) = &bucket.entry {
+			bucket.priority = value.priority();
+			self.unlink(index);
+			self.link(index);
+		}
+	}
+}
+
+impl<V> Default for Slab<V> {
+	fn default() -> Self {
+		Slab {
+			index: Map::default(),
+			entries: Buckets::default(),
+			head: None,
+			tail: None,
+			free: None,
+			next_seq: 0,
+			#[cfg(feature = "bench-internals")]
+			counters: Counters::default(),
+		}
+	}
+}
+
+impl<V> Bucket<V> {
+	fn order_key(&self) -> (i32, u64) {
+		(self.priority, self.seq)
+	}
+}
+
+impl<V> Drop for Slab<V> {
+	fn drop(&mut self) {
+		self.clear();
+	}
+}
+
+impl<V: fmt::Debug> fmt::Debug for Slab<V> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_map().entries(self.iter()).finish()
+	}
+}
+
+impl<V: Prioritized> FromIterator<(TypeId, V)> for Slab<V> {
+	fn from_iter<I: IntoIterator<Item = (TypeId, V)>>(iter: I) -> Self {
+		let mut slab = Slab::default();
+		for (key, value) in iter {
+			slab.insert(key, value);
+		}
+		slab
+	}
+}
+
+impl<V> IntoIterator for Slab<V> {
+	type Item = (TypeId, V);
+	type IntoIter = IntoIter<V>;
+
+	fn into_iter(mut self) -> IntoIter<V> {
+		self.index.clear();
+		self.tail = None;
+		IntoIter {
+			entries: mem::take(&mut self.entries),
+			next: self.head.take(),
+		}
+	}
+}
+
+/// An iterator moving the entries out of a slab, in the same order as
+/// [`Slab::iter`].
+pub(crate) struct IntoIter<V> {
+	entries: Buckets<V>,
+	next: Option<usize>,
+}
+
+impl<V> Iterator for IntoIter<V> {
+	type Item = (TypeId, V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let bucket = &mut self.entries[self.next?];
+		self.next = bucket.next;
+		bucket.entry.take()
+	}
+}
+
+pub(crate) struct OccupiedEntry<'a, V> {
+	slab: &'a mut Slab<V>,
+	key: TypeId,
+}
+
+impl<'a, V> OccupiedEntry<'a, V> {
+	pub(crate) fn key(&self) -> &TypeId {
+		&self.key
+	}
+
+	pub(crate) fn get(&self) -> &V {
+		self.slab.get(&self.key).expect("occupied entry is present")
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut V {
+		self.slab
+			.get_mut(&self.key)
+			.expect("occupied entry is present")
+	}
+
+	pub(crate) fn into_mut(self) -> &'a mut V {
+		self.slab
+			.get_mut(&self.key)
+			.expect("occupied entry is present")
+	}
+
+	pub(crate) fn remove(self) -> V {
+		self.slab
+			.remove(&self.key)
+			.expect("occupied entry is present")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::rc::Rc;
+	use alloc::vec::Vec;
+	use core::cell::RefCell;
+
+	/// A value with a priority that records its id when dropped.
+	struct Tracked(i32, u32, Rc<RefCell<Vec<u32>>>);
+
+	impl Prioritized for Tracked {
+		fn priority(&self) -> i32 {
+			self.0
+		}
+	}
+
+	impl Drop for Tracked {
+		fn drop(&mut self) {
+			self.2.borrow_mut().push(self.1);
+		}
+	}
+
+	fn key<T: 'static>() -> TypeId {
+		TypeId::of::<T>()
+	}
+
+	fn slab(priorities: &[i32], drops: &Rc<RefCell<Vec<u32>>>) -> Slab<Tracked> {
+		let keys = [key::<u8>(), key::<u16>(), key::<u32>(), key::<u64>()];
+		let mut slab = Slab::default();
+		for (id, priority) in priorities.iter().enumerate() {
+			let id = id as u32;
+			slab.insert(keys[id as usize], Tracked(*priority, id, drops.clone()));
+		}
+		slab
+	}
+
+	fn ids(slab: &Slab<Tracked>) -> Vec<u32> {
+		slab.values().map(|entry| entry.1).collect()
+	}
+
+	#[test]
+	fn insert_get_and_overwrite() {
+		let drops = Rc::default();
+		let mut slab = slab(&[0, 0], &drops);
+		assert_eq!(slab.len(), 2);
+		assert_eq!(slab.get(&key::<u8>()).unwrap().1, 0);
+		assert!(slab.get(&key::<u32>()).is_none());
+		let handle = slab.handle_of(&key::<u8>()).unwrap();
+		let previous = slab.insert(key::<u8>(), Tracked(0, 9, drops.clone()));
+		assert_eq!(previous.map(|entry| entry.1), Some(0));
+		assert_eq!(slab.handle_of(&key::<u8>()), Some(handle));
+		assert_eq!(ids(&slab), [1, 9]);
+		assert_eq!(slab.len(), 2);
+	}
+
+	#[test]
+	fn removal_invalidates_handles_and_reuses_the_index() {
+		let drops = Rc::default();
+		let mut slab = slab(&[0, 0], &drops);
+		let (index, generation) = slab.handle_of(&key::<u8>()).unwrap();
+		assert_eq!(slab.get_at(index, generation).unwrap().0, &key::<u8>());
+		assert_eq!(slab.remove(&key::<u8>()).map(|entry| entry.1), Some(0));
+		assert!(slab.get_at(index, generation).is_none());
+		assert!(slab.get_at_mut(index, generation).is_none());
+		assert!(slab.remove(&key::<u8>()).is_none());
+
+		slab.insert(key::<u32>(), Tracked(0, 2, drops.clone()));
+		let reused = slab.handle_of(&key::<u32>()).unwrap();
+		assert_eq!(reused, (index, generation + 1));
+		assert!(slab.get_at(index, generation).is_none());
+		assert_eq!(slab.get_at(reused.0, reused.1).unwrap().1 .1, 2);
+		assert_eq!(ids(&slab), [1, 2]);
+	}
+
+	#[test]
+	fn iterates_by_priority_then_insertion() {
+		let drops = Rc::default();
+		let mut slab = slab(&[1, 0, -1, 0], &drops);
+		assert_eq!(ids(&slab), [2, 1, 3, 0]);
+		let mut ids_mut: Vec<_> = slab.iter_mut().map(|(_, entry)| entry.1).collect();
+		ids_mut.sort();
+		assert_eq!(ids_mut, [0, 1, 2, 3]);
+
+		slab.get_mut(&key::<u8>()).unwrap().0 = -2;
+		slab.reorder(&key::<u8>());
+		assert_eq!(ids(&slab), [0, 2, 1, 3]);
+		slab.get_mut(&key::<u16>()).unwrap().0 = 5;
+		slab.reorder(&key::<u16>());
+		slab.remove(&key::<u32>());
+		assert_eq!(ids(&slab), [0, 3, 1]);
+		slab.insert(key::<u32>(), Tracked(0, 2, drops.clone()));
+		assert_eq!(ids(&slab), [0, 3, 2, 1]);
+
+		let moved: Vec<_> = slab.into_iter().map(|(_, entry)| entry.1).collect();
+		assert_eq!(moved, [0, 3, 2, 1]);
+	}
+
+	#[test]
+	fn retain_removes_rejected_entries() {
+		let drops = Rc::default();
+		let mut slab = slab(&[0, 0, 0], &drops);
+		slab.retain(|_, entry| entry.1 != 1);
+		assert_eq!(ids(&slab), [0, 2]);
+		assert!(!slab.contains_key(&key::<u16>()));
+		assert_eq!(*drops.borrow(), [1]);
+	}
+
+	#[test]
+	fn entries_are_dropped_in_reverse_order() {
+		let drops = Rc::default();
+		let mut slab = slab(&[0, 1, 0], &drops);
+		slab.clear();
+		assert!(slab.is_empty());
+		assert_eq!(*drops.borrow(), [1, 2, 0]);
+
+		let drops = Rc::default();
+		drop(self::slab(&[0, 0, -1, 0], &drops));
+		assert_eq!(*drops.borrow(), [3, 1, 0, 2]);
+	}
+
+	#[test]
+	fn entry_api() {
+		let drops: Rc<RefCell<Vec<u32>>> = Rc::default();
+		let mut slab = Slab::default();
+		assert!(slab.occupied(key::<u8>()).is_none());
+		slab.insert(key::<u8>(), Tracked(0, 0, drops.clone()));
+		slab.occupied(key::<u8>()).unwrap().get_mut().1 += 10;
+		assert_eq!(slab.occupied(key::<u8>()).unwrap().remove().1, 10);
+		assert!(slab.is_empty());
+	}
+}
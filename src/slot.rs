@@ -0,0 +1,347 @@
+use crate::evict::Usage;
+#[cfg(feature = "async")]
+use crate::lazy_async::AsyncInit;
+use crate::slab::Prioritized;
+#[cfg(feature = "stats")]
+use crate::stats::Stats;
+use crate::MemSize;
+use crate::State;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::OnceCell;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "debug-endpoint")]
+use std::time::SystemTime;
+
+type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+type SizeFn = fn(&dyn Any) -> usize;
+pub(crate) type Teardown = Box<dyn FnOnce(Box<dyn Any>)>;
+pub(crate) type Factory = Rc<dyn Fn(&State) -> Box<dyn Any>>;
+
+/// How the value of a slot is constructed on first access.
+pub(crate) enum Init {
+	/// Built synchronously by calling the factory, which is called again for
+	/// each `State` spawned from a prototype.
+	Factory(Factory),
+	/// Built synchronously by calling a factory that can only be called once.
+	Once(Factory),
+	/// Built by awaiting a future, see [`State::get_async`].
+	#[cfg(feature = "async")]
+	Async(AsyncInit),
+}
+
+/// A stored value together with the metadata recorded for it at insertion
+/// time.
+///
+/// The value may not have been constructed yet, in which case it is built
+/// according to the slot's `Init` on first access.
+pub(crate) struct Slot {
+	value: OnceCell<Box<dyn Any>>,
+	init: Option<Init>,
+	pub(crate) name: &'static str,
+	clone: Option<CloneFn>,
+	eq: Option<EqFn>,
+	size: Option<SizeFn>,
+	pub(crate) teardown: Option<Teardown>,
+	/// The types that the factory of the value reads, as declared with
+	/// [`State::register_factory_with_deps`].
+	pub(crate) deps: Vec<(TypeId, &'static str)>,
+	#[cfg(feature = "std")]
+	pub(crate) expires: Option<Instant>,
+	pub(crate) frozen: bool,
+	/// The priority given with [`State::put_with_priority`], or `None` to
+	/// keep the priority of the value it overwrites.
+	pub(crate) priority: Option<i32>,
+	/// The access history used to pick values to evict, for values put with
+	/// [`State::put_evictable`].
+	pub(crate) usage: Option<Usage>,
+	/// Whether the type of the value is `Send` and `Sync`, for values put
+	/// with [`put_audited!`](crate::put_audited).
+	#[cfg(feature = "send-audit")]
+	pub(crate) send_sync: Option<(bool, bool)>,
+	#[cfg(feature = "stats")]
+	pub(crate) stats: Stats,
+	#[cfg(feature = "debug-endpoint")]
+	pub(crate) inserted: SystemTime,
+}
+
+impl Slot {
+	pub(crate) fn new<T: 'static>(t: T) -> Self {
+		Slot::named(Box::new(t), type_name::<T>())
+	}
+
+	pub(crate) fn named(value: Box<dyn Any>, name: &'static str) -> Self {
+		Slot {
+			value: OnceCell::from(value),
+			init: None,
+			name,
+			clone: None,
+			eq: None,
+			size: None,
+			teardown: None,
+			deps: Vec::new(),
+			#[cfg(feature = "std")]
+			expires: None,
+			frozen: false,
+			priority: None,
+			usage: None,
+			#[cfg(feature = "send-audit")]
+			send_sync: None,
+			#[cfg(feature = "stats")]
+			stats: Stats::new(),
+			#[cfg(feature = "debug-endpoint")]
+			inserted: SystemTime::now(),
+		}
+	}
+
+	/// Creates a slot whose value can be duplicated with
+	/// [`try_clone`](Slot::try_clone).
+	pub(crate) fn cloneable<T: Clone + 'static>(t: T) -> Self {
+		Slot {
+			clone: Some(clone_value::<T>),
+			..Slot::new(t)
+		}
+	}
+
+	/// Creates a slot whose value can be compared with
+	/// [`eq_value`](Slot::eq_value).
+	pub(crate) fn comparable<T: PartialEq + 'static>(t: T) -> Self {
+		Slot {
+			eq: Some(eq_value::<T>),
+			..Slot::new(t)
+		}
+	}
+
+	/// Creates a slot whose memory usage can be measured with
+	/// [`mem_size`](Slot::mem_size).
+	pub(crate) fn sized<T: MemSize + 'static>(t: T) -> Self {
+		Slot {
+			size: Some(size_value::<T>),
+			..Slot::new(t)
+		}
+	}
+
+	/// Creates a slot whose value is handed to `teardown` when the `State` is
+	/// shut down.
+	pub(crate) fn with_teardown<T: 'static>(t: T, teardown: impl FnOnce(T) + 'static) -> Self {
+		Slot {
+			teardown: Some(Box::new(move |value| {
+				teardown(*value.downcast().expect("slot holds its type"))
+			})),
+			..Slot::new(t)
+		}
+	}
+
+	/// Creates a slot whose value is built according to `init` on first
+	/// access.
+	pub(crate) fn lazy<T: 'static>(init: Init) -> Self {
+		Slot {
+			value: OnceCell::new(),
+			init: Some(init),
+			name: type_name::<T>(),
+			clone: None,
+			eq: None,
+			size: None,
+			teardown: None,
+			deps: Vec::new(),
+			#[cfg(feature = "std")]
+			expires: None,
+			frozen: false,
+			priority: None,
+			usage: None,
+			#[cfg(feature = "send-audit")]
+			send_sync: None,
+			#[cfg(feature = "stats")]
+			stats: Stats::new(),
+			#[cfg(feature = "debug-endpoint")]
+			inserted: SystemTime::now(),
+		}
+	}
+
+	/// Returns the value, constructing it first if it has a factory. Values
+	/// that are constructed asynchronously are only returned once their
+	/// future has completed.
+	pub(crate) fn get(&self, state: &State) -> Option<&dyn Any> {
+		if let Some(value) = self.value.get() {
+			return Some(&**value);
+		}
+		match self.init.as_ref()? {
+			Init::Factory(factory) | Init::Once(factory) => {
+				Some(&**self.value.get_or_init(|| factory(state)))
+			},
+			#[cfg(feature = "async")]
+			Init::Async(_) => None,
+		}
+	}
+
+	/// Polls the construction of the value, driving its future if it is
+	/// constructed asynchronously.
+	#[cfg(feature = "async")]
+	pub(crate) fn poll_get(
+		&self,
+		state: &State,
+		cx: &mut core::task::Context<'_>,
+	) -> core::task::Poll<Option<&dyn Any>> {
+		if let (None, Some(Init::Async(init))) = (self.value.get(), &self.init) {
+			core::task::ready!(init.poll(&self.value, cx));
+		}
+		core::task::Poll::Ready(self.get(state))
+	}
+
+	/// Determines if the value still has to be built by a synchronous
+	/// factory.
+	pub(crate) fn is_pending(&self) -> bool {
+		self.value.get().is_none() && matches!(self.init, Some(Init::Factory(_) | Init::Once(_)))
+	}
+
+	/// Returns the value if it has been constructed.
+	pub(crate) fn try_get(&self) -> Option<&dyn Any> {
+		self.value.get().map(|b| &**b)
+	}
+
+	/// Returns the value mutably if it has been constructed.
+	pub(crate) fn get_mut(&mut self) -> Option<&mut dyn Any> {
+		self.value.get_mut().map(|b| &mut **b)
+	}
+
+	/// Moves the value out of the slot if it has been constructed.
+	pub(crate) fn into_value(self) -> Option<Box<dyn Any>> {
+		self.value.into_inner()
+	}
+
+	/// Moves the value out of the slot, leaving the slot empty.
+	pub(crate) fn take_value(&mut self) -> Option<Box<dyn Any>> {
+		self.value.take()
+	}
+
+	/// Replaces the value of the slot, keeping its metadata.
+	pub(crate) fn set_value(&mut self, value: Box<dyn Any>) {
+		self.value = OnceCell::from(value);
+	}
+
+	/// Determines if the slot was put with a time-to-live that has elapsed.
+	#[cfg(feature = "std")]
+	pub(crate) fn is_expired(&self) -> bool {
+		self.expires.is_some_and(|at| Instant::now() >= at)
+	}
+
+	/// Time-to-live requires a clock, so without `std` nothing expires.
+	#[cfg(not(feature = "std"))]
+	pub(crate) fn is_expired(&self) -> bool {
+		false
+	}
+
+	pub(crate) fn is_cloneable(&self) -> bool {
+		self.clone.is_some()
+	}
+
+	/// Compares the values of two slots of the same type if the slot was
+	/// created with an equality function and both values have been
+	/// constructed.
+	pub(crate) fn eq_value(&self, other: &Slot) -> Option<bool> {
+		Some((self.eq?)(self.try_get()?, other.try_get()?))
+	}
+
+	/// Creates the slot for a `State` spawned from a prototype: a copy of the
+	/// value if the slot was created with a clone function, or a slot that
+	/// runs the same factory again if the value is built by a reusable
+	/// factory.
+	pub(crate) fn respawn(&self) -> Option<Slot> {
+		if let Some(slot) = self.try_clone() {
+			return Some(slot);
+		}
+		match self.init.as_ref()? {
+			Init::Factory(factory) => Some(Slot {
+				value: OnceCell::new(),
+				init: Some(Init::Factory(factory.clone())),
+				name: self.name,
+				clone: None,
+				eq: self.eq,
+				size: self.size,
+				teardown: None,
+				deps: self.deps.clone(),
+				#[cfg(feature = "std")]
+				expires: self.expires,
+				frozen: self.frozen,
+				priority: self.priority,
+				usage: None,
+				#[cfg(feature = "send-audit")]
+				send_sync: self.send_sync,
+				#[cfg(feature = "stats")]
+				stats: Stats::new(),
+				#[cfg(feature = "debug-endpoint")]
+				inserted: SystemTime::now(),
+			}),
+			_ => None,
+		}
+	}
+
+	/// Measures the approximate memory usage of the value if the slot was
+	/// created with a size function and the value has been constructed.
+	pub(crate) fn mem_size(&self) -> Option<usize> {
+		Some((self.size?)(self.try_get()?))
+	}
+
+	/// Duplicates the value if the slot was created with a clone function
+	/// and the value has been constructed.
+	pub(crate) fn clone_value(&self) -> Option<Box<dyn Any>> {
+		Some((self.clone?)(self.try_get()?))
+	}
+
+	/// Duplicates the slot if it was created with a clone function.
+	pub(crate) fn try_clone(&self) -> Option<Slot> {
+		let value = self.clone_value()?;
+		Some(Slot {
+			clone: self.clone,
+			eq: self.eq,
+			size: self.size,
+			#[cfg(feature = "std")]
+			expires: self.expires,
+			frozen: self.frozen,
+			priority: self.priority,
+			#[cfg(feature = "send-audit")]
+			send_sync: self.send_sync,
+			..Slot::named(value, self.name)
+		})
+	}
+}
+
+impl Prioritized for Slot {
+	fn priority(&self) -> i32 {
+		self.priority.unwrap_or(0)
+	}
+}
+
+impl fmt::Debug for Slot {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Slot")
+			.field("name", &self.name)
+			.field("initialized", &self.value.get().is_some())
+			.finish_non_exhaustive()
+	}
+}
+
+fn clone_value<T: Clone + 'static>(value: &dyn Any) -> Box<dyn Any> {
+	Box::new(
+		value
+			.downcast_ref::<T>()
+			.expect("slot holds its type")
+			.clone(),
+	)
+}
+
+fn eq_value<T: PartialEq + 'static>(a: &dyn Any, b: &dyn Any) -> bool {
+	a.downcast_ref::<T>() == b.downcast_ref::<T>()
+}
+
+fn size_value<T: MemSize + 'static>(value: &dyn Any) -> usize {
+	let value = value.downcast_ref::<T>().expect("slot holds its type");
+	core::mem::size_of::<T>() + value.heap_size()
+}
@@ -0,0 +1,357 @@
+//! A map and a vector that keep their first few entries inline, for use by
+//! the `small` feature.
+//!
+//! Most containers only ever hold a handful of values, which are found by a
+//! linear scan without allocating. Once more values are inserted, every
+//! entry moves into the [`Base`] map, or a `Vec`, for the rest of the
+//! collection's lifetime.
+
+use crate::map;
+use crate::map::Base;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::array;
+use core::fmt;
+use core::iter::Flatten;
+use core::mem;
+use core::ops::Index;
+use core::ops::IndexMut;
+use core::slice;
+
+/// The number of entries kept inline before the map spills.
+const INLINE: usize = 8;
+
+pub(crate) struct SmallMap<V> {
+	repr: Repr<V>,
+}
+
+enum Repr<V> {
+	/// The first `len` items are occupied, the rest are `None`.
+	Inline {
+		len: usize,
+		items: [Option<(TypeId, V)>; INLINE],
+	},
+	Spilled(Base<V>),
+}
+
+impl<V> SmallMap<V> {
+	/// Creates an empty map, which spills right away if `capacity` entries do
+	/// not fit inline.
+	pub(crate) fn with_capacity(capacity: usize) -> Self {
+		if capacity <= INLINE {
+			return SmallMap::default();
+		}
+		SmallMap {
+			repr: Repr::Spilled(map::base_with_capacity(capacity)),
+		}
+	}
+
+	/// Reserves room for `additional` more entries, spilling right away if
+	/// they do not fit inline.
+	pub(crate) fn reserve(&mut self, additional: usize) {
+		match &mut self.repr {
+			Repr::Inline { len, .. } if *len + additional <= INLINE => {},
+			Repr::Inline { len, items } => {
+				let mut map = map::base_with_capacity(*len + additional);
+				map.extend(items.iter_mut().filter_map(Option::take));
+				self.repr = Repr::Spilled(map);
+			},
+			Repr::Spilled(map) => map::base_reserve(map, additional),
+		}
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		match &self.repr {
+			Repr::Inline { len, .. } => *len,
+			Repr::Spilled(map) => map.len(),
+		}
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub(crate) fn get(&self, key: &TypeId) -> Option<&V> {
+		match &self.repr {
+			Repr::Inline { len, items } => items[..*len]
+				.iter()
+				.flatten()
+				.find(|(k, _)| k == key)
+				.map(|(_, v)| v),
+			Repr::Spilled(map) => map.get(key),
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self, key: &TypeId) -> Option<&mut V> {
+		match &mut self.repr {
+			Repr::Inline { len, items } => items[..*len]
+				.iter_mut()
+				.flatten()
+				.find(|(k, _)| k == key)
+				.map(|(_, v)| v),
+			Repr::Spilled(map) => map.get_mut(key),
+		}
+	}
+
+	pub(crate) fn contains_key(&self, key: &TypeId) -> bool {
+		self.get(key).is_some()
+	}
+
+	pub(crate) fn insert(&mut self, key: TypeId, value: V) -> Option<V> {
+		if let Some(existing) = self.get_mut(&key) {
+			return Some(mem::replace(existing, value));
+		}
+		match &mut self.repr {
+			Repr::Inline { len, items } if *len < INLINE => {
+				items[*len] = Some((key, value));
+				*len += 1;
+				None
+			},
+			Repr::Inline { items, .. } => {
+				let mut map = map::base_with_capacity(INLINE + 1);
+				map.extend(items.iter_mut().filter_map(Option::take));
+				map.insert(key, value);
+				self.repr = Repr::Spilled(map);
+				None
+			},
+			Repr::Spilled(map) => map.insert(key, value),
+		}
+	}
+
+	pub(crate) fn remove(&mut self, key: &TypeId) -> Option<V> {
+		match &mut self.repr {
+			Repr::Inline { len, items } => {
+				let i = items[..*len]
+					.iter()
+					.position(|item| item.as_ref().is_some_and(|(k, _)| k == key))?;
+				*len -= 1;
+				items.swap(i, *len);
+				items[*len].take().map(|(_, v)| v)
+			},
+			Repr::Spilled(map) => map.remove(key),
+		}
+	}
+
+	pub(crate) fn clear(&mut self) {
+		match &mut self.repr {
+			Repr::Inline { len, items } => {
+				items.iter_mut().for_each(|item| *item = None);
+				*len = 0;
+			},
+			Repr::Spilled(map) => map.clear(),
+		}
+	}
+
+	pub(crate) fn iter(&self) -> Iter<'_, V> {
+		match &self.repr {
+			Repr::Inline { len, items } => Iter::Inline(items[..*len].iter()),
+			Repr::Spilled(map) => Iter::Spilled(map.iter()),
+		}
+	}
+
+	pub(crate) fn keys(&self) -> impl Iterator<Item = &TypeId> {
+		self.iter().map(|(key, _)| key)
+	}
+
+	pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+		self.iter().map(|(_, value)| value)
+	}
+}
+
+impl<V> Default for SmallMap<V> {
+	fn default() -> Self {
+		SmallMap {
+			repr: Repr::Inline {
+				len: 0,
+				items: [const { None }; INLINE],
+			},
+		}
+	}
+}
+
+impl<V: fmt::Debug> fmt::Debug for SmallMap<V> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_map().entries(self.iter()).finish()
+	}
+}
+
+impl<V> FromIterator<(TypeId, V)> for SmallMap<V> {
+	fn from_iter<I: IntoIterator<Item = (TypeId, V)>>(iter: I) -> Self {
+		let mut map = SmallMap::default();
+		for (key, value) in iter {
+			map.insert(key, value);
+		}
+		map
+	}
+}
+
+impl<V> IntoIterator for SmallMap<V> {
+	type Item = (TypeId, V);
+	type IntoIter = IntoIter<V>;
+
+	fn into_iter(self) -> IntoIter<V> {
+		match self.repr {
+			Repr::Inline { items, .. } => IntoIter::Inline(items.into_iter().flatten()),
+			Repr::Spilled(map) => IntoIter::Spilled(map.into_iter()),
+		}
+	}
+}
+
+impl<'a, V> IntoIterator for &'a SmallMap<V> {
+	type Item = (&'a TypeId, &'a V);
+	type IntoIter = Iter<'a, V>;
+
+	fn into_iter(self) -> Iter<'a, V> {
+		self.iter()
+	}
+}
+
+pub(crate) enum Iter<'a, V> {
+	Inline(slice::Iter<'a, Option<(TypeId, V)>>),
+	Spilled(<&'a Base<V> as IntoIterator>::IntoIter),
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+	type Item = (&'a TypeId, &'a V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self {
+			Iter::Inline(iter) => iter.next()?.as_ref().map(|(k, v)| (k, v)),
+			Iter::Spilled(iter) => iter.next(),
+		}
+	}
+}
+
+pub(crate) enum IntoIter<V> {
+	Inline(Flatten<array::IntoIter<Option<(TypeId, V)>, INLINE>>),
+	Spilled(<Base<V> as IntoIterator>::IntoIter),
+}
+
+impl<V> Iterator for IntoIter<V> {
+	type Item = (TypeId, V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self {
+			IntoIter::Inline(iter) => iter.next(),
+			IntoIter::Spilled(iter) => iter.next(),
+		}
+	}
+}
+
+/// A vector that keeps its first few items inline.
+pub(crate) enum SmallVec<T> {
+	/// The first `len` items are `Some`, the rest are `None`.
+	Inline {
+		len: usize,
+		items: [Option<T>; INLINE],
+	},
+	Spilled(Vec<T>),
+}
+
+impl<T> SmallVec<T> {
+	/// Creates an empty vector, which spills right away if `capacity` items
+	/// do not fit inline.
+	pub(crate) fn with_capacity(capacity: usize) -> Self {
+		if capacity <= INLINE {
+			return SmallVec::default();
+		}
+		SmallVec::Spilled(Vec::with_capacity(capacity))
+	}
+
+	/// Reserves room for `additional` more items, spilling right away if
+	/// they do not fit inline.
+	pub(crate) fn reserve(&mut self, additional: usize) {
+		match self {
+			SmallVec::Inline { len, .. } if *len + additional <= INLINE => {},
+			SmallVec::Inline { len, items } => {
+				let mut vec = Vec::with_capacity(*len + additional);
+				vec.extend(items.iter_mut().filter_map(Option::take));
+				*self = SmallVec::Spilled(vec);
+			},
+			SmallVec::Spilled(vec) => vec.reserve(additional),
+		}
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		match self {
+			SmallVec::Inline { len, .. } => *len,
+			SmallVec::Spilled(vec) => vec.len(),
+		}
+	}
+
+	pub(crate) fn push(&mut self, item: T) {
+		match self {
+			SmallVec::Inline { len, items } if *len < INLINE => {
+				items[*len] = Some(item);
+				*len += 1;
+			},
+			SmallVec::Inline { items, .. } => {
+				let mut vec = Vec::with_capacity(INLINE * 2);
+				vec.extend(items.iter_mut().filter_map(Option::take));
+				vec.push(item);
+				*self = SmallVec::Spilled(vec);
+			},
+			SmallVec::Spilled(vec) => vec.push(item),
+		}
+	}
+
+	pub(crate) fn get(&self, index: usize) -> Option<&T> {
+		match self {
+			SmallVec::Inline { len, items } => items[..*len].get(index)?.as_ref(),
+			SmallVec::Spilled(vec) => vec.get(index),
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+		match self {
+			SmallVec::Inline { len, items } => items[..*len].get_mut(index)?.as_mut(),
+			SmallVec::Spilled(vec) => vec.get_mut(index),
+		}
+	}
+
+	pub(crate) fn iter_mut(&mut self) -> IterMut<'_, T> {
+		match self {
+			SmallVec::Inline { len, items } => IterMut::Inline(items[..*len].iter_mut().flatten()),
+			SmallVec::Spilled(vec) => IterMut::Spilled(vec.iter_mut()),
+		}
+	}
+}
+
+impl<T> Default for SmallVec<T> {
+	fn default() -> Self {
+		SmallVec::Inline {
+			len: 0,
+			items: [const { None }; INLINE],
+		}
+	}
+}
+
+impl<T> Index<usize> for SmallVec<T> {
+	type Output = T;
+
+	fn index(&self, index: usize) -> &T {
+		self.get(index).expect("index is in bounds")
+	}
+}
+
+impl<T> IndexMut<usize> for SmallVec<T> {
+	fn index_mut(&mut self, index: usize) -> &mut T {
+		self.get_mut(index).expect("index is in bounds")
+	}
+}
+
+pub(crate) enum IterMut<'a, T> {
+	Inline(Flatten<slice::IterMut<'a, Option<T>>>),
+	Spilled(slice::IterMut<'a, T>),
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+	type Item = &'a mut T;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self {
+			IterMut::Inline(iter) => iter.next(),
+			IterMut::Spilled(iter) => iter.next(),
+		}
+	}
+}
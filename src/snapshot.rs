@@ -0,0 +1,154 @@
+use crate::map::Map;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateError;
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+/// A copy of the cloneable entries of a `State`, taken with
+/// [`State::snapshot`] and reinstated with [`State::restore`].
+#[derive(Debug)]
+pub struct StateSnapshot {
+	data: Map<Slot>,
+}
+
+impl State {
+	/// Puts a value into the `State` storage, like [`put`](State::put), and
+	/// records how to clone it so it is captured by
+	/// [`snapshot`](State::snapshot).
+	pub fn put_cloneable<T: Clone + 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting cloneable record to state for type_id `{:?}`",
+			type_id
+		);
+		self.insert_slot(type_id, Slot::cloneable(t));
+	}
+
+	/// Captures a copy of every entry that was put with
+	/// [`put_cloneable`](State::put_cloneable). Other entries are not part of
+	/// the snapshot.
+	pub fn snapshot(&self) -> StateSnapshot {
+		let data = self
+			.data
+			.iter()
+			.filter_map(|(type_id, slot)| Some((*type_id, slot.try_clone()?)))
+			.collect();
+		StateSnapshot { data }
+	}
+
+	/// Tries to create an independent copy of this `State`, such as one for
+	/// each worker thread from a prototype container. Fails with
+	/// [`StateError::NotCloneable`] unless every value was put with
+	/// [`put_cloneable`](State::put_cloneable). Change hooks are not copied.
+	pub fn try_deep_clone(&self) -> Result<State, StateError> {
+		trace!(" cloning state data");
+		let names: Vec<_> = self
+			.data
+			.values()
+			.filter(|slot| !slot.is_cloneable())
+			.map(|slot| slot.name)
+			.collect();
+		if !names.is_empty() {
+			return Err(StateError::NotCloneable { names });
+		}
+		let data = self
+			.data
+			.iter()
+			.map(|(type_id, slot)| {
+				let slot = slot.try_clone().expect("cloneable slot is constructed");
+				(*type_id, slot)
+			})
+			.collect();
+		Ok(State {
+			data,
+			..State::default()
+		})
+	}
+
+	/// Creates an independent copy of this `State`, like
+	/// [`try_deep_clone`](State::try_deep_clone).
+	///
+	/// # Panics
+	///
+	/// If any of the values was not put with
+	/// [`put_cloneable`](State::put_cloneable).
+	pub fn deep_clone(&self) -> State {
+		self.try_deep_clone().unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Rolls the cloneable entries back to `snapshot`. Cloneable entries put
+	/// since the snapshot was taken are dropped, while entries that cannot be
+	/// cloned are left untouched.
+	///
+	/// Fails with [`StateError::Frozen`] if an entry that would be replaced
+	/// or dropped has been frozen, and nothing is restored.
+	pub fn try_restore(&mut self, snapshot: StateSnapshot) -> Result<(), StateError> {
+		trace!(" restoring state from snapshot");
+		let stale: Vec<_> = self
+			.data
+			.iter()
+			.filter(|(type_id, slot)| slot.is_cloneable() && !snapshot.data.contains_key(type_id))
+			.map(|(type_id, _)| *type_id)
+			.collect();
+		let touched = stale.iter().chain(snapshot.data.keys());
+		if let Some(slot) = touched
+			.filter_map(|type_id| self.data.get(type_id))
+			.find(|slot| slot.frozen)
+		{
+			return Err(StateError::Frozen { name: slot.name });
+		}
+		for type_id in stale {
+			self.remove_slot(type_id);
+		}
+		for (type_id, slot) in snapshot.data {
+			self.insert_slot(type_id, slot);
+		}
+		Ok(())
+	}
+
+	/// Rolls the cloneable entries back to `snapshot`, like
+	/// [`try_restore`](State::try_restore).
+	///
+	/// # Panics
+	///
+	/// If an entry that would be replaced or dropped has been frozen.
+	pub fn restore(&mut self, snapshot: StateSnapshot) {
+		self.try_restore(snapshot)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn restore_round_trips_cloneable_entries() {
+		let mut state = State::default();
+		state.put_cloneable(1u8);
+		state.put(1u16);
+		let snapshot = state.snapshot();
+		*state.borrow_mut::<u8>() = 2;
+		state.put(2u16);
+		state.put_cloneable(2u32);
+		state.restore(snapshot);
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert_eq!(state.borrow::<u16>(), &2);
+		assert!(!state.has::<u32>());
+	}
+
+	#[test]
+	fn frozen_entries_are_not_restored() {
+		let mut state = State::default();
+		state.put_cloneable(1u8);
+		let snapshot = state.snapshot();
+		state.put_cloneable(2u8);
+		state.put_cloneable(2u32);
+		state.freeze_type::<u32>();
+		let result = state.try_restore(snapshot);
+		assert!(matches!(result, Err(StateError::Frozen { name: "u32" })));
+		assert_eq!(state.borrow::<u8>(), &2);
+		assert!(state.has::<u32>());
+	}
+}
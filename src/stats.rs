@@ -0,0 +1,64 @@
+use crate::State;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::cell::Cell;
+use std::time::Instant;
+
+/// The access statistics of a value in a `State`, as returned by
+/// [`State::entry_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryStats {
+	/// The `TypeId` of the value.
+	pub type_id: TypeId,
+	/// The name of the type of the value.
+	pub name: &'static str,
+	/// When the value was put.
+	pub inserted: Instant,
+	/// When the value was last borrowed, or `None` if it never was.
+	pub last_access: Option<Instant>,
+	/// The number of times the value was borrowed, shared or mutably.
+	pub accesses: u64,
+}
+
+/// The access statistics recorded in a slot.
+#[derive(Debug)]
+pub(crate) struct Stats {
+	inserted: Instant,
+	last_access: Cell<Option<Instant>>,
+	accesses: Cell<u64>,
+}
+
+impl Stats {
+	pub(crate) fn new() -> Self {
+		Stats {
+			inserted: Instant::now(),
+			last_access: Cell::new(None),
+			accesses: Cell::new(0),
+		}
+	}
+}
+
+impl State {
+	/// Returns the access statistics of every value in the `State` storage,
+	/// so long-lived services can find values that are stale or never used.
+	pub fn entry_stats(&self) -> Vec<EntryStats> {
+		self.data
+			.iter()
+			.map(|(type_id, slot)| EntryStats {
+				type_id: *type_id,
+				name: slot.name,
+				inserted: slot.stats.inserted,
+				last_access: slot.stats.last_access.get(),
+				accesses: slot.stats.accesses.get(),
+			})
+			.collect()
+	}
+
+	/// Records an access to the value with the given `TypeId`.
+	pub(crate) fn record_access(&self, type_id: TypeId) {
+		if let Some(slot) = self.data.get(&type_id) {
+			slot.stats.last_access.set(Some(Instant::now()));
+			slot.stats.accesses.set(slot.stats.accesses.get() + 1);
+		}
+	}
+}
@@ -0,0 +1,139 @@
+//! A cell holding an `Arc` that is read without locking, for the values of
+//! [`ConcurrentState`](crate::ConcurrentState).
+//!
+//! Readers announce themselves in one of two reader counters before loading
+//! the pointer, and leave once they have taken their own strong count on
+//! the `Arc`. A writer swaps in the new pointer and then waits until both
+//! counters have been seen at zero, after which no reader can still be
+//! about to take a strong count on the old `Arc`, so it can be released.
+//! Readers never wait; the counter they announce themselves in is flipped
+//! by writers, so that a steady stream of readers cannot keep a writer
+//! waiting forever.
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::SeqCst;
+
+pub(crate) struct ArcCell<T> {
+	/// A pointer obtained from `Arc::into_raw`, which owns one strong count.
+	ptr: AtomicPtr<T>,
+	readers: [AtomicUsize; 2],
+	epoch: AtomicUsize,
+	/// Makes the cell `Send` and `Sync` exactly when `Arc<T>` is, as it
+	/// hands out clones of the `Arc` to every thread.
+	marker: PhantomData<Arc<T>>,
+}
+
+impl<T> ArcCell<T> {
+	pub(crate) fn new(value: Arc<T>) -> Self {
+		ArcCell {
+			ptr: AtomicPtr::new(Arc::into_raw(value).cast_mut()),
+			readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+			epoch: AtomicUsize::new(0),
+			marker: PhantomData,
+		}
+	}
+
+	/// Returns a clone of the current `Arc`.
+	pub(crate) fn load(&self) -> Arc<T> {
+		let readers = &self.readers[self.epoch.load(SeqCst) & 1];
+		readers.fetch_add(1, SeqCst);
+		let ptr = self.ptr.load(SeqCst);
+		// SAFETY: `ptr` came from `Arc::into_raw` and the cell owned a strong
+		// count on it when it was loaded. A writer that swaps it out only
+		// releases that count once it has seen `readers` at zero after the
+		// swap, which cannot happen before the `fetch_sub` below, so the
+		// allocation is alive until this strong count has been taken.
+		unsafe { Arc::increment_strong_count(ptr) };
+		readers.fetch_sub(1, SeqCst);
+		// SAFETY: the strong count taken above is owned by the new `Arc`.
+		unsafe { Arc::from_raw(ptr) }
+	}
+
+	/// Replaces the current `Arc` with `value` and returns the previous one,
+	/// once no reader can still be loading it.
+	pub(crate) fn swap(&self, value: Arc<T>) -> Arc<T> {
+		let previous = self.ptr.swap(Arc::into_raw(value).cast_mut(), SeqCst);
+		self.wait_for_readers();
+		// SAFETY: `previous` came from `Arc::into_raw` and the strong count
+		// owned by the cell is handed to the returned `Arc`. Readers that
+		// loaded it have taken their own strong count by now.
+		unsafe { Arc::from_raw(previous) }
+	}
+
+	/// Waits until every reader that may have loaded the pointer before it
+	/// was swapped has taken its strong count. Every reader that announces
+	/// itself later loads the new pointer.
+	fn wait_for_readers(&self) {
+		for _ in 0..2 {
+			let epoch = self.epoch.fetch_xor(1, SeqCst) & 1;
+			while self.readers[epoch].load(SeqCst) != 0 {
+				std::thread::yield_now();
+			}
+		}
+	}
+}
+
+impl<T> Drop for ArcCell<T> {
+	fn drop(&mut self) {
+		// SAFETY: the pointer came from `Arc::into_raw`, and no reader can be
+		// loading it while the cell is exclusively borrowed.
+		drop(unsafe { Arc::from_raw(*self.ptr.get_mut()) });
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArcCell<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&self.load(), f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+
+	#[test]
+	fn swap_hands_back_the_previous_arc() {
+		let first = Arc::new(1);
+		let cell = ArcCell::new(first.clone());
+		assert_eq!(Arc::strong_count(&first), 2);
+		let loaded = cell.load();
+		assert_eq!(Arc::strong_count(&first), 3);
+		let previous = cell.swap(Arc::new(2));
+		assert!(Arc::ptr_eq(&previous, &first));
+		assert_eq!(*cell.load(), 2);
+		drop((loaded, previous));
+		assert_eq!(Arc::strong_count(&first), 1);
+		let second = cell.load();
+		drop(cell);
+		assert_eq!(Arc::strong_count(&second), 1);
+	}
+
+	#[test]
+	fn readers_see_every_value_whole_while_writers_swap() {
+		let cell = ArcCell::new(Arc::new((0u64, 0u64)));
+		thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					let mut last = 0;
+					for _ in 0..10_000 {
+						let value = cell.load();
+						assert_eq!(value.0, value.1);
+						assert!(value.0 >= last, "values are swapped in order");
+						last = value.0;
+					}
+				});
+			}
+			scope.spawn(|| {
+				for i in 1..=2_000 {
+					drop(cell.swap(Arc::new((i, i))));
+				}
+			});
+		});
+		assert_eq!(*cell.load(), (2_000, 2_000));
+	}
+}
@@ -0,0 +1,145 @@
+use crate::map::Map;
+use crate::missing;
+use crate::StateRead;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::any::Any;
+use core::any::TypeId;
+
+/// A state container which only accepts `Send + Sync` values, so the
+/// container itself can be shared across threads, for example behind an
+/// `Arc`.
+#[derive(Default, Debug)]
+pub struct SyncState {
+	data: Map<(&'static str, Box<dyn Any + Send + Sync>)>,
+}
+
+impl SyncState {
+	/// Puts a value into the `SyncState` storage. One value of each type is
+	/// retained. Successive calls to `put` will overwrite the existing value
+	/// of the same type.
+	pub fn put<T: Any + Send + Sync>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting record to sync state for type_id `{:?}`",
+			type_id
+		);
+		self.data.insert(type_id, (type_name::<T>(), Box::new(t)));
+	}
+
+	/// Tries to put a value into the `SyncState` storage without overwriting
+	/// an existing value of the same type. If a value of type `T` is already
+	/// present, the new value is handed back in `Err`.
+	pub fn try_put<T: Any + Send + Sync>(&mut self, t: T) -> Result<(), T> {
+		if self.has::<T>() {
+			return Err(t);
+		}
+		self.put(t);
+		Ok(())
+	}
+
+	/// Determines if the current value exists in `SyncState` storage.
+	pub fn has<T: Any + Send + Sync>(&self) -> bool {
+		self.data.contains_key(&TypeId::of::<T>())
+	}
+
+	/// Tries to borrow a value from the `SyncState` storage.
+	pub fn try_borrow<T: Any + Send + Sync>(&self) -> Option<&T> {
+		let type_id = TypeId::of::<T>();
+		trace!(" borrowing sync state data for type_id `{:?}`", type_id);
+		self.data.get(&type_id).and_then(|(_, b)| b.downcast_ref())
+	}
+
+	/// Borrows a value from the `SyncState` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `SyncState`.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: Any + Send + Sync>(&self) -> &T {
+		match self.try_borrow() {
+			Some(value) => value,
+			None => missing::<T>(None, self.type_names()),
+		}
+	}
+
+	/// Tries to mutably borrow a value from the `SyncState` storage.
+	pub fn try_borrow_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" mutably borrowing sync state data for type_id `{:?}`",
+			type_id
+		);
+		self.data
+			.get_mut(&type_id)
+			.and_then(|(_, b)| b.downcast_mut())
+	}
+
+	/// Mutably borrows a value from the `SyncState` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `SyncState`.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow_mut<T: Any + Send + Sync>(&mut self) -> &mut T {
+		if !self.has::<T>() {
+			missing::<T>(None, self.type_names())
+		}
+		self.try_borrow_mut().expect("value was checked above")
+	}
+
+	/// Tries to move a value out of the `SyncState` storage and return
+	/// ownership.
+	pub fn try_take<T: Any + Send + Sync>(&mut self) -> Option<T> {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" taking ownership from sync state data for type_id `{:?}`",
+			type_id
+		);
+		self.data
+			.remove(&type_id)
+			.and_then(|(_, b)| b.downcast().ok())
+			.map(|b| *b)
+	}
+
+	/// Moves a value out of the `SyncState` storage and returns ownership.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `SyncState`.
+	#[track_caller]
+	pub fn take<T: Any + Send + Sync>(&mut self) -> T {
+		if !self.has::<T>() {
+			missing::<T>(None, self.type_names())
+		}
+		self.try_take().expect("value was checked above")
+	}
+
+	/// The names of the types stored in the `SyncState`.
+	fn type_names(&self) -> Vec<&'static str> {
+		self.data.values().map(|(name, _)| *name).collect()
+	}
+}
+
+impl StateRead for SyncState {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		self.data
+			.get(&TypeId::of::<T>())
+			.and_then(|(_, b)| b.downcast_ref())
+	}
+
+	#[track_caller]
+	fn borrow<T: 'static>(&self) -> &T {
+		match StateRead::try_borrow(self) {
+			Some(value) => value,
+			None => missing::<T>(None, self.type_names()),
+		}
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		self.data.contains_key(&TypeId::of::<T>())
+	}
+}
@@ -0,0 +1,148 @@
+use crate::State;
+use alloc::boxed::Box;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+
+std::thread_local! {
+	static CURRENT: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+/// A future that makes a `State` available through [`State::current`] while
+/// it is polled, as returned by [`State::scope_task_local`].
+pub struct TaskLocalFuture<F: Future> {
+	state: Option<State>,
+	future: Pin<Box<F>>,
+}
+
+impl State {
+	/// Wraps `future` so that `state` can be accessed with
+	/// [`current`](State::current) from anywhere within it, such as from
+	/// handlers deep in a call stack, without passing the `State` along.
+	///
+	/// The `State` is only installed while the future is being polled, so
+	/// tasks that are interleaved on the same thread each see their own
+	/// `State`. As `State` is not `Send`, neither is the returned future, so
+	/// it has to run on a single-threaded executor, or be spawned with a
+	/// local spawner such as `spawn_local`.
+	///
+	/// # Panics
+	///
+	/// Polling the returned future panics if it is polled from within the
+	/// closure passed to [`current`](State::current).
+	pub fn scope_task_local<F: Future>(state: State, future: F) -> TaskLocalFuture<F> {
+		TaskLocalFuture {
+			state: Some(state),
+			future: Box::pin(future),
+		}
+	}
+
+	/// Calls `f` with the `State` of the enclosing
+	/// [`scope_task_local`](State::scope_task_local) future and returns its
+	/// result.
+	///
+	/// # Panics
+	///
+	/// If called outside of a `scope_task_local` future, or from within `f`.
+	pub fn current<R>(f: impl FnOnce(&mut State) -> R) -> R {
+		State::try_current(f).expect("no task-local State container is in scope")
+	}
+
+	/// Calls `f` with the `State` of the enclosing
+	/// [`scope_task_local`](State::scope_task_local) future and returns its
+	/// result, or returns `None` if called outside of one.
+	///
+	/// # Panics
+	///
+	/// If called from within `f`.
+	pub fn try_current<R>(f: impl FnOnce(&mut State) -> R) -> Option<R> {
+		CURRENT.with(|current| current.borrow_mut().as_mut().map(f))
+	}
+}
+
+impl<F: Future> Future for TaskLocalFuture<F> {
+	type Output = F::Output;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+		let this = &mut *self;
+		let previous = CURRENT.with(|current| {
+			let Ok(mut current) = current.try_borrow_mut() else {
+				panic!("a task-local State container cannot be polled from within State::current");
+			};
+			let state = this
+				.state
+				.take()
+				.expect("task-local State container is moved back after every poll");
+			current.replace(state)
+		});
+		let scope = Scope {
+			slot: &mut this.state,
+			previous,
+		};
+		let poll = this.future.as_mut().poll(cx);
+		drop(scope);
+		poll
+	}
+}
+
+/// Moves the installed `State` back into its future when dropped, even if
+/// polling panicked, and restores the `State` of an enclosing scope.
+struct Scope<'a> {
+	slot: &'a mut Option<State>,
+	previous: Option<State>,
+}
+
+impl Drop for Scope<'_> {
+	fn drop(&mut self) {
+		*self.slot = CURRENT.with(|current| current.replace(self.previous.take()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::panic::AssertUnwindSafe;
+	use core::task::Waker;
+	use std::panic::catch_unwind;
+
+	fn poll<F: Future>(future: &mut TaskLocalFuture<F>) -> Poll<F::Output> {
+		let mut cx = Context::from_waker(Waker::noop());
+		Pin::new(future).poll(&mut cx)
+	}
+
+	fn state_with(value: u32) -> State {
+		let mut state = State::default();
+		state.put(value);
+		state
+	}
+
+	#[test]
+	fn current_is_the_state_of_the_polled_future() {
+		let outer = State::scope_task_local(state_with(1), async {
+			let mut inner = State::scope_task_local(state_with(2), async {
+				State::current(|state| *state.borrow::<u32>())
+			});
+			let inner = poll(&mut inner);
+			(State::current(|state| *state.borrow::<u32>()), inner)
+		});
+		let mut outer = outer;
+		assert_eq!(poll(&mut outer), Poll::Ready((1, Poll::Ready(2))));
+		assert_eq!(State::try_current(|_| ()), None);
+	}
+
+	#[test]
+	fn polling_from_within_current_panics() {
+		let mut inner = State::scope_task_local(state_with(2), async {});
+		let mut outer = State::scope_task_local(state_with(1), async move {
+			State::current(|_| {
+				let _ = poll(&mut inner);
+			})
+		});
+		let error = catch_unwind(AssertUnwindSafe(|| poll(&mut outer))).unwrap_err();
+		let message = error.downcast_ref::<&str>().unwrap();
+		assert!(message.contains("cannot be polled from within State::current"));
+		assert_eq!(State::try_current(|_| ()), None);
+	}
+}
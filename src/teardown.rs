@@ -0,0 +1,113 @@
+use crate::slot::Slot;
+use crate::State;
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+/// The order in which [`State::shutdown`] runs teardown callbacks. Values
+/// are ordered by their priority, as given to
+/// [`put_with_priority`](State::put_with_priority), and then by insertion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TeardownOrder {
+	/// Values put last, or with the highest priority, are torn down first,
+	/// so values can rely on those put before them during teardown.
+	#[default]
+	Reverse,
+	/// Values are torn down in the order they were put, from the lowest
+	/// priority to the highest.
+	Insertion,
+}
+
+impl State {
+	/// Puts a value into the `State` storage like [`put`](State::put), and
+	/// registers `teardown` to be called with the value by
+	/// [`shutdown`](State::shutdown), so resources such as connection pools
+	/// can be flushed and closed deterministically.
+	///
+	/// If the value is overwritten or removed before the `State` is shut
+	/// down, the callback is dropped without being called.
+	pub fn put_with_teardown<T: 'static>(&mut self, t: T, teardown: impl FnOnce(T) + 'static) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting record with teardown to state for type_id `{:?}`",
+			type_id
+		);
+		self.insert_slot(type_id, Slot::with_teardown(t, teardown));
+	}
+
+	/// Removes every value that was put with
+	/// [`put_with_teardown`](State::put_with_teardown) in the given `order`,
+	/// calling its teardown callback, and then drops the remaining values.
+	/// Frozen values are torn down as well. The `State` is empty afterwards.
+	pub fn shutdown(&mut self, order: TeardownOrder) {
+		trace!(" shutting down state data in {:?} order", order);
+		let mut teardowns: Vec<TypeId> = self
+			.data
+			.iter()
+			.filter(|(_, slot)| slot.teardown.is_some())
+			.map(|(type_id, _)| *type_id)
+			.collect();
+		if order == TeardownOrder::Reverse {
+			teardowns.reverse();
+		}
+		for type_id in teardowns {
+			let mut slot = self.remove_slot(type_id).expect("slot is present");
+			let teardown = slot.teardown.take();
+			if let (Some(teardown), Some(value)) = (teardown, slot.into_value()) {
+				teardown(value);
+			}
+		}
+		self.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::MergePolicy;
+	use alloc::rc::Rc;
+	use core::cell::RefCell;
+
+	fn record(log: &Rc<RefCell<Vec<u32>>>) -> impl Fn(u32) + 'static {
+		let log = log.clone();
+		move |value| log.borrow_mut().push(value)
+	}
+
+	#[test]
+	fn shutdown_runs_teardowns_in_order() {
+		let log = Rc::new(RefCell::new(Vec::new()));
+		let mut state = State::default();
+		let first = record(&log);
+		state.put_with_teardown(1u8, move |value| first(value.into()));
+		let second = record(&log);
+		state.put_with_teardown(2u16, move |value| second(value.into()));
+		let third = record(&log);
+		state.put_with_teardown(3u32, third);
+		state.put(4u64);
+		state.shutdown(TeardownOrder::Reverse);
+		assert_eq!(*log.borrow(), [3, 2, 1]);
+		assert!(state.is_empty());
+	}
+
+	#[test]
+	fn teardown_survives_merge() {
+		let log = Rc::new(RefCell::new(Vec::new()));
+		let mut a = State::default();
+		let record = record(&log);
+		a.put_with_teardown(5u8, move |value| record(value.into()));
+		let mut b = State::default();
+		b.merge(a, MergePolicy::Overwrite).unwrap();
+		b.shutdown(TeardownOrder::Insertion);
+		assert_eq!(*log.borrow(), [5]);
+	}
+
+	#[test]
+	fn overwritten_teardown_is_not_called() {
+		let log = Rc::new(RefCell::new(Vec::new()));
+		let mut state = State::default();
+		let record = record(&log);
+		state.put_with_teardown(5u8, move |value| record(value.into()));
+		state.put(6u8);
+		state.shutdown(TeardownOrder::Reverse);
+		assert!(log.borrow().is_empty());
+	}
+}
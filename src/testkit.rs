@@ -0,0 +1,384 @@
+//! Generators and a model-based harness for testing state containers.
+//!
+//! The harness applies a sequence of [`Op`]s both to a container and to a
+//! reference model backed by a `HashMap<TypeId, Value>`, and reports the
+//! first operation whose outcome differs. Containers other than [`State`],
+//! such as wrappers in downstream crates, can prove that they behave like
+//! a `State` by implementing [`ModelSubject`] and running the same
+//! sequences.
+//!
+//! ```ignore
+//! use gotham_state::testkit;
+//!
+//! for seed in 0..100 {
+//!     let ops = testkit::Gen::new(seed).ops(64);
+//!     testkit::check(&mut MyState::default(), &ops).unwrap();
+//! }
+//! ```
+
+use crate::LayeredState;
+use crate::State;
+use crate::StateRead;
+use crate::StateWrite;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+use std::collections::HashMap;
+
+/// A container that can be checked against the reference model.
+pub trait ModelSubject: StateWrite {
+	/// Tries to move a value of type `T` out of the container.
+	fn try_take<T: 'static>(&mut self) -> Option<T>;
+}
+
+impl ModelSubject for State {
+	fn try_take<T: 'static>(&mut self) -> Option<T> {
+		State::try_take(self)
+	}
+}
+
+impl ModelSubject for LayeredState<'_> {
+	fn try_take<T: 'static>(&mut self) -> Option<T> {
+		LayeredState::try_take(self)
+	}
+}
+
+macro_rules! values {
+	($($variant:ident($ty:ty)),+ $(,)?) => {
+		/// A value of one of the types the harness stores.
+		#[derive(Debug, Clone, PartialEq, Eq)]
+		pub enum Value {
+			$($variant($ty),)+
+		}
+
+		/// One of the types the harness stores.
+		#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+		pub enum Kind {
+			$($variant,)+
+		}
+
+		const KINDS: &[Kind] = &[$(Kind::$variant),+];
+
+		impl Value {
+			/// Returns the type of the value.
+			pub fn kind(&self) -> Kind {
+				match self {
+					$(Value::$variant(_) => Kind::$variant,)+
+				}
+			}
+
+			fn put_into<S: StateWrite + ?Sized>(self, subject: &mut S) {
+				match self {
+					$(Value::$variant(v) => subject.put(v),)+
+				}
+			}
+
+			/// Overwrites the value of the same type in place, returning the
+			/// previous value.
+			fn replace_in<S: StateWrite + ?Sized>(self, subject: &mut S) -> Option<Value> {
+				match self {
+					$(Value::$variant(v) => subject
+						.try_borrow_mut::<$ty>()
+						.map(|old| Value::$variant(core::mem::replace(old, v))),)+
+				}
+			}
+		}
+
+		impl Kind {
+			/// Returns the `TypeId` of the type.
+			pub fn type_id(self) -> TypeId {
+				match self {
+					$(Kind::$variant => TypeId::of::<$ty>(),)+
+				}
+			}
+
+			fn borrow_from<S: StateRead + ?Sized>(self, subject: &S) -> Option<Value> {
+				match self {
+					$(Kind::$variant => subject.try_borrow::<$ty>().cloned().map(Value::$variant),)+
+				}
+			}
+
+			fn has_in<S: StateRead + ?Sized>(self, subject: &S) -> bool {
+				match self {
+					$(Kind::$variant => subject.has::<$ty>(),)+
+				}
+			}
+
+			fn take_from<S: ModelSubject + ?Sized>(self, subject: &mut S) -> Option<Value> {
+				match self {
+					$(Kind::$variant => subject.try_take::<$ty>().map(Value::$variant),)+
+				}
+			}
+		}
+	};
+}
+
+values!(
+	U8(u8),
+	U64(u64),
+	I32(i32),
+	Flag(bool),
+	Pair((u16, u16)),
+	Text(String),
+);
+
+/// An operation applied by the harness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+	/// Puts the value, overwriting the value of the same type.
+	Put(Value),
+	/// Borrows the value of the type.
+	Borrow(Kind),
+	/// Determines if a value of the type is present.
+	Has(Kind),
+	/// Overwrites the value of the same type through a mutable borrow.
+	Replace(Value),
+	/// Moves the value of the type out.
+	Take(Kind),
+}
+
+/// The observable result of an [`Op`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+	/// The operation has no observable result.
+	Done,
+	/// The value that was borrowed, replaced or taken.
+	Value(Option<Value>),
+	/// Whether a value was present.
+	Present(bool),
+}
+
+/// The first difference between a container and the reference model, as
+/// returned by [`check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+	/// The index of the operation that differed, or the number of operations
+	/// if the contents differed after all of them were applied.
+	pub step: usize,
+	/// The operation that differed, or `None` for the final comparison of
+	/// the contents.
+	pub op: Option<Op>,
+	/// The outcome of the reference model.
+	pub expected: Outcome,
+	/// The outcome of the container.
+	pub actual: Outcome,
+}
+
+impl fmt::Display for Mismatch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.op {
+			Some(op) => write!(f, "step {} ({:?})", self.step, op)?,
+			None => write!(f, "final contents")?,
+		}
+		write!(f, ": expected {:?}, got {:?}", self.expected, self.actual)
+	}
+}
+
+impl std::error::Error for Mismatch {}
+
+/// The reference model of a state container.
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+	values: HashMap<TypeId, Value>,
+}
+
+impl Model {
+	/// Applies `op` to the model.
+	pub fn apply(&mut self, op: &Op) -> Outcome {
+		match op {
+			Op::Put(value) => {
+				self.values.insert(value.kind().type_id(), value.clone());
+				Outcome::Done
+			},
+			Op::Borrow(kind) => Outcome::Value(self.values.get(&kind.type_id()).cloned()),
+			Op::Has(kind) => Outcome::Present(self.values.contains_key(&kind.type_id())),
+			Op::Replace(value) => Outcome::Value(
+				self.values
+					.get_mut(&value.kind().type_id())
+					.map(|old| core::mem::replace(old, value.clone())),
+			),
+			Op::Take(kind) => Outcome::Value(self.values.remove(&kind.type_id())),
+		}
+	}
+
+	/// Returns the value of the type, if present.
+	pub fn get(&self, kind: Kind) -> Option<&Value> {
+		self.values.get(&kind.type_id())
+	}
+}
+
+/// Applies `op` to `subject`.
+pub fn apply<S: ModelSubject + ?Sized>(subject: &mut S, op: &Op) -> Outcome {
+	match op {
+		Op::Put(value) => {
+			value.clone().put_into(subject);
+			Outcome::Done
+		},
+		Op::Borrow(kind) => Outcome::Value(kind.borrow_from(subject)),
+		Op::Has(kind) => Outcome::Present(kind.has_in(subject)),
+		Op::Replace(value) => Outcome::Value(value.clone().replace_in(subject)),
+		Op::Take(kind) => Outcome::Value(kind.take_from(subject)),
+	}
+}
+
+/// Applies `ops` to `subject` and to a fresh [`Model`], comparing the
+/// outcome of every operation and the final contents, starting from an
+/// empty `subject`.
+pub fn check<S: ModelSubject + ?Sized>(subject: &mut S, ops: &[Op]) -> Result<(), Mismatch> {
+	let mut model = Model::default();
+	for (step, op) in ops.iter().enumerate() {
+		let expected = model.apply(op);
+		let actual = apply(subject, op);
+		if expected != actual {
+			return Err(Mismatch {
+				step,
+				op: Some(op.clone()),
+				expected,
+				actual,
+			});
+		}
+	}
+	for &kind in KINDS {
+		let expected = Outcome::Value(model.get(kind).cloned());
+		let actual = Outcome::Value(kind.borrow_from(subject));
+		if expected != actual {
+			return Err(Mismatch {
+				step: ops.len(),
+				op: None,
+				expected,
+				actual,
+			});
+		}
+	}
+	Ok(())
+}
+
+/// A deterministic generator of arbitrary values and operations.
+#[derive(Debug, Clone)]
+pub struct Gen {
+	state: u64,
+}
+
+impl Gen {
+	/// Creates a generator whose output is determined by `seed`.
+	pub fn new(seed: u64) -> Self {
+		Gen {
+			state: seed ^ 0x9E37_79B9_7F4A_7C15,
+		}
+	}
+
+	/// Returns the next pseudo-random number.
+	pub fn next_u64(&mut self) -> u64 {
+		// splitmix64
+		self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		(self.next_u64() % n as u64) as usize
+	}
+
+	/// Generates a type.
+	pub fn kind(&mut self) -> Kind {
+		KINDS[self.below(KINDS.len())]
+	}
+
+	/// Generates a value of the type.
+	pub fn value_of(&mut self, kind: Kind) -> Value {
+		let n = self.next_u64();
+		match kind {
+			Kind::U8 => Value::U8(n as u8),
+			Kind::U64 => Value::U64(n),
+			Kind::I32 => Value::I32(n as i32),
+			Kind::Flag => Value::Flag(n & 1 == 1),
+			Kind::Pair => Value::Pair((n as u16, (n >> 16) as u16)),
+			Kind::Text => {
+				let len = self.below(8);
+				Value::Text(
+					(0..len)
+						.map(|_| (b'a' + self.below(26) as u8) as char)
+						.collect(),
+				)
+			},
+		}
+	}
+
+	/// Generates a value.
+	pub fn value(&mut self) -> Value {
+		let kind = self.kind();
+		self.value_of(kind)
+	}
+
+	/// Generates an operation.
+	pub fn op(&mut self) -> Op {
+		match self.below(5) {
+			0 => Op::Put(self.value()),
+			1 => Op::Borrow(self.kind()),
+			2 => Op::Has(self.kind()),
+			3 => Op::Replace(self.value()),
+			_ => Op::Take(self.kind()),
+		}
+	}
+
+	/// Generates `len` operations.
+	pub fn ops(&mut self, len: usize) -> Vec<Op> {
+		(0..len).map(|_| self.op()).collect()
+	}
+}
+
+/// Decodes operations from raw bytes, such as the input of a fuzzer, so
+/// that similar inputs produce similar sequences. Every four bytes decode to
+/// one operation, and trailing bytes are ignored.
+pub fn ops_from_bytes(bytes: &[u8]) -> Vec<Op> {
+	bytes
+		.chunks_exact(4)
+		.map(|chunk| {
+			let kind = KINDS[chunk[1] as usize % KINDS.len()];
+			let seed = u64::from(u16::from_le_bytes([chunk[2], chunk[3]]));
+			match chunk[0] % 5 {
+				0 => Op::Put(Gen::new(seed).value_of(kind)),
+				1 => Op::Borrow(kind),
+				2 => Op::Has(kind),
+				3 => Op::Replace(Gen::new(seed).value_of(kind)),
+				_ => Op::Take(kind),
+			}
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn state_behaves_like_the_model() {
+		for seed in 0..200 {
+			let ops = Gen::new(seed).ops(64);
+			if let Err(mismatch) = check(&mut State::default(), &ops) {
+				panic!("seed {seed}: {mismatch}");
+			}
+		}
+	}
+
+	#[test]
+	fn layered_state_behaves_like_the_model() {
+		let parent = State::default();
+		for seed in 0..200 {
+			let ops = Gen::new(seed).ops(64);
+			if let Err(mismatch) = check(&mut LayeredState::new(&parent), &ops) {
+				panic!("seed {seed}: {mismatch}");
+			}
+		}
+	}
+
+	#[test]
+	fn ops_from_bytes_replay_against_state() {
+		let ops = ops_from_bytes(b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09");
+		assert!(!ops.is_empty());
+		check(&mut State::default(), &ops).unwrap();
+	}
+}
@@ -0,0 +1,51 @@
+use crate::State;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+std::thread_local! {
+	static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+/// A handle to the `State` of the current thread, as returned by
+/// [`thread_local`]. Each thread has its own `State`, which is created empty
+/// on first use and dropped when the thread exits.
+///
+/// The handle cannot be sent to other threads, since it always refers to the
+/// `State` of the thread it is used on.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadLocalState {
+	marker: PhantomData<*const State>,
+}
+
+/// Returns a handle to the `State` of the current thread, for per-thread
+/// caches and similar values that need no locking.
+pub fn thread_local() -> ThreadLocalState {
+	ThreadLocalState {
+		marker: PhantomData,
+	}
+}
+
+impl ThreadLocalState {
+	/// Calls `f` with the `State` of the current thread and returns its
+	/// result.
+	///
+	/// # Panics
+	///
+	/// If the `State` is currently borrowed mutably by an enclosing call to
+	/// [`with_mut`](ThreadLocalState::with_mut).
+	pub fn with<R>(&self, f: impl FnOnce(&State) -> R) -> R {
+		STATE.with(|state| f(&state.borrow()))
+	}
+
+	/// Calls `f` with the `State` of the current thread mutably and returns
+	/// its result.
+	///
+	/// # Panics
+	///
+	/// If the `State` is currently borrowed by an enclosing call to
+	/// [`with`](ThreadLocalState::with) or
+	/// [`with_mut`](ThreadLocalState::with_mut).
+	pub fn with_mut<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+		STATE.with(|state| f(&mut state.borrow_mut()))
+	}
+}
@@ -0,0 +1,260 @@
+use crate::State;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::Location;
+use std::time::Duration;
+use std::time::Instant;
+
+/// A borrow made through [`State::borrow_tracked`] or
+/// [`State::borrow_mut_tracked`], as listed by [`State::borrow_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowRecord {
+	/// The name of the borrowed type.
+	pub name: &'static str,
+	/// Where the borrow was made.
+	pub location: &'static Location<'static>,
+	/// Whether the value was borrowed mutably.
+	pub mutable: bool,
+	/// How long the borrow was held, or `None` if it has not been released.
+	pub held: Option<Duration>,
+}
+
+/// The borrows recorded for a `State`, shared behind an `Rc` so guards can
+/// record their release while the `State` is mutably borrowed.
+#[derive(Default, Clone)]
+pub(crate) struct Tracker(Rc<RefCell<Records>>);
+
+/// The number of released borrows kept for the report. Older ones are
+/// forgotten as new borrows are made.
+const CAPACITY: usize = 256;
+
+/// The recorded borrows, each with an id that stays valid when released
+/// borrows are cleared.
+#[derive(Default)]
+struct Records {
+	next: usize,
+	list: VecDeque<(usize, BorrowRecord)>,
+}
+
+impl Tracker {
+	fn start<T: 'static>(&self, location: &'static Location<'static>, mutable: bool) -> Release {
+		let mut records = self.0.borrow_mut();
+		let id = records.next;
+		records.next += 1;
+		if records.list.len() >= CAPACITY {
+			// Borrows that are still held are kept, as there are only as
+			// many of them as there are live guards.
+			if let Some(oldest) = records.list.iter().position(|(_, r)| r.held.is_some()) {
+				records.list.remove(oldest);
+			}
+		}
+		records.list.push_back((
+			id,
+			BorrowRecord {
+				name: type_name::<T>(),
+				location,
+				mutable,
+				held: None,
+			},
+		));
+		Release {
+			tracker: self.clone(),
+			id,
+			start: Instant::now(),
+		}
+	}
+
+	/// Forgets every recorded borrow.
+	pub(crate) fn clear(&self) {
+		self.0.borrow_mut().list.clear();
+	}
+
+	fn records(&self) -> impl Iterator<Item = BorrowRecord> + '_ {
+		let records = self.0.borrow();
+		(0..records.list.len()).map(move |i| records.list[i].1)
+	}
+}
+
+/// Records the duration of a borrow when dropped.
+struct Release {
+	tracker: Tracker,
+	id: usize,
+	start: Instant,
+}
+
+impl Drop for Release {
+	fn drop(&mut self) {
+		let held = self.start.elapsed();
+		let mut records = self.tracker.0.borrow_mut();
+		let record = records.list.iter_mut().rev().find(|(id, _)| *id == self.id);
+		if let Some((_, record)) = record {
+			trace!(
+				" releasing {} borrowed at {} after {:?}",
+				record.name,
+				record.location,
+				held
+			);
+			record.held = Some(held);
+		}
+	}
+}
+
+/// A shared borrow of a value in a `State` that records how long it is held.
+pub struct Tracked<'a, T> {
+	value: &'a T,
+	_release: Release,
+}
+
+/// A mutable borrow of a value in a `State` that records how long it is
+/// held.
+pub struct TrackedMut<'a, T> {
+	value: &'a mut T,
+	_release: Release,
+}
+
+impl State {
+	/// Borrows a value from the `State` storage like [`borrow`](State::borrow),
+	/// recording the caller and how long the returned guard is held.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`.
+	#[track_caller]
+	pub fn borrow_tracked<T: 'static>(&self) -> Tracked<'_, T> {
+		let location = Location::caller();
+		let value = self.borrow();
+		Tracked {
+			value,
+			_release: self.tracker.start::<T>(location, false),
+		}
+	}
+
+	/// Mutably borrows a value from the `State` storage like
+	/// [`borrow_mut`](State::borrow_mut), recording the caller and how long
+	/// the returned guard is held.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`.
+	#[track_caller]
+	pub fn borrow_mut_tracked<T: 'static>(&mut self) -> TrackedMut<'_, T> {
+		let location = Location::caller();
+		let tracker = self.tracker.clone();
+		let value = self.borrow_mut();
+		TrackedMut {
+			value,
+			_release: tracker.start::<T>(location, true),
+		}
+	}
+
+	/// Returns the tracked borrows made from this `State`, in the order the
+	/// borrows were made. Only the most recent 256 borrows are kept, besides
+	/// those that are still held.
+	pub fn borrow_report(&self) -> Vec<BorrowRecord> {
+		self.tracker.records().collect()
+	}
+
+	/// Returns the tracked borrows that are still held, or that were held
+	/// for longer than `threshold`.
+	pub fn long_borrows(&self, threshold: Duration) -> Vec<BorrowRecord> {
+		self.tracker
+			.records()
+			.filter(|r| r.held.is_none_or(|held| held > threshold))
+			.collect()
+	}
+
+	/// Forgets every tracked borrow that has been released.
+	pub fn clear_borrow_report(&self) {
+		self.tracker
+			.0
+			.borrow_mut()
+			.list
+			.retain(|(_, r)| r.held.is_none());
+	}
+}
+
+impl<T> Deref for Tracked<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		self.value
+	}
+}
+
+impl<T> Deref for TrackedMut<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		self.value
+	}
+}
+
+impl<T> DerefMut for TrackedMut<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		self.value
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tracked<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.value.fmt(f)
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for TrackedMut<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.value.fmt(f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn records_borrows_until_released() {
+		let mut state = State::default();
+		state.put(1u8);
+		let guard = state.borrow_tracked::<u8>();
+		assert_eq!(*guard, 1);
+		let report = state.borrow_report();
+		assert_eq!(report.len(), 1);
+		assert_eq!(
+			(report[0].name, report[0].mutable, report[0].held),
+			("u8", false, None)
+		);
+		assert_eq!(state.long_borrows(Duration::MAX).len(), 1);
+		drop(guard);
+		*state.borrow_mut_tracked::<u8>() += 1;
+		let report = state.borrow_report();
+		assert!(report.iter().all(|r| r.held.is_some()));
+		assert!(report[1].mutable);
+		assert!(state.long_borrows(Duration::MAX).is_empty());
+		state.clear_borrow_report();
+		assert!(state.borrow_report().is_empty());
+	}
+
+	#[test]
+	fn keeps_only_recent_released_borrows() {
+		let mut state = State::default();
+		state.put(1u8);
+		let held = state.borrow_tracked::<u8>();
+		for _ in 0..CAPACITY * 2 {
+			state.borrow_tracked::<u8>();
+		}
+		let report = state.borrow_report();
+		assert_eq!(report.len(), CAPACITY);
+		assert_eq!(report[0].held, None);
+		drop(held);
+		assert!(state.borrow_report()[0].held.is_some());
+
+		state.clear();
+		assert!(state.borrow_report().is_empty());
+	}
+}
@@ -0,0 +1,68 @@
+use crate::State;
+use alloc::boxed::Box;
+
+impl State {
+	/// Puts a trait object into the `State` storage, keyed by the trait
+	/// rather than by the concrete type, such as
+	/// `state.put_trait::<dyn Logger>(Box::new(MyLogger))`. Consumers can
+	/// then depend on the interface with [`borrow_trait`](State::borrow_trait).
+	///
+	/// The value is stored as a `Box<T>`, so putting a `Box<dyn Logger>`
+	/// directly is equivalent.
+	pub fn put_trait<T: ?Sized + 'static>(&mut self, t: Box<T>) {
+		self.put(t);
+	}
+
+	/// Determines if a trait object for the trait `T` exists in `State`
+	/// storage.
+	pub fn has_trait<T: ?Sized + 'static>(&self) -> bool {
+		self.has::<Box<T>>()
+	}
+
+	/// Tries to borrow the trait object put for the trait `T`.
+	pub fn try_borrow_trait<T: ?Sized + 'static>(&self) -> Option<&T> {
+		self.try_borrow::<Box<T>>().map(|b| &**b)
+	}
+
+	/// Borrows the trait object put for the trait `T`.
+	///
+	/// # Panics
+	///
+	/// If no trait object for `T` is present in `State`.
+	#[track_caller]
+	pub fn borrow_trait<T: ?Sized + 'static>(&self) -> &T {
+		self.borrow::<Box<T>>()
+	}
+
+	/// Tries to mutably borrow the trait object put for the trait `T`.
+	pub fn try_borrow_trait_mut<T: ?Sized + 'static>(&mut self) -> Option<&mut T> {
+		self.try_borrow_mut::<Box<T>>().map(|b| &mut **b)
+	}
+
+	/// Mutably borrows the trait object put for the trait `T`.
+	///
+	/// # Panics
+	///
+	/// If no trait object for `T` is present in `State`.
+	#[track_caller]
+	pub fn borrow_trait_mut<T: ?Sized + 'static>(&mut self) -> &mut T {
+		self.borrow_mut::<Box<T>>()
+	}
+
+	/// Tries to move the trait object put for the trait `T` out of the
+	/// `State` storage.
+	pub fn try_take_trait<T: ?Sized + 'static>(&mut self) -> Option<Box<T>> {
+		self.try_take()
+	}
+
+	/// Moves the trait object put for the trait `T` out of the `State`
+	/// storage.
+	///
+	/// # Panics
+	///
+	/// If no trait object for `T` is present in `State`.
+	#[track_caller]
+	pub fn take_trait<T: ?Sized + 'static>(&mut self) -> Box<T> {
+		self.take()
+	}
+}
@@ -0,0 +1,336 @@
+use crate::evict::Eviction;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateError;
+use crate::StateRead;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use core::any::Any;
+use core::any::TypeId;
+
+/// A set of mutations to a `State` that is applied all at once, as created
+/// by [`State::transaction`].
+///
+/// Mutations are applied to the `State` as they are made, and the original
+/// contents of every type they touch are recorded in an undo log, so they
+/// can be restored with all of their metadata, such as teardown callbacks
+/// and time-to-live, if the transaction is rolled back.
+#[derive(Debug)]
+pub struct Transaction<'s> {
+	state: &'s mut State,
+	/// The original contents of every type touched by the transaction, as of
+	/// the first time it was touched.
+	undo: BTreeMap<TypeId, Undo>,
+	/// The eviction settings of the `State`, which are suspended while the
+	/// transaction is open, so it never drops values it cannot restore.
+	eviction: Option<Eviction>,
+}
+
+/// How the original contents of a type are restored on rollback.
+#[derive(Debug)]
+enum Undo {
+	/// No value was present.
+	Absent,
+	/// The value was replaced or removed, and its slot is kept as it was.
+	Slot(Box<Slot>),
+	/// The value is mutated in place, and a copy of it is kept.
+	Value(Box<dyn Any>),
+}
+
+impl State {
+	/// Runs `f` with a [`Transaction`] and keeps its mutations if `f` returns
+	/// `Ok`. If `f` returns `Err` or panics, the mutations are rolled back and
+	/// the `State` is left as it was, so invariants that span several values,
+	/// such as a pool and its metrics, are preserved.
+	///
+	/// Change hooks registered with [`on_change`](State::on_change) are
+	/// called as the mutations are made, and again as they are rolled back.
+	///
+	/// A `State` created with [`with_limit`](State::with_limit) evicts no
+	/// values while the transaction is open, and enforces its limit once the
+	/// transaction is committed.
+	///
+	/// ```ignore
+	/// state.transaction(|tx| {
+	///     tx.put(new_pool);
+	///     tx.remove::<PoolMetrics>()?;
+	///     Ok::<_, StateError>(())
+	/// })?;
+	/// ```
+	pub fn transaction<R, E>(
+		&mut self,
+		f: impl FnOnce(&mut Transaction<'_>) -> Result<R, E>,
+	) -> Result<R, E> {
+		trace!(" starting state transaction");
+		let eviction = self.eviction.take();
+		let mut tx = Transaction {
+			state: self,
+			undo: BTreeMap::new(),
+			eviction,
+		};
+		let result = f(&mut tx)?;
+		trace!(" committing {} state changes", tx.undo.len());
+		tx.undo.clear();
+		drop(tx);
+		self.evict(None);
+		Ok(result)
+	}
+}
+
+impl Transaction<'_> {
+	/// Records the slot that a mutation replaced or removed, unless the type
+	/// was touched before. A slot that was mutated in place is restored with
+	/// its metadata and the copy of its original value.
+	fn record(&mut self, type_id: TypeId, replaced: Option<Slot>) {
+		let Some(undo) = self.undo.get_mut(&type_id) else {
+			self.undo.insert(
+				type_id,
+				replaced.map_or(Undo::Absent, |slot| Undo::Slot(Box::new(slot))),
+			);
+			return;
+		};
+		if let (Some(mut slot), Undo::Value(_)) = (replaced, &*undo) {
+			if let Undo::Value(value) = core::mem::replace(undo, Undo::Absent) {
+				slot.set_value(value);
+			}
+			*undo = Undo::Slot(Box::new(slot));
+		}
+	}
+
+	/// Checks that the value of type `T` may be mutated or removed, and
+	/// copies it if the original has to be kept for a rollback.
+	fn copy_original<T: 'static>(&mut self) -> Result<Option<Box<dyn Any>>, StateError> {
+		let type_id = TypeId::of::<T>();
+		self.state.expire(type_id);
+		if self.state.is_frozen(type_id) {
+			return Err(StateError::frozen::<T>());
+		}
+		self.state.force(type_id);
+		let slot = self
+			.state
+			.data
+			.get(&type_id)
+			.ok_or_else(StateError::missing::<T>)?;
+		if self.undo.contains_key(&type_id) {
+			return Ok(None);
+		}
+		slot.clone_value()
+			.map(Some)
+			.ok_or_else(|| StateError::NotCloneable {
+				names: vec![slot.name],
+			})
+	}
+
+	/// Puts a value, overwriting the value of the same type.
+	///
+	/// # Panics
+	///
+	/// If the existing value of type `T` has been frozen.
+	pub fn put<T: 'static>(&mut self, t: T) {
+		let type_id = TypeId::of::<T>();
+		let replaced = self.state.insert_slot(type_id, Slot::new(t));
+		self.record(type_id, replaced);
+	}
+
+	/// Determines if a value of type `T` is present.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.state.has::<T>()
+	}
+
+	/// Tries to borrow the value of type `T`.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		self.state.try_borrow()
+	}
+
+	/// Tries to mutably borrow the value of type `T`. The value is copied
+	/// first, so the mutation can be rolled back, which requires it to have
+	/// been put with [`put_cloneable`](State::put_cloneable), unless it was
+	/// put or borrowed mutably earlier in the transaction.
+	pub fn try_borrow_mut<T: 'static>(&mut self) -> Result<&mut T, StateError> {
+		let type_id = TypeId::of::<T>();
+		if let Some(value) = self.copy_original::<T>()? {
+			self.undo.insert(type_id, Undo::Value(value));
+		}
+		self.state
+			.try_borrow_mut()
+			.ok_or_else(StateError::missing::<T>)
+	}
+
+	/// Removes the value of type `T`, and returns it. The value is copied
+	/// first, so the removal can be rolled back, which requires it to have
+	/// been put with [`put_cloneable`](State::put_cloneable), unless it was
+	/// put or borrowed mutably earlier in the transaction.
+	pub fn take<T: 'static>(&mut self) -> Result<T, StateError> {
+		let type_id = TypeId::of::<T>();
+		let original = self.copy_original::<T>()?;
+		let mut slot = self
+			.state
+			.remove_slot(type_id)
+			.ok_or_else(StateError::missing::<T>)?;
+		let value = slot
+			.take_value()
+			.and_then(|b| b.downcast().ok())
+			.expect("slot holds its type");
+		match original {
+			Some(original) => {
+				slot.set_value(original);
+				self.undo.insert(type_id, Undo::Slot(Box::new(slot)));
+			},
+			None => self.record(type_id, Some(slot)),
+		}
+		Ok(*value)
+	}
+
+	/// Removes the value of type `T`, returning whether one was present.
+	/// Unlike [`take`](Transaction::take), this works for every value, as
+	/// the value is kept for a rollback.
+	pub fn remove<T: 'static>(&mut self) -> Result<bool, StateError> {
+		let type_id = TypeId::of::<T>();
+		self.state.expire(type_id);
+		if self.state.is_frozen(type_id) {
+			return Err(StateError::frozen::<T>());
+		}
+		let removed = self.state.remove_slot(type_id);
+		let present = removed.is_some();
+		self.record(type_id, removed);
+		Ok(present)
+	}
+}
+
+impl Drop for Transaction<'_> {
+	/// Rolls back the mutations that have not been committed, and resumes
+	/// eviction.
+	fn drop(&mut self) {
+		let undo = core::mem::take(&mut self.undo);
+		if !undo.is_empty() {
+			trace!(" rolling back {} state changes", undo.len());
+		}
+		for (type_id, undo) in undo {
+			match undo {
+				Undo::Absent => drop(self.state.remove_slot(type_id)),
+				Undo::Slot(slot) => drop(self.state.insert_slot(type_id, *slot)),
+				Undo::Value(value) => {
+					if let Some(slot) = self.state.data.get_mut(&type_id) {
+						slot.set_value(value);
+					}
+				},
+			}
+		}
+		self.state.eviction = self.eviction.take();
+	}
+}
+
+impl StateRead for Transaction<'_> {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		Transaction::try_borrow(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::EvictionPolicy;
+	use crate::Limit;
+	use crate::TeardownOrder;
+	use alloc::rc::Rc;
+	use core::cell::Cell;
+
+	struct Connection(u32);
+
+	#[test]
+	fn commit_keeps_mutations() {
+		let mut state = State::default();
+		state.put(Connection(1));
+		state.put_cloneable(1u8);
+		state
+			.transaction(|tx| {
+				tx.put(Connection(2));
+				*tx.try_borrow_mut::<u8>()? += 1;
+				assert_eq!(tx.try_borrow::<Connection>().unwrap().0, 2);
+				Ok::<_, StateError>(())
+			})
+			.unwrap();
+		assert_eq!(state.borrow::<Connection>().0, 2);
+		assert_eq!(state.borrow::<u8>(), &2);
+	}
+
+	#[test]
+	fn error_rolls_back_with_metadata() {
+		let torn_down = Rc::new(Cell::new(false));
+		let mut state = State::default();
+		let flag = torn_down.clone();
+		state.put_with_teardown(Connection(1), move |_| flag.set(true));
+		state.put_cloneable(1u8);
+		let result = state.transaction(|tx| {
+			tx.remove::<Connection>()?;
+			tx.put(Connection(2));
+			*tx.try_borrow_mut::<u8>()? = 5;
+			assert_eq!(tx.take::<u8>()?, 5);
+			tx.put(2u16);
+			Err::<(), _>(StateError::missing::<()>())
+		});
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<Connection>().0, 1);
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert!(!state.has::<u16>());
+		state.shutdown(TeardownOrder::Reverse);
+		assert!(torn_down.get());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn panic_rolls_back() {
+		let mut state = State::default();
+		state.put(Connection(1));
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			let _ = state.transaction(|tx| {
+				tx.put(Connection(2));
+				panic!("failed halfway");
+				#[allow(unreachable_code)]
+				Ok::<_, StateError>(())
+			});
+		}));
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<Connection>().0, 1);
+	}
+
+	#[test]
+	fn values_that_cannot_be_restored_are_refused() {
+		let mut state = State::default();
+		state.put(Connection(1));
+		let result = state.transaction(|tx| tx.take::<Connection>().map(|_| ()));
+		assert!(matches!(result, Err(StateError::NotCloneable { .. })));
+		let result = state.transaction(|tx| {
+			tx.put(Connection(2));
+			tx.try_borrow_mut::<Connection>()?.0 = 3;
+			tx.take::<Connection>()
+		});
+		assert_eq!(result.unwrap().0, 3);
+		assert!(!state.has::<Connection>());
+	}
+
+	#[test]
+	fn rollback_restores_values_of_a_limited_state() {
+		let mut state = State::with_limit(Limit::Entries(2), EvictionPolicy::Fifo);
+		state.put_evictable(1u8);
+		state.put_evictable(2u16);
+		let result = state.transaction(|tx| {
+			tx.put(3u32);
+			tx.put(4u64);
+			Err::<(), _>(StateError::missing::<()>())
+		});
+		assert!(result.is_err());
+		assert_eq!(state.borrow::<u8>(), &1);
+		assert_eq!(state.borrow::<u16>(), &2);
+		assert!(!state.has::<u32>() && !state.has::<u64>());
+		state
+			.transaction(|tx| {
+				tx.put(3u32);
+				Ok::<_, StateError>(())
+			})
+			.unwrap();
+		assert!(!state.has::<u8>());
+		assert!(state.has::<u16>() && state.has::<u32>());
+	}
+}
@@ -0,0 +1,107 @@
+use crate::State;
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+impl State {
+	/// Moves the value of type `T` from this `State` into `dest`, together
+	/// with whatever was recorded when it was put, such as a clone function,
+	/// a teardown callback, a priority or a time-to-live. Returns whether a
+	/// value was present.
+	///
+	/// # Panics
+	///
+	/// If the value of type `T` has been frozen in either container.
+	pub fn transfer<T: 'static>(&mut self, dest: &mut State) -> bool {
+		self.transfer_by_type_id(TypeId::of::<T>(), dest)
+	}
+
+	/// Moves the value with the given `TypeId` from this `State` into `dest`
+	/// without needing to name its type. Returns whether a value was present.
+	///
+	/// # Panics
+	///
+	/// If the value has been frozen in either container.
+	pub fn transfer_by_type_id(&mut self, type_id: TypeId, dest: &mut State) -> bool {
+		trace!(" transferring state data for type_id `{:?}`", type_id);
+		self.expire(type_id);
+		self.assert_not_frozen(type_id);
+		dest.assert_not_frozen(type_id);
+		match self.remove_slot(type_id) {
+			Some(slot) => {
+				dest.insert_slot(type_id, slot);
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Moves every value that has not been frozen from this `State` into
+	/// `dest`, overwriting values of the same types. Returns the number of
+	/// values moved.
+	///
+	/// # Panics
+	///
+	/// If a value to be moved has been frozen in `dest`, in which case
+	/// nothing is moved.
+	pub fn transfer_all(&mut self, dest: &mut State) -> usize {
+		trace!(" transferring all state data");
+		let type_ids: Vec<_> = self
+			.data
+			.iter()
+			.filter(|(_, slot)| !slot.frozen)
+			.map(|(type_id, _)| *type_id)
+			.collect();
+		for type_id in &type_ids {
+			dest.assert_not_frozen(*type_id);
+		}
+		type_ids
+			.into_iter()
+			.filter(|type_id| self.transfer_by_type_id(*type_id, dest))
+			.count()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::TeardownOrder;
+	use alloc::rc::Rc;
+	use core::cell::Cell;
+
+	#[test]
+	fn transfer_moves_metadata() {
+		let torn_down = Rc::new(Cell::new(false));
+		let mut source = State::default();
+		let flag = torn_down.clone();
+		source.put_with_teardown(1u8, move |_| flag.set(true));
+		source.set_priority::<u8>(4);
+		let mut dest = State::default();
+		assert!(source.transfer::<u8>(&mut dest));
+		assert!(!source.transfer::<u8>(&mut dest));
+		assert_eq!(dest.priority::<u8>(), Some(4));
+		dest.shutdown(TeardownOrder::Reverse);
+		assert!(torn_down.get());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn frozen_destination_keeps_the_value() {
+		let mut source = State::default();
+		source.put(1u8);
+		source.put(2u16);
+		let mut dest = State::default();
+		dest.put(3u16);
+		dest.freeze_type::<u16>();
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			source.transfer::<u16>(&mut dest);
+		}));
+		assert!(result.is_err());
+		assert_eq!(source.borrow::<u16>(), &2);
+		let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+			source.transfer_all(&mut dest);
+		}));
+		assert!(result.is_err());
+		assert_eq!(source.len(), 2);
+		assert_eq!(dest.borrow::<u16>(), &3);
+	}
+}
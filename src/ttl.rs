@@ -0,0 +1,98 @@
+use crate::keyed;
+use crate::slot::Slot;
+use crate::State;
+use crate::StateEvent;
+use core::any::TypeId;
+use std::time::Duration;
+use std::time::Instant;
+
+impl State {
+	/// Puts a value into the `State` storage that expires once `ttl` has
+	/// elapsed. Expired values are treated as absent by the accessors, and
+	/// are dropped on the next mutable access or by
+	/// [`purge_expired`](State::purge_expired).
+	pub fn put_with_ttl<T: 'static>(&mut self, t: T, ttl: Duration) {
+		let type_id = TypeId::of::<T>();
+		trace!(
+			" inserting expiring record to state for type_id `{:?}`",
+			type_id
+		);
+		let mut slot = Slot::new(t);
+		slot.expires = Instant::now().checked_add(ttl);
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Puts a value into the `State` storage under the key type `K`, like
+	/// [`put_keyed`](State::put_keyed), that expires once `ttl` has elapsed.
+	pub fn put_keyed_with_ttl<K: 'static, T: 'static>(&mut self, t: T, ttl: Duration) {
+		let type_id = keyed::keyed_type_id::<K, T>();
+		trace!(
+			" inserting expiring keyed record to state for type_id `{:?}`",
+			type_id
+		);
+		let mut slot = keyed::keyed_slot::<K, T>(t);
+		slot.expires = Instant::now().checked_add(ttl);
+		self.insert_slot(type_id, slot);
+	}
+
+	/// Drops every value whose time-to-live has elapsed. Returns the number
+	/// of values dropped.
+	pub fn purge_expired(&mut self) -> usize {
+		trace!(" purging expired state data");
+		let len = self.data.len();
+		let hooks = &self.hooks;
+		self.data.retain(|type_id, slot| {
+			if !slot.is_expired() {
+				return true;
+			}
+			hooks.emit(StateEvent::Removed {
+				type_id: *type_id,
+				name: slot.name,
+			});
+			false
+		});
+		len - self.data.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::String;
+	use core::panic::AssertUnwindSafe;
+	use std::panic::catch_unwind;
+
+	const HOUR: Duration = Duration::from_secs(3600);
+
+	struct Primary;
+
+	#[test]
+	fn values_expire_after_their_ttl() {
+		let mut state = State::default();
+		state.put_with_ttl(1u8, HOUR);
+		state.put_with_ttl(1u16, Duration::ZERO);
+		state.put_keyed_with_ttl::<Primary, _>(1u32, Duration::ZERO);
+		state.put(1u64);
+		assert_eq!(state.try_borrow::<u8>(), Some(&1));
+		assert!(!state.has::<u16>());
+		assert!(state.try_borrow_keyed::<Primary, u32>().is_none());
+		assert_eq!(state.purge_expired(), 2);
+		assert_eq!(state.purge_expired(), 0);
+		assert_eq!(state.len(), 2);
+	}
+
+	#[test]
+	fn borrowing_an_expired_value_panics_as_missing() {
+		let mut state = State::default();
+		state.put_with_ttl(1u16, Duration::ZERO);
+		let error = catch_unwind(AssertUnwindSafe(|| {
+			state.borrow::<u16>();
+		}))
+		.unwrap_err();
+		let message = error.downcast_ref::<String>().unwrap();
+		assert!(
+			message.contains("required type u16 is not present"),
+			"{message}"
+		);
+	}
+}
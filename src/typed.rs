@@ -0,0 +1,130 @@
+/// Declares a state container with one field per type, for a set of types
+/// that is fixed at compile time.
+///
+/// The fields are plain struct fields, so they can be accessed statically,
+/// and the generated struct implements [`StateRead`](crate::StateRead) and
+/// [`StateWrite`](crate::StateWrite), so it can be used wherever a dynamic
+/// [`State`](crate::State) is accepted generically. Every field type must be
+/// `'static`, and distinct, or the struct does not compile:
+///
+/// ```compile_fail
+/// gotham_state::typed_state! {
+///     struct Ports {
+///         http: u16,
+///         https: u16,
+///     }
+/// }
+/// ```
+///
+/// ```ignore
+/// typed_state! {
+///     #[derive(Default)]
+///     pub struct AppState {
+///         pub config: Config,
+///         pub counter: Counter,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! typed_state {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident {
+			$($field_vis:vis $field:ident: $ty:ty),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		$vis struct $name {
+			$($field_vis $field: $ty,)*
+		}
+
+		// A type listed twice implements the trait twice, which is rejected,
+		// as the field declared later would never be reached.
+		const _: () = {
+			#[allow(dead_code)]
+			trait DistinctFieldType {}
+			$(impl DistinctFieldType for $ty {})*
+		};
+
+		impl $crate::StateRead for $name {
+			fn try_borrow<T: 'static>(&self) -> ::core::option::Option<&T> {
+				$(
+					if let ::core::option::Option::Some(value) =
+						(&self.$field as &dyn ::core::any::Any).downcast_ref::<T>()
+					{
+						return ::core::option::Option::Some(value);
+					}
+				)*
+				::core::option::Option::None
+			}
+		}
+
+		impl $crate::StateWrite for $name {
+			fn put<T: 'static>(&mut self, t: T) {
+				let mut value = ::core::option::Option::Some(t);
+				$(
+					if let ::core::option::Option::Some(value) = (&mut value
+						as &mut dyn ::core::any::Any)
+						.downcast_mut::<::core::option::Option<$ty>>()
+					{
+						self.$field = value.take().expect("value is present");
+						return;
+					}
+				)*
+				panic!(
+					"type {} is not declared in {}",
+					::core::any::type_name::<T>(),
+					::core::stringify!($name)
+				);
+			}
+
+			fn try_borrow_mut<T: 'static>(&mut self) -> ::core::option::Option<&mut T> {
+				$(
+					if let ::core::option::Option::Some(value) =
+						(&mut self.$field as &mut dyn ::core::any::Any).downcast_mut::<T>()
+					{
+						return ::core::option::Option::Some(value);
+					}
+				)*
+				::core::option::Option::None
+			}
+		}
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::State;
+	use crate::StateRead;
+	use crate::StateWrite;
+
+	#[derive(Debug, Default, PartialEq)]
+	struct Config(u32);
+
+	crate::typed_state! {
+		#[derive(Default)]
+		struct AppState {
+			config: Config,
+			counter: u64,
+		}
+	}
+
+	fn bump<S: StateWrite>(state: &mut S) {
+		*state.try_borrow_mut::<u64>().unwrap() += 1;
+	}
+
+	#[test]
+	fn fields_are_accessed_by_type() {
+		let mut typed = AppState::default();
+		typed.put(Config(1));
+		bump(&mut typed);
+		assert_eq!(typed.try_borrow::<Config>(), Some(&Config(1)));
+		assert_eq!(typed.counter, 1);
+		assert_eq!(typed.try_borrow::<u8>(), None);
+
+		let mut state = State::default();
+		state.put(0u64);
+		bump(&mut state);
+		assert_eq!(state.borrow::<u64>(), &1);
+	}
+}
@@ -0,0 +1,151 @@
+use crate::State;
+use core::marker::PhantomData;
+
+/// The end of a type-level list of the types given to a
+/// [`TypedStateBuilder`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HNil;
+
+/// A type-level list of the types given to a [`TypedStateBuilder`], with `H`
+/// given last.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HCons<H, T>(H, T);
+
+/// The index of a type that is the head of an [`HCons`] list.
+pub struct Here;
+
+/// The index of a type in the tail of an [`HCons`] list.
+pub struct There<I>(PhantomData<I>);
+
+/// Implemented by lists that contain `T` at index `I`. The index is inferred,
+/// and inference fails if `T` is present more than once.
+pub trait Contains<T, I> {}
+
+impl<T, Tail> Contains<T, Here> for HCons<T, Tail> {}
+
+impl<T, H, Tail: Contains<T, I>, I> Contains<T, There<I>> for HCons<H, Tail> {}
+
+/// Implemented by lists that contain every type in the list `R`, at the
+/// indices `I`.
+pub trait ContainsAll<R, I> {}
+
+impl<L> ContainsAll<HNil, HNil> for L {}
+
+impl<L, T, Rest, I, Is> ContainsAll<HCons<T, Rest>, HCons<I, Is>> for L where
+	L: Contains<T, I> + ContainsAll<Rest, Is>
+{
+}
+
+/// Implemented by lists whose values can be put into a `State`.
+pub trait PutAll {
+	/// Puts the values into `state`, last given first.
+	fn put_all(self, state: &mut State);
+}
+
+impl PutAll for HNil {
+	fn put_all(self, _: &mut State) {}
+}
+
+impl<H: 'static, T: PutAll> PutAll for HCons<H, T> {
+	fn put_all(self, state: &mut State) {
+		state.put(self.0);
+		self.1.put_all(state);
+	}
+}
+
+/// A builder for a `State` that tracks the types of its values in its type
+/// parameters, so mistakes are caught at compile time: giving a value of the
+/// same type twice fails to compile, and so does calling
+/// [`build`](TypedStateBuilder::build) when a type declared with
+/// [`require`](TypedStateBuilder::require) was never given.
+///
+/// `P` lists the types given so far and `R` the required types, as
+/// [`HCons`] lists.
+///
+/// ```
+/// use gotham_state::State;
+///
+/// let state = State::typed_builder()
+///     .require::<u16>()
+///     .with(1u8)
+///     .with(2u16)
+///     .build();
+/// assert_eq!(state.borrow::<u16>(), &2);
+/// ```
+///
+/// A type given twice fails to compile:
+///
+/// ```compile_fail,E0283
+/// use gotham_state::State;
+///
+/// let state = State::typed_builder().with(1u8).with(2u8).build();
+/// ```
+///
+/// And so does a required type that was never given:
+///
+/// ```compile_fail,E0277
+/// use gotham_state::State;
+///
+/// let state = State::typed_builder().require::<u16>().with(1u8).build();
+/// ```
+pub struct TypedStateBuilder<P = HNil, R = HNil> {
+	provided: P,
+	required: PhantomData<R>,
+}
+
+impl State {
+	/// Creates a [`TypedStateBuilder`] for an empty `State`.
+	pub fn typed_builder() -> TypedStateBuilder {
+		TypedStateBuilder::new()
+	}
+}
+
+impl TypedStateBuilder {
+	/// Creates a builder for an empty `State`.
+	pub fn new() -> Self {
+		TypedStateBuilder {
+			provided: HNil,
+			required: PhantomData,
+		}
+	}
+}
+
+impl Default for TypedStateBuilder {
+	fn default() -> Self {
+		TypedStateBuilder::new()
+	}
+}
+
+impl<P, R> TypedStateBuilder<P, R> {
+	/// Gives a value to put into the `State` being built. Fails to compile
+	/// if a value of type `T` was already given.
+	pub fn with<T: 'static, I>(self, t: T) -> TypedStateBuilder<HCons<T, P>, R>
+	where
+		HCons<T, P>: Contains<T, I>,
+	{
+		TypedStateBuilder {
+			provided: HCons(t, self.provided),
+			required: PhantomData,
+		}
+	}
+
+	/// Declares that a value of type `T` must be given before the `State` can
+	/// be built.
+	pub fn require<T: 'static>(self) -> TypedStateBuilder<P, HCons<T, R>> {
+		TypedStateBuilder {
+			provided: self.provided,
+			required: PhantomData,
+		}
+	}
+
+	/// Finishes building and returns the `State`. Fails to compile if a
+	/// required type was never given.
+	pub fn build<I>(self) -> State
+	where
+		P: ContainsAll<R, I> + PutAll,
+	{
+		let mut state = State::default();
+		self.provided.put_all(&mut state);
+		state
+	}
+}
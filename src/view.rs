@@ -0,0 +1,58 @@
+use crate::State;
+use crate::StateRead;
+
+/// A read-only view of a `State`, as returned by [`State::as_view`].
+///
+/// Taking a `StateView` instead of `&State` makes it clear from a function's
+/// signature that it only reads values, as the view does not expose any
+/// method that could mutate the `State`, even through interior mutability.
+#[derive(Debug, Clone, Copy)]
+pub struct StateView<'s> {
+	state: &'s State,
+}
+
+impl State {
+	/// Returns a read-only view of the `State`.
+	pub fn as_view(&self) -> StateView<'_> {
+		StateView { state: self }
+	}
+}
+
+impl<'s> StateView<'s> {
+	/// Determines if a value of type `T` exists in the `State` storage.
+	pub fn has<T: 'static>(&self) -> bool {
+		self.state.has::<T>()
+	}
+
+	/// Tries to borrow a value from the `State` storage.
+	pub fn try_borrow<T: 'static>(&self) -> Option<&'s T> {
+		self.state.try_borrow()
+	}
+
+	/// Borrows a value from the `State` storage.
+	///
+	/// # Panics
+	///
+	/// If a value of type `T` is not present in `State`.
+	#[track_caller]
+	#[allow(clippy::should_implement_trait)]
+	pub fn borrow<T: 'static>(&self) -> &'s T {
+		self.state.borrow()
+	}
+}
+
+impl<'s> From<&'s State> for StateView<'s> {
+	fn from(state: &'s State) -> Self {
+		state.as_view()
+	}
+}
+
+impl StateRead for StateView<'_> {
+	fn try_borrow<T: 'static>(&self) -> Option<&T> {
+		StateView::try_borrow(self)
+	}
+
+	fn has<T: 'static>(&self) -> bool {
+		StateView::has::<T>(self)
+	}
+}
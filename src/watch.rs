@@ -0,0 +1,96 @@
+use crate::lock::Mutex;
+use crate::ConcurrentState;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::any::TypeId;
+use core::fmt;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+
+type Notify = Box<dyn Fn(&Arc<dyn Any + Send + Sync>) -> bool + Send + Sync>;
+
+/// The subscribers registered with [`ConcurrentState::watch`].
+#[derive(Default)]
+pub(crate) struct Watchers(Mutex<Vec<(TypeId, Notify)>>);
+
+impl Watchers {
+	/// Sends `value` to every subscriber of the type with the given `TypeId`,
+	/// dropping the subscribers whose receiver has been dropped.
+	pub(crate) fn notify(&self, type_id: TypeId, value: &Arc<dyn Any + Send + Sync>) {
+		self.0
+			.lock()
+			.retain(|(id, notify)| *id != type_id || notify(value));
+	}
+
+	fn subscribe(&self, type_id: TypeId, notify: Notify) {
+		self.0.lock().push((type_id, notify));
+	}
+}
+
+impl fmt::Debug for Watchers {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Watchers")
+			.field("len", &self.0.lock().len())
+			.finish()
+	}
+}
+
+impl ConcurrentState {
+	/// Subscribes to changes of the value of type `T`. Every time the value
+	/// is replaced with [`put`](ConcurrentState::put),
+	/// [`store`](ConcurrentState::store) or
+	/// [`compare_and_put`](ConcurrentState::compare_and_put), the new value
+	/// is sent to the returned receiver, so consumers such as config
+	/// hot-reload can react without polling.
+	///
+	/// The subscription ends once the receiver is dropped.
+	pub fn watch<T: Any + Send + Sync>(&self) -> Receiver<Arc<T>> {
+		trace!(
+			" watching concurrent state data for type_id `{:?}`",
+			TypeId::of::<T>()
+		);
+		let (sender, receiver) = mpsc::channel();
+		let notify: Notify = Box::new(move |value| {
+			let value = value
+				.clone()
+				.downcast()
+				.expect("value has the watched type");
+			sender.send(value).is_ok()
+		});
+		self.watchers.subscribe(TypeId::of::<T>(), notify);
+		receiver
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn watchers_receive_every_new_value() {
+		let mut state = ConcurrentState::default();
+		let receiver = state.watch::<u32>();
+		let others = state.watch::<u8>();
+		state.put(1u32);
+		state.store(Arc::new(2u32));
+		state.compare_and_put(1, Arc::new(3u32)).unwrap();
+		let values: Vec<_> = receiver.try_iter().map(|value| *value).collect();
+		assert_eq!(values, [1, 2, 3]);
+		assert!(others.try_recv().is_err());
+	}
+
+	#[test]
+	fn failed_writes_are_not_sent() {
+		let mut state = ConcurrentState::default();
+		let receiver = state.watch::<u32>();
+		assert!(state.try_store(Arc::new(1u32)).is_err());
+		state.put(1u32);
+		assert!(state.compare_and_put(5, Arc::new(2u32)).is_err());
+		assert_eq!(receiver.try_iter().count(), 1);
+		drop(receiver);
+		state.put(2u32);
+		assert_eq!(state.watchers.0.lock().len(), 0);
+	}
+}
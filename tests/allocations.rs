@@ -0,0 +1,53 @@
+//! Counts the allocations made by a `State` with the `small` feature, which
+//! keeps its storage inline and only allocates the values themselves. The
+//! diagnostic features that record accesses allocate for their records, so
+//! they are left out.
+#![cfg(not(any(feature = "metrics", feature = "track-borrows")))]
+
+use gotham_state::State;
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+
+struct Counting;
+
+thread_local! {
+	static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// SAFETY: every call is forwarded to the system allocator.
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.with(|count| count.set(count.get() + 1));
+		// SAFETY: the caller upholds the contract of `GlobalAlloc::alloc`.
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		// SAFETY: the caller upholds the contract of `GlobalAlloc::dealloc`.
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+	ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn only_values_are_allocated() {
+	let before = allocations();
+	let mut state = State::default();
+	state.put(1u8);
+	state.put(2u16);
+	state.put(3u32);
+	assert_eq!(allocations() - before, 3);
+
+	assert_eq!(state.take::<u16>(), 2);
+	state.put(4u64);
+	assert_eq!(state.borrow::<u32>(), &3);
+	assert_eq!(allocations() - before, 4);
+}
@@ -0,0 +1,91 @@
+use gotham_state::Extract;
+use gotham_state::State;
+use gotham_state::StateError;
+
+#[derive(Debug, PartialEq)]
+struct Token(u32);
+
+#[derive(Debug, PartialEq)]
+struct Config(&'static str);
+
+#[derive(Extract)]
+struct Deps<'a> {
+	token: Token,
+	config: &'a Config,
+	counter: &'a mut u64,
+}
+
+#[allow(dead_code)]
+#[derive(Extract)]
+struct TakeAndBorrow<'a> {
+	token: Token,
+	again: &'a Token,
+}
+
+#[allow(dead_code)]
+#[derive(Extract)]
+struct BorrowTwice<'a> {
+	first: &'a mut Config,
+	second: &'a Config,
+}
+
+fn deps_state() -> State {
+	let mut state = State::default();
+	state.put(Token(1));
+	state.put(Config("prod"));
+	state.put(0u64);
+	state
+}
+
+#[test]
+fn extracts_every_field() {
+	let mut state = deps_state();
+	let deps = Deps::try_extract(&mut state).unwrap();
+	assert_eq!(deps.token, Token(1));
+	assert_eq!(deps.config, &Config("prod"));
+	*deps.counter += 1;
+	assert!(!state.has::<Token>());
+	assert_eq!(state.borrow::<u64>(), &1);
+}
+
+#[test]
+fn missing_field_takes_nothing() {
+	let mut state = deps_state();
+	state.take::<u64>();
+	let error = Deps::try_extract(&mut state).err().unwrap();
+	assert!(matches!(error, StateError::MissingType { .. }));
+	assert!(state.has::<Token>());
+}
+
+#[test]
+fn frozen_field_takes_nothing() {
+	let mut state = deps_state();
+	state.freeze_type::<u64>();
+	let error = Deps::try_extract(&mut state).err().unwrap();
+	assert_eq!(error, StateError::Frozen { name: "u64" });
+	assert!(state.has::<Token>());
+
+	let mut state = deps_state();
+	state.freeze_type::<Token>();
+	let error = Deps::try_extract(&mut state).err().unwrap();
+	assert!(matches!(error, StateError::Frozen { .. }));
+	assert!(state.has::<Token>());
+}
+
+#[test]
+fn frozen_borrowed_field_is_extracted() {
+	let mut state = deps_state();
+	state.freeze_type::<Config>();
+	assert!(Deps::try_extract(&mut state).is_ok());
+}
+
+#[test]
+fn duplicate_types_take_nothing() {
+	let mut state = deps_state();
+	let error = TakeAndBorrow::try_extract(&mut state).err().unwrap();
+	assert!(matches!(error, StateError::BorrowConflict { .. }));
+	assert!(state.has::<Token>());
+
+	let error = BorrowTwice::try_extract(&mut state).err().unwrap();
+	assert!(matches!(error, StateError::BorrowConflict { .. }));
+}